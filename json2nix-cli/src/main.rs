@@ -1,19 +1,50 @@
 use std::{
-    fs,
-    io::{Read, stdin},
+    fs::{self, File},
+    io::{Read, Write, stdin, stdout},
+    path::{Path, PathBuf},
 };
 
-use json2nix::{Json2NixConfig, json2nix};
+use json2nix::{
+    FlattenSetKeys, InputFormat, Json2NixConfig, Json2NixError, KeyOrder, OutputStyle, StringRewrite, StringStyle, TomlDatetimes,
+    UnrepresentableNumbers, Warning, json2nix_from_slice, json2nix_stream, nix2json, parse_attr_path, parse_rules,
+};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(version, about, bin_name = "json2nix", long_about = None)]
 struct Args {
-    #[arg(index = 1, help = "The input JSON file. Use '-' to read from stdin until EOF.")]
+    #[arg(index = 1, help = "The input file, or Nix file with --reverse. Use '-' to read from stdin until EOF.")]
     input: String,
 
-    #[arg(short, long, help = "The output file. If not specified, the result is printed to stdout.")]
+    #[arg(
+        long,
+        value_enum,
+        help = "The format of the input. If not specified, it is detected from the file extension and defaults to JSON."
+    )]
+    from: Option<InputFormatArg>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = TomlDatetimesArg::String,
+        help = "How to convert datetimes of TOML input."
+    )]
+    toml_datetimes: TomlDatetimesArg,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "Convert a Nix expression to JSON instead. Only literal values without variables or functions are supported."
+    )]
+    reverse: bool,
+
+    #[arg(
+        short,
+        long,
+        help = "The output file, which is only replaced if the conversion succeeds. If not specified, the result is printed to stdout, where a part of it may already be printed when the conversion of large JSON input fails."
+    )]
     output: Option<String>,
 
     #[arg(short, long, default_value_t = 2, help = "The number of spaces for indentation.")]
@@ -24,30 +55,403 @@ struct Args {
 
     #[arg(short, long, default_value_t = false, help = "Compact the keys in sets if they contain only one value.")]
     compact_set_keys: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Flatten all nested sets into fully qualified attribute paths like 'a.b.c = 1;'."
+    )]
+    flatten_set_keys: bool,
+
+    #[arg(
+        long,
+        requires = "flatten_set_keys",
+        help = "Only flatten nested sets until the attribute paths have this number of attributes."
+    )]
+    flatten_max_depth: Option<usize>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Use indented strings ('' ... '') for strings that contain a newline."
+    )]
+    indented_strings: bool,
+
+    #[arg(
+        long,
+        requires = "indented_strings",
+        help = "Also use indented strings for strings that contain more than this number of double quotes."
+    )]
+    double_quote_threshold: Option<usize>,
+
+    #[arg(long, help = "Put lists and sets on one line if they fit into this number of characters.")]
+    max_line_width: Option<usize>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = UnrepresentableNumbersArg::Error,
+        help = "How to handle integers that are out of the range of Nix."
+    )]
+    unrepresentable_numbers: UnrepresentableNumbersArg,
+
+    #[arg(
+        long,
+        help = "Nest the output under this attribute path, e.g. 'programs.git.settings'. It is compacted with --compact-set-keys."
+    )]
+    attr_path: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Wrap the output in a module: '{ config, lib, pkgs, ... }: { ... }'."
+    )]
+    module: bool,
+
+    #[arg(
+        long,
+        help = "A JSON file with rules that wrap values in lib.mkDefault and the like, e.g. '[{ \"pointer\": \"/services/*/enable\", \"action\": \"mkForce\" }]'."
+    )]
+    rules: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Insert the Nix expression of objects like '{ \"$nix\": \"pkgs.hello\" }' as is."
+    )]
+    raw_nix_objects: bool,
+
+    #[arg(
+        long,
+        help = "Insert the rest of strings with this prefix as a Nix expression, e.g. 'nix:' for \"nix:pkgs.hello\"."
+    )]
+    raw_nix_prefix: Option<String>,
+
+    #[arg(
+        long,
+        help = "Replace this home directory and '~' at the start of paths in strings with '${config.home.homeDirectory}', e.g. '/home/alice'."
+    )]
+    rewrite_home: Option<String>,
+
+    #[arg(long, default_value_t = false, help = "Convert strings like './foo' or '../foo' to path literals.")]
+    rewrite_relative_paths: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Replace store paths in strings like '/nix/store/<hash>-hello-2.12/bin/hello' with '${pkgs.hello}/bin/hello'."
+    )]
+    rewrite_store_paths: bool,
+
+    #[arg(
+        long,
+        value_name = "MIN_SIZE",
+        help = "Move lists and sets that occur more than once and contain at least this number of values into a 'let ... in' block."
+    )]
+    extract_repeated: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "DEPTH",
+        help = "Fail if lists and sets are nested deeper than this. By default, JSON input of any depth is converted."
+    )]
+    max_depth: Option<usize>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = KeyOrderArg::Preserve,
+        help = "The order of the attributes in sets."
+    )]
+    key_order: KeyOrderArg,
+
+    #[arg(
+        long,
+        value_name = "KEYS",
+        value_delimiter = ',',
+        help = "Put these attributes in front of all others in this order, e.g. 'enable,package'."
+    )]
+    key_priority: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = StyleArg::Default,
+        help = "The layout rules for the output. 'nixfmt' ignores --indentation and --max-line-width."
+    )]
+    style: StyleArg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum StyleArg {
+    /// Use --indentation and --max-line-width
+    Default,
+    /// Produce the same output as nixfmt
+    Nixfmt,
+}
+
+impl From<StyleArg> for OutputStyle {
+    fn from(value: StyleArg) -> Self {
+        match value {
+            StyleArg::Default => OutputStyle::Default,
+            StyleArg::Nixfmt => OutputStyle::Nixfmt,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum KeyOrderArg {
+    /// Keep the order of the input
+    Preserve,
+    /// Sort by bytes like Nix does
+    Nix,
+    /// Sort like 'nix', but compare numbers by value, so 'item2' comes before 'item10'
+    Natural,
+}
+
+impl From<KeyOrderArg> for KeyOrder {
+    fn from(value: KeyOrderArg) -> Self {
+        match value {
+            KeyOrderArg::Preserve => KeyOrder::Preserve,
+            KeyOrderArg::Nix => KeyOrder::Nix,
+            KeyOrderArg::Natural => KeyOrder::Natural,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum InputFormatArg {
+    Json,
+    /// JSON5 or JSONC, comments are carried over
+    Json5,
+    /// INI or git-config, sections become nested sets
+    Ini,
+    /// XML or binary property list
+    Plist,
+    Yaml,
+    Toml,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TomlDatetimesArg {
+    /// A string like "1979-05-27T07:32:00Z"
+    String,
+    /// An attribute set like { year = 1979; month = 5; day = 27; ... }
+    AttrSet,
+}
+
+impl From<TomlDatetimesArg> for TomlDatetimes {
+    fn from(value: TomlDatetimesArg) -> Self {
+        match value {
+            TomlDatetimesArg::String => TomlDatetimes::String,
+            TomlDatetimesArg::AttrSet => TomlDatetimes::AttrSet,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum UnrepresentableNumbersArg {
+    /// Fail the conversion
+    Error,
+    /// Emit the number as a string
+    String,
+    /// Emit the number as a float and print a warning
+    Float,
+}
+
+impl From<UnrepresentableNumbersArg> for UnrepresentableNumbers {
+    fn from(value: UnrepresentableNumbersArg) -> Self {
+        match value {
+            UnrepresentableNumbersArg::Error => UnrepresentableNumbers::Error,
+            UnrepresentableNumbersArg::String => UnrepresentableNumbers::String,
+            UnrepresentableNumbersArg::Float => UnrepresentableNumbers::Float,
+        }
+    }
 }
 
 fn main() -> Result<(), String> {
     let args = Args::parse();
 
-    let json_result = match args.input.as_str() {
+    if args.reverse {
+        let input = read_input(&args.input)?;
+        let input = String::from_utf8(input).map_err(|err| format!("Could not read the input from '{}' because of: {}", args.input, err))?;
+        let json = nix2json(&input).map_err(|err| error_message(&args.input, err))?;
+        return write_output(args.output.as_deref(), json, "JSON document");
+    }
+
+    let attr_path = match &args.attr_path {
+        Some(attr_path) => parse_attr_path(attr_path).map_err(|err| format!("Could not parse the attribute path '{}': {}", attr_path, err))?,
+        None => Vec::new(),
+    };
+
+    let rules = match &args.rules {
+        Some(file_name) => {
+            let rules = fs::read_to_string(file_name).map_err(|err| format!("Could not read the rules from '{}' because of: {}", file_name, err))?;
+            parse_rules(&rules).map_err(|err| format!("Could not parse the rules from '{}': {}", file_name, err))?
+        }
+        None => Vec::new(),
+    };
+
+    let mut rewrites = Vec::new();
+    if let Some(home_directory) = &args.rewrite_home {
+        rewrites.push(StringRewrite::HomeDirectory(home_directory.clone()));
+    }
+    if args.rewrite_relative_paths {
+        rewrites.push(StringRewrite::RelativePaths);
+    }
+    if args.rewrite_store_paths {
+        rewrites.push(StringRewrite::StorePaths);
+    }
+
+    let config = Json2NixConfig {
+        string_style: match args.indented_strings {
+            true => StringStyle::Indented {
+                double_quote_threshold: args.double_quote_threshold,
+            },
+            false => StringStyle::DoubleQuoted,
+        },
+        flatten_set_keys: match (args.flatten_set_keys, args.flatten_max_depth) {
+            (true, Some(max_depth)) => FlattenSetKeys::MaxDepth(max_depth),
+            (true, None) => FlattenSetKeys::Always,
+            (false, _) => FlattenSetKeys::Never,
+        },
+        unrepresentable_numbers: args.unrepresentable_numbers.into(),
+        max_line_width: args.max_line_width,
+        style: args.style.into(),
+        input_format: input_format(&args),
+        attr_path,
+        module: args.module,
+        rules,
+        raw_nix_objects: args.raw_nix_objects,
+        raw_nix_prefix: args.raw_nix_prefix.clone(),
+        rewrites,
+        extract_repeated: args.extract_repeated,
+        max_depth: args.max_depth,
+        key_order: args.key_order.into(),
+        key_priority: args.key_priority.clone(),
+        ..Json2NixConfig::new(args.initial_indentation, args.indentation, args.compact_set_keys)
+    };
+
+    // large JSON documents are converted while they are read instead of reading them into memory first
+    if config.supports_streaming() {
+        for warning in stream(&args, &config)? {
+            eprintln!("Warning: {}", warning);
+        }
+        return Ok(());
+    }
+
+    let input = read_input(&args.input)?;
+    let (nix, warnings) = json2nix_from_slice(&input, &config).map_err(|err| error_message(&args.input, err))?;
+
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    write_output(args.output.as_deref(), nix, "Nix expression")
+}
+
+fn read_input(input: &str) -> Result<Vec<u8>, String> {
+    let input_result = match input {
         "-" => {
-            let mut input = String::new();
-            stdin().read_to_string(&mut input).map_err(|err| err.to_string()).map(|_| input)
+            let mut input = Vec::new();
+            stdin().read_to_end(&mut input).map_err(|err| err.to_string()).map(|_| input)
         }
-        file_name => fs::read_to_string(file_name).map_err(|err| err.to_string()),
+        file_name => fs::read(file_name).map_err(|err| err.to_string()),
+    };
+
+    input_result.map_err(|err| format!("Could read the input from '{}' because of: {}", input, err))
+}
+
+fn stream(args: &Args, config: &Json2NixConfig) -> Result<Vec<Warning>, String> {
+    let reader: Box<dyn Read> = match args.input.as_str() {
+        "-" => Box::new(stdin().lock()),
+        file_name => Box::new(File::open(file_name).map_err(|err| format!("Could read the input from '{}' because of: {}", file_name, err))?),
     };
 
-    let json = json_result.map_err(|err| format!("Could read the input from '{}' because of: {}", args.input, err))?;
+    match args.output.as_deref() {
+        Some(file_name) => {
+            // the output is written to a temporary file first, so an existing file is kept if the input turns out to be invalid
+            let temporary_file_name = temporary_file_name(file_name);
+            let write_error = |err: std::io::Error| format!("Could not write the generated Nix expression to '{}': {}", file_name, err);
+            let file = File::create(&temporary_file_name).map_err(write_error)?;
 
-    let config = Json2NixConfig::new(args.initial_indentation, args.indentation, args.compact_set_keys);
-    let nix = json2nix(&json, &config).map_err(|err| format!("Could not convert the input to Nix: {}", err))?;
+            match json2nix_stream(reader, file, config) {
+                Ok(warnings) => {
+                    fs::rename(&temporary_file_name, file_name).map_err(write_error)?;
+                    Ok(warnings)
+                }
+                Err(err) => {
+                    let _ = fs::remove_file(&temporary_file_name);
+                    Err(error_message(&args.input, err))
+                }
+            }
+        }
+        None => {
+            let mut stdout = stdout().lock();
+            let warnings = json2nix_stream(reader, &mut stdout, config).map_err(|err| error_message(&args.input, err))?;
+            writeln!(stdout).map_err(|err| format!("Could not write the generated Nix expression: {}", err))?;
+            Ok(warnings)
+        }
+    }
+}
+
+// A hidden file next to `file_name`, so it can be renamed to it without copying.
+fn temporary_file_name(file_name: &str) -> PathBuf {
+    let path = Path::new(file_name);
+    let name = path.file_name().map_or_else(|| "output".into(), |name| name.to_string_lossy());
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+fn input_format(args: &Args) -> InputFormat {
+    let input_format = match args.from {
+        Some(InputFormatArg::Json) => InputFormat::Json,
+        Some(InputFormatArg::Json5) => InputFormat::Json5,
+        Some(InputFormatArg::Ini) => InputFormat::Ini,
+        Some(InputFormatArg::Plist) => InputFormat::Plist,
+        Some(InputFormatArg::Yaml) => InputFormat::Yaml,
+        Some(InputFormatArg::Toml) => InputFormat::Toml {
+            datetimes: TomlDatetimes::default(),
+        },
+        None => Path::new(&args.input)
+            .file_name()
+            .and_then(|file_name| InputFormat::from_file_name(&file_name.to_string_lossy()))
+            .unwrap_or_default(),
+    };
+
+    match input_format {
+        InputFormat::Toml { .. } => InputFormat::Toml {
+            datetimes: args.toml_datetimes.into(),
+        },
+        input_format => input_format,
+    }
+}
+
+fn error_message(input: &str, err: Json2NixError) -> String {
+    match err {
+        Json2NixError::Parse {
+            line,
+            column,
+            category,
+            message,
+        } => format!(
+            "Could not parse the input from '{}' ({}:{}): {}: {}",
+            input, line, column, category, message
+        ),
+        err => format!("Could not convert the input: {}", err),
+    }
+}
 
-    match args.output {
+fn write_output(output: Option<&str>, content: String, description: &str) -> Result<(), String> {
+    match output {
         Some(file_name) => {
-            fs::write(&file_name, nix).map_err(|err| format!("Could not write the generated Nix expression to '{}': {}", file_name, err))?;
+            fs::write(file_name, content).map_err(|err| format!("Could not write the generated {} to '{}': {}", description, file_name, err))?;
         }
         None => {
-            println!("{}", nix)
+            // the nixfmt style already ends with a newline
+            match content.ends_with('\n') {
+                true => print!("{}", content),
+                false => println!("{}", content),
+            }
         }
     };
 
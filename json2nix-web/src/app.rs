@@ -1,10 +1,12 @@
 use crate::checkbox::CheckBox;
 use crate::copy_button::CopyButton;
 use crate::number_input::NumberInput;
-use json2nix::{Json2NixConfig, json2nix};
+use crate::select::Select;
+use crate::text_input::TextInput;
+use codee::string::FromToStringCodec;
+use json2nix::{FlattenSetKeys, InputFormat, Json2NixConfig, Json2NixError, KeyOrder, OutputStyle, StringStyle, TomlDatetimes, json2nix};
 use leptos::prelude::*;
 use leptos_use::{UseCookieOptions, use_cookie_with_options};
-use codee::string::FromToStringCodec;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[wasm_bindgen]
@@ -12,7 +14,7 @@ extern "C" {
     pub fn highlight_nix_code(code: &str) -> String;
 }
 
-const PROJECT_REPOSITORY_URL: &'static str = env!("CARGO_PKG_REPOSITORY");
+const PROJECT_REPOSITORY_URL: &str = env!("CARGO_PKG_REPOSITORY");
 const ONE_YEAR_IN_MILLISECONDS: i64 = 365 * 24 * 60 * 60 * 1000;
 
 #[component]
@@ -26,6 +28,13 @@ pub fn App() -> impl IntoView {
     let initial_indentation = RwSignal::new(0);
     let indentation = RwSignal::new(2);
     let compact_set_keys = RwSignal::new(false);
+    let flatten_set_keys = RwSignal::new(false);
+    let indented_strings = RwSignal::new(false);
+    let max_line_width = RwSignal::new(0);
+    let nixfmt_style = RwSignal::new(false);
+    let input_format = RwSignal::new(InputFormat::Json);
+    let key_order = RwSignal::new(KeyOrder::Preserve);
+    let key_priority = RwSignal::new("".to_string());
 
     let generated_nix_code_result = Signal::derive(move || {
         let json = raw_input.get();
@@ -34,19 +43,37 @@ pub fn App() -> impl IntoView {
             return Ok("".to_string());
         }
 
-        let config = Json2NixConfig::new(initial_indentation.get(), indentation.get(), compact_set_keys.get());
+        let config = Json2NixConfig {
+            string_style: match indented_strings.get() {
+                true => StringStyle::Indented {
+                    double_quote_threshold: None,
+                },
+                false => StringStyle::DoubleQuoted,
+            },
+            flatten_set_keys: match flatten_set_keys.get() {
+                true => FlattenSetKeys::Always,
+                false => FlattenSetKeys::Never,
+            },
+            max_line_width: Some(max_line_width.get()).filter(|width| *width > 0),
+            style: match nixfmt_style.get() {
+                true => OutputStyle::Nixfmt,
+                false => OutputStyle::Default,
+            },
+            input_format: input_format.get(),
+            key_order: key_order.get(),
+            key_priority: key_priority.with(|keys| keys.split(',').map(str::trim).filter(|key| !key.is_empty()).map(str::to_string).collect()),
+            ..Json2NixConfig::new(initial_indentation.get(), indentation.get(), compact_set_keys.get())
+        };
         json2nix(&json, &config)
     });
 
-    let nix_code_or_empty = Signal::derive(move || generated_nix_code_result.get().unwrap_or_default());
+    let nix_code_or_empty = Signal::derive(move || generated_nix_code_result.with(|result| result.as_ref().cloned().unwrap_or_default()));
 
     let highlighted_nix_code = Signal::derive(move || {
-        let generated_nix_code = generated_nix_code_result.get();
-
-        match generated_nix_code {
-            Ok(code) => Ok(highlight_nix_code(&code)),
-            Err(err) => Err(err),
-        }
+        generated_nix_code_result.with(|result| match result {
+            Ok(code) => Ok(highlight_nix_code(code)),
+            Err(err) => Err(error_message(err)),
+        })
     });
 
     let input_raw_input = move |event| {
@@ -60,13 +87,21 @@ pub fn App() -> impl IntoView {
             <div class="columns">
                 <div class="column is-flex is-align-items-center">
                     <div class="title" style="min-width: 20rem;">
-                        "Convert JSON to Nix"
+                        {move || format!("Convert {} to Nix", input_format_name(input_format.get()))}
                     </div>
                 </div>
                 <div class="column">
                     <div class="columns">
                         <div class="column is-three-quarters is-flex is-justify-content-start">
                             <div class="mr-3">
+                                <Select
+                                    id="input_format"
+                                    value=input_format
+                                    options=vec![(InputFormat::Json, "JSON"), (InputFormat::Json5, "JSON5"), (InputFormat::Ini, "INI"), (InputFormat::Plist, "plist"), (InputFormat::Yaml, "YAML"), (InputFormat::Toml { datetimes: TomlDatetimes::String }, "TOML")]
+                                    label="Input".to_string()
+                                />
+                            </div>
+                            <div class="mx-3">
                                 <NumberInput
                                     id="initial_indentation"
                                     value=initial_indentation
@@ -84,13 +119,59 @@ pub fn App() -> impl IntoView {
                                     label="Indentation".to_string()
                                 />
                             </div>
-                            <div class="ml-3">
+                            <div class="mx-3">
+                                <NumberInput
+                                    id="max_line_width"
+                                    value=max_line_width
+                                    min=0
+                                    max=500
+                                    label="Line Width".to_string()
+                                />
+                            </div>
+                            <div class="mx-3">
+                                <Select
+                                    id="key_order"
+                                    value=key_order
+                                    options=vec![(KeyOrder::Preserve, "Preserve"), (KeyOrder::Nix, "Nix"), (KeyOrder::Natural, "Natural")]
+                                    label="Key Order".to_string()
+                                />
+                            </div>
+                            <div class="mx-3">
+                                <TextInput
+                                    id="key_priority"
+                                    value=key_priority
+                                    label="Keys First".to_string()
+                                    placeholder="enable, package"
+                                />
+                            </div>
+                            <div class="mx-3">
                                 <CheckBox
                                     id="compact_set_keys"
                                     checked=compact_set_keys
                                     label="Compact Set Keys".to_string()
                                 />
                             </div>
+                            <div class="mx-3">
+                                <CheckBox
+                                    id="flatten_set_keys"
+                                    checked=flatten_set_keys
+                                    label="Flatten Set Keys".to_string()
+                                />
+                            </div>
+                            <div class="mx-3">
+                                <CheckBox
+                                    id="indented_strings"
+                                    checked=indented_strings
+                                    label="Indented Strings".to_string()
+                                />
+                            </div>
+                            <div class="ml-3">
+                                <CheckBox
+                                    id="nixfmt_style"
+                                    checked=nixfmt_style
+                                    label="nixfmt Style".to_string()
+                                />
+                            </div>
                         </div>
                         <div class="column is-flex is-justify-content-end is-align-items-center">
                             <div>
@@ -111,7 +192,7 @@ pub fn App() -> impl IntoView {
                 <div class="column is-half">
                     <textarea
                         on:input=input_raw_input
-                        placeholder="Your JSON goes here..."
+                        placeholder=move || format!("Your {} goes here...", input_format_name(input_format.get()))
                         class=move || "textarea is-family-monospace is-size-6".to_string() + if highlighted_nix_code.get().is_err() { " is-danger" } else { "" }
                         style="height: 100%; resize: none; background-color: var(--bulma-pre-background); padding: var(--bulma-pre-padding);"
                         spellcheck="false"
@@ -144,3 +225,26 @@ pub fn App() -> impl IntoView {
         </div>
     }
 }
+
+fn error_message(err: &Json2NixError) -> String {
+    match err {
+        Json2NixError::Parse {
+            line,
+            column,
+            category,
+            message,
+        } => format!("Line {}, column {}: {}: {}", line, column, category, message),
+        err => err.to_string(),
+    }
+}
+
+fn input_format_name(input_format: InputFormat) -> &'static str {
+    match input_format {
+        InputFormat::Json => "JSON",
+        InputFormat::Json5 => "JSON5",
+        InputFormat::Ini => "INI",
+        InputFormat::Plist => "plist",
+        InputFormat::Yaml => "YAML",
+        InputFormat::Toml { .. } => "TOML",
+    }
+}
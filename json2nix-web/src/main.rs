@@ -2,9 +2,11 @@ mod app;
 mod checkbox;
 mod copy_button;
 mod number_input;
+mod select;
+mod text_input;
 
 use leptos::prelude::mount_to_body;
 
 fn main() {
-    mount_to_body(|| app::App());
+    mount_to_body(app::App);
 }
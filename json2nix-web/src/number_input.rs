@@ -7,10 +7,9 @@ pub fn NumberInput(#[prop(into)] value: RwSignal<usize>, min: usize, max: usize,
         if new_value.is_empty() {
             value.set(min);
         }
-        match new_value.parse::<usize>() {
-            Ok(new_value) => value.set(new_value.clamp(min, max)),
-            Err(_) => {}
-        };
+        if let Ok(new_value) = new_value.parse::<usize>() {
+            value.set(new_value.clamp(min, max));
+        }
     };
 
     view! {
@@ -0,0 +1,46 @@
+use leptos::prelude::*;
+
+#[component]
+pub fn Select<T>(#[prop(into)] value: RwSignal<T>, options: Vec<(T, &'static str)>, id: &'static str, label: String) -> impl IntoView
+where
+    T: Copy + PartialEq + Send + Sync + 'static,
+{
+    let select_options = options.clone();
+    let select_value = move |event| {
+        let index = event_target_value(&event).parse::<usize>().unwrap_or_default();
+        if let Some((option, _)) = options.get(index) {
+            value.set(*option);
+        }
+    };
+
+    view! {
+        <div>
+            <div class="field">
+                <div class="field-label" style="width: 100%;">
+                    <label for=id class="label has-text-left">{label}</label>
+                </div>
+                <div class="field-body">
+                    <div class="field">
+                        <div class="control">
+                            <div class="select">
+                                <select id=id on:change=select_value>
+                                    {select_options
+                                        .into_iter()
+                                        .enumerate()
+                                        .map(|(index, (option, label))| {
+                                            view! {
+                                                <option value=index.to_string() prop:selected=move || value.get() == option>
+                                                    {label}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                            </div>
+                        </div>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,31 @@
+use leptos::prelude::*;
+
+#[component]
+pub fn TextInput(#[prop(into)] value: RwSignal<String>, id: &'static str, label: String, placeholder: &'static str) -> impl IntoView {
+    let input_value = move |event| value.set(event_target_value(&event));
+
+    view! {
+        <div>
+            <div class="field">
+                <div class="field-label" style="width: 100%;">
+                    <label for=id class="label has-text-left">{label}</label>
+                </div>
+                <div class="field-body">
+                    <div class="field">
+                        <p class="control">
+                            <input
+                                id=id
+                                type="text"
+                                on:input=input_value
+                                prop:value=value
+                                placeholder=placeholder
+                                spellcheck="false"
+                                class="input"
+                            />
+                        </p>
+                    </div>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,69 @@
+//! Benchmarks of the conversion of a large and a deeply nested document. Run them with `cargo bench -p json2nix`.
+
+use std::{hint::black_box, io};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use json2nix::{Json2NixConfig, json2nix, json2nix_expr, json2nix_stream, print_nix_expr};
+use serde_json::{Value, json};
+
+// about 2 MB of packages with attributes, lists and strings that need escaping
+fn large() -> String {
+    let packages = (0..5_000)
+        .map(|index| {
+            json!({
+                "name": format!("package-{}", index),
+                "version": format!("1.{}.0", index % 17),
+                "enable": index % 2 == 0,
+                "priority": index,
+                "weight": index as f64 / 7.0,
+                "description": "A \"quoted\" description with ${interpolation} and\nmultiple\nlines",
+                "dependencies": (0..index % 8).map(|dependency| format!("dependency-{}", dependency)).collect::<Vec<_>>(),
+                "settings": { "colors": { "foreground": "#ffffff", "background": "#000000" }, "font": { "size": 12 } },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string(&json!({ "packages": packages })).unwrap()
+}
+
+// sets nested 120 levels deep, just below the recursion limit of serde_json, with some attributes on every level
+fn deep() -> String {
+    let value = (0..120).fold(Value::Null, |child, level| {
+        json!({
+            "level": level,
+            "name": format!("level {}", level),
+            "values": [1, 2, 3],
+            "child": child,
+        })
+    });
+
+    serde_json::to_string(&value).unwrap()
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let large = large();
+    let deep = deep();
+    let config = Json2NixConfig::default();
+    let max_line_width = Json2NixConfig {
+        max_line_width: Some(80),
+        ..Json2NixConfig::default()
+    };
+
+    c.bench_function("json2nix/large", |b| b.iter(|| json2nix(black_box(&large), &config).unwrap()));
+    c.bench_function("json2nix/large/max_line_width", |b| {
+        b.iter(|| json2nix(black_box(&large), &max_line_width).unwrap())
+    });
+    c.bench_function("json2nix/deep", |b| b.iter(|| json2nix(black_box(&deep), &config).unwrap()));
+
+    let (large_expr, _) = json2nix_expr(&large, &config).unwrap();
+    c.bench_function("print/large", |b| b.iter(|| print_nix_expr(black_box(&large_expr), &config)));
+    let (deep_expr, _) = json2nix_expr(&deep, &config).unwrap();
+    c.bench_function("print/deep", |b| b.iter(|| print_nix_expr(black_box(&deep_expr), &config)));
+
+    c.bench_function("stream/large", |b| {
+        b.iter(|| json2nix_stream(black_box(large.as_bytes()), io::sink(), &config).unwrap())
+    });
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,108 @@
+/// A Nix expression, which can be rendered with [`crate::print_nix_expr`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum NixExpr {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// A float, which must be finite as Nix has no literals for infinity and NaN.
+    Float(f64),
+    String(String),
+    /// A double-quoted string with interpolations like `"${pkgs.hello}/bin/hello"`.
+    InterpolatedString(Vec<StringPart>),
+    /// A path like `./foo` or `/etc/hosts`, which is rendered as is.
+    Path(String),
+    List(Vec<NixExpr>),
+    AttrSet(Vec<Binding>),
+    /// Any Nix expression, which is rendered as is.
+    Raw(String),
+    /// A function application like `lib.mkOverride 50 value`. Arguments are parenthesized when rendered if necessary.
+    Apply {
+        function: String,
+        arguments: Vec<NixExpr>,
+    },
+    /// A function with an attribute set pattern that accepts additional attributes: `{ a, b, ... }: body`
+    Function {
+        formals: Vec<String>,
+        body: Box<NixExpr>,
+    },
+    /// `let bindings in body`, where the bindings may refer to each other.
+    Let {
+        bindings: Vec<Binding>,
+        body: Box<NixExpr>,
+    },
+    /// An expression preceded by comments. Each comment may span multiple lines.
+    Commented {
+        comments: Vec<String>,
+        expr: Box<NixExpr>,
+    },
+}
+
+/// A part of a [`NixExpr::InterpolatedString`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    /// Text that is escaped when rendered.
+    Literal(String),
+    /// A Nix expression that is inserted as is into `${…}`.
+    Interpolation(String),
+}
+
+/// An attribute of an attribute set: `path = value;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    /// The attribute path, e.g. `["a", "b"]` for `a.b = …;`. The attribute names are quoted when rendered if necessary.
+    pub path: Vec<String>,
+    pub value: NixExpr,
+    /// Comments rendered in front of the binding. Each comment may span multiple lines.
+    pub comments: Vec<String>,
+}
+
+impl Binding {
+    pub fn new(path: Vec<String>, value: NixExpr) -> Self {
+        Self {
+            path,
+            value,
+            comments: Vec::new(),
+        }
+    }
+}
+
+impl NixExpr {
+    /// The nested expressions in the order they are rendered.
+    pub(crate) fn children(&self) -> impl DoubleEndedIterator<Item = &NixExpr> {
+        let (elements, bindings, last): (&[NixExpr], &[Binding], Option<&NixExpr>) = match self {
+            NixExpr::List(elements) | NixExpr::Apply { arguments: elements, .. } => (elements, &[], None),
+            NixExpr::AttrSet(bindings) => (&[], bindings, None),
+            NixExpr::Let { bindings, body } => (&[], bindings, Some(body)),
+            NixExpr::Function { body: expr, .. } | NixExpr::Commented { expr, .. } => (&[], &[], Some(expr)),
+            _ => (&[], &[], None),
+        };
+        elements.iter().chain(bindings.iter().map(|binding| &binding.value)).chain(last)
+    }
+
+    pub(crate) fn children_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut NixExpr> {
+        let (elements, bindings, last): (&mut [NixExpr], &mut [Binding], Option<&mut NixExpr>) = match self {
+            NixExpr::List(elements) | NixExpr::Apply { arguments: elements, .. } => (elements, &mut [], None),
+            NixExpr::AttrSet(bindings) => (&mut [], bindings, None),
+            NixExpr::Let { bindings, body } => (&mut [], bindings, Some(body)),
+            NixExpr::Function { body: expr, .. } | NixExpr::Commented { expr, .. } => (&mut [], &mut [], Some(expr)),
+            _ => (&mut [], &mut [], None),
+        };
+        elements
+            .iter_mut()
+            .chain(bindings.iter_mut().map(|binding| &mut binding.value))
+            .chain(last)
+    }
+}
+
+/// Drops `expr` without recursion, which would overflow the stack for deeply nested expressions.
+pub(crate) fn drop_expr(expr: NixExpr) {
+    // only the nested expressions with nested expressions of their own are taken out, leaves are dropped in place
+    let mut stack = vec![expr];
+    while let Some(mut expr) = stack.pop() {
+        stack.extend(
+            expr.children_mut()
+                .filter(|child| child.children().next().is_some())
+                .map(|child| std::mem::replace(child, NixExpr::Null)),
+        );
+    }
+}
@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    FlattenSetKeys, Json2NixConfig, Json2NixError, Warning,
+    ast::{Binding, NixExpr, drop_expr},
+    extract::extract_repeated,
+    number::convert_number,
+    order::ordered_attributes,
+    raw::{RAW_NIX_KEY, check_balanced},
+    rewrite::{describe, rewrite_string},
+    rules::validate_rules,
+};
+
+/// Comments of the input, keyed by the JSON Pointer (RFC 6901) of the value they belong to, e.g. `/a/0` for the first
+/// element of the list `a`. The comments of the root value are stored under the empty pointer.
+pub type Comments = HashMap<String, Vec<String>>;
+
+/// Converts a JSON value to a Nix expression. All decisions about the structure are made here, the layout is up to the printer.
+pub fn to_nix_expr(value: &Value, comments: &Comments, config: &Json2NixConfig, warnings: &mut Vec<Warning>) -> Result<NixExpr, Json2NixError> {
+    validate_rules(&config.rules)?;
+
+    let mut converter = Converter {
+        config,
+        comments,
+        warnings,
+        pointer: String::new(),
+        depth: 0,
+        flatten_set_keys: config.flatten_set_keys,
+        path: Vec::new(),
+        exprs: Vec::new(),
+        bindings: Vec::new(),
+    };
+
+    let expr = converter.expr(value)?;
+    Ok(wrap(converter.commented(expr), config))
+}
+
+/// Nests `expr` under [`Json2NixConfig::attr_path`], extracts repeated values if [`Json2NixConfig::extract_repeated`] is
+/// set and wraps it in a module function if [`Json2NixConfig::module`] is set.
+pub(crate) fn wrap(expr: NixExpr, config: &Json2NixConfig) -> NixExpr {
+    let expr = match config.attr_path.is_empty() {
+        true => expr,
+        false => {
+            // the comments of the root now belong to the attribute
+            let (comments, expr) = match expr {
+                NixExpr::Commented { comments, expr } => (comments, *expr),
+                expr => (Vec::new(), expr),
+            };
+            NixExpr::AttrSet(prefixed_bindings(&config.attr_path, comments, expr, config))
+        }
+    };
+
+    let expr = match config.extract_repeated {
+        Some(min_size) => extract_repeated(expr, min_size),
+        None => expr,
+    };
+
+    match config.module {
+        true => NixExpr::Function {
+            formals: vec!["config".to_string(), "lib".to_string(), "pkgs".to_string()],
+            body: Box::new(expr),
+        },
+        false => expr,
+    }
+}
+
+// The prefix is merged into the attribute paths of the expression just like the attribute paths of nested sets.
+fn prefixed_bindings(attr_path: &[String], mut comments: Vec<String>, expr: NixExpr, config: &Json2NixConfig) -> Vec<Binding> {
+    let merge_paths = config.compact_set_keys || config.flatten_set_keys != FlattenSetKeys::Never;
+
+    match expr {
+        NixExpr::AttrSet(bindings)
+            if !bindings.is_empty() && (config.compact_set_keys && bindings.len() == 1 || config.flatten_set_keys != FlattenSetKeys::Never) =>
+        {
+            bindings
+                .into_iter()
+                .map(|binding| Binding {
+                    path: attr_path.iter().cloned().chain(binding.path).collect(),
+                    comments: std::mem::take(&mut comments).into_iter().chain(binding.comments).collect(),
+                    value: binding.value,
+                })
+                .collect()
+        }
+        expr if merge_paths => vec![Binding {
+            comments,
+            ..Binding::new(attr_path.to_vec(), expr)
+        }],
+        expr => {
+            let (first, rest) = attr_path.split_first().expect("the attribute path is not empty");
+            let value = rest
+                .iter()
+                .rev()
+                .fold(expr, |value, key| NixExpr::AttrSet(vec![Binding::new(vec![key.clone()], value)]));
+            vec![Binding {
+                comments,
+                ..Binding::new(vec![first.clone()], value)
+            }]
+        }
+    }
+}
+
+/// Converts the string at the JSON Pointer `pointer`, which may be a raw Nix expression with
+/// [`Json2NixConfig::raw_nix_prefix`] or be rewritten by [`Json2NixConfig::rewrites`].
+pub(crate) fn string_expr(string: &str, pointer: &str, config: &Json2NixConfig, warnings: &mut Vec<Warning>) -> Result<NixExpr, Json2NixError> {
+    check_nul(string, pointer)?;
+    if let Some(raw) = config.raw_nix_prefix.as_deref().and_then(|prefix| string.strip_prefix(prefix)) {
+        return raw_expr(raw, pointer);
+    }
+
+    Ok(match rewrite_string(string, &config.rewrites) {
+        Some(expr) => {
+            warnings.push(Warning::RewrittenString {
+                pointer: pointer.to_string(),
+                original: string.to_string(),
+                replacement: describe(&expr),
+            });
+            expr
+        }
+        None => NixExpr::String(string.to_string()),
+    })
+}
+
+/// Fails if `string` at `pointer`, which is a string or an attribute name, contains a NUL character.
+pub(crate) fn check_nul(string: &str, pointer: &str) -> Result<(), Json2NixError> {
+    match string.contains('\0') {
+        true => Err(Json2NixError::NulCharacter {
+            pointer: pointer.to_string(),
+        }),
+        false => Ok(()),
+    }
+}
+
+fn raw_expr(raw: &str, pointer: &str) -> Result<NixExpr, Json2NixError> {
+    check_balanced(raw).map_err(|message| Json2NixError::InvalidRawNix {
+        pointer: pointer.to_string(),
+        message,
+    })?;
+    Ok(NixExpr::Raw(raw.trim().to_string()))
+}
+
+/// Wraps `expr` with all rules of `config` that match the JSON Pointer `pointer`, in order.
+pub(crate) fn apply_rules(expr: NixExpr, pointer: &str, config: &Json2NixConfig) -> NixExpr {
+    config
+        .rules
+        .iter()
+        .filter(|rule| rule.matches(pointer))
+        .fold(expr, |expr, rule| rule.action.apply(expr))
+}
+
+/// Appends `token` to the JSON Pointer `pointer` and returns the previous length, so it can be restored with `truncate`.
+pub fn push_pointer(pointer: &mut String, token: &str) -> usize {
+    let length = pointer.len();
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+    length
+}
+
+struct Converter<'a> {
+    config: &'a Json2NixConfig,
+    comments: &'a Comments,
+    warnings: &'a mut Vec<Warning>,
+    /// The JSON Pointer of the value that is currently converted.
+    pointer: String,
+    /// The number of reference tokens of the pointer, which is the number of lists and sets around the current value.
+    depth: usize,
+    /// The flattening that applies to the value that is currently converted, which is disabled below the maximum depth.
+    flatten_set_keys: FlattenSetKeys,
+    /// The attribute path of the binding that is currently converted, relative to the innermost set that is not merged.
+    path: Vec<String>,
+    /// The converted values, which are taken by the task that converts their list, set or binding.
+    exprs: Vec<NixExpr>,
+    /// The converted bindings, which are taken by the task that converts their set.
+    bindings: Vec<Binding>,
+}
+
+/// A step of the conversion. The steps are kept on a stack instead of the call stack, so input of any depth can be converted.
+enum Task<'v> {
+    /// Converts the value at the current pointer and pushes its expression.
+    Value(&'v Value),
+    /// Converts the element of a list with the given index.
+    Element(usize, &'v Value),
+    /// Finishes the element that was just converted, see [`Converter::end_element`].
+    EndElement(usize),
+    /// Converts the attribute with the given key and pushes its bindings. `comments` are the ones of the sets that were
+    /// merged into the attribute path.
+    Attribute { key: &'v str, value: &'v Value, comments: Vec<String> },
+    /// Finishes the attribute whose value was just converted, see [`Converter::end_attribute`].
+    EndAttribute {
+        comments: Vec<String>,
+        flatten_set_keys: FlattenSetKeys,
+        length: usize,
+    },
+    /// Removes the last attribute of the path after the set it refers to was merged into it.
+    EndMergedAttribute(usize),
+    /// Collects the elements from the given index on into a list.
+    EndList(usize),
+    /// Collects the bindings from the given index on into a set and restores the attribute path of its binding.
+    EndAttrSet(usize, Vec<String>),
+}
+
+impl Converter<'_> {
+    fn expr(&mut self, value: &Value) -> Result<NixExpr, Json2NixError> {
+        let mut tasks = vec![Task::Value(value)];
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Value(value) => self.value(value, &mut tasks)?,
+                Task::Element(index, value) => {
+                    let length = self.push_pointer(&index.to_string());
+                    if is_container(value) {
+                        tasks.push(Task::EndElement(length));
+                        tasks.push(Task::Value(value));
+                    } else {
+                        self.value(value, &mut tasks)?;
+                        self.end_element(length);
+                    }
+                }
+                Task::EndElement(length) => self.end_element(length),
+                Task::Attribute { key, value, comments } => self.attribute(key, value, comments, &mut tasks)?,
+                Task::EndAttribute {
+                    comments,
+                    flatten_set_keys,
+                    length,
+                } => self.end_attribute(comments, flatten_set_keys, length),
+                Task::EndMergedAttribute(length) => {
+                    self.path.pop();
+                    self.truncate_pointer(length);
+                }
+                Task::EndList(start) => {
+                    let elements = self.exprs.split_off(start);
+                    self.exprs.push(apply_rules(NixExpr::List(elements), &self.pointer, self.config));
+                }
+                Task::EndAttrSet(start, path) => {
+                    let bindings = self.bindings.split_off(start);
+                    self.path = path;
+                    self.exprs.push(apply_rules(NixExpr::AttrSet(bindings), &self.pointer, self.config));
+                }
+            }
+        }
+
+        Ok(self.exprs.pop().expect("the value is converted"))
+    }
+
+    fn value<'v>(&mut self, value: &'v Value, tasks: &mut Vec<Task<'v>>) -> Result<(), Json2NixError> {
+        if let Some(raw) = self.raw_nix_object(value) {
+            let expr = apply_rules(raw_expr(raw, &self.pointer)?, &self.pointer, self.config);
+            self.exprs.push(expr);
+            return Ok(());
+        }
+
+        let expr = match value {
+            Value::Null => NixExpr::Null,
+            Value::Bool(bool) => NixExpr::Bool(*bool),
+            Value::Number(number) => convert_number(number, self.config.unrepresentable_numbers, self.warnings)?,
+            Value::String(string) => string_expr(string, &self.pointer, self.config, self.warnings)?,
+            Value::Array(array) => {
+                self.check_depth()?;
+                tasks.push(Task::EndList(self.exprs.len()));
+                tasks.extend(array.iter().enumerate().rev().map(|(index, element)| Task::Element(index, element)));
+                return Ok(());
+            }
+            Value::Object(object) => {
+                self.check_depth()?;
+                tasks.push(Task::EndAttrSet(self.bindings.len(), std::mem::take(&mut self.path)));
+                push_attributes(object, Vec::new(), self.config, tasks);
+                return Ok(());
+            }
+        };
+
+        self.exprs.push(apply_rules(expr, &self.pointer, self.config));
+        Ok(())
+    }
+
+    /// Converts the attribute `key` of the innermost set. Nested sets are merged into the attribute path (`a.b.c = …;`) if
+    /// they have only one attribute and `compact_set_keys` is set, or if they are flattened. The comments of merged sets
+    /// are moved to their first binding.
+    fn attribute<'v>(&mut self, key: &str, value: &'v Value, mut comments: Vec<String>, tasks: &mut Vec<Task<'v>>) -> Result<(), Json2NixError> {
+        let length = self.push_pointer(key);
+        check_nul(key, &self.pointer)?;
+        self.path.push(key.to_string());
+        comments.extend(self.comments_at_pointer());
+
+        match value.as_object() {
+            // sets that are wrapped by a rule or contain a raw Nix expression must stay sets
+            Some(object)
+                if !object.is_empty()
+                    && (self.config.compact_set_keys && object.len() == 1 || self.flatten(self.path.len()))
+                    && !self.has_rules()
+                    && self.raw_nix_object(value).is_none() =>
+            {
+                self.check_depth()?;
+                tasks.push(Task::EndMergedAttribute(length));
+                push_attributes(object, comments, self.config, tasks);
+            }
+            _ => {
+                // only the outermost sets are flattened up to the maximum depth
+                let flatten_set_keys = match self.flatten_set_keys {
+                    FlattenSetKeys::MaxDepth(_) => FlattenSetKeys::Never,
+                    flatten_set_keys => flatten_set_keys,
+                };
+                let flatten_set_keys = std::mem::replace(&mut self.flatten_set_keys, flatten_set_keys);
+                if is_container(value) {
+                    tasks.push(Task::EndAttribute {
+                        comments,
+                        flatten_set_keys,
+                        length,
+                    });
+                    tasks.push(Task::Value(value));
+                } else {
+                    self.value(value, tasks)?;
+                    self.end_attribute(comments, flatten_set_keys, length);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the comments of the element that was just converted and restores the pointer to `length`.
+    fn end_element(&mut self, length: usize) {
+        let expr = self.exprs.pop().expect("the element is converted");
+        let expr = self.commented(expr);
+        self.exprs.push(expr);
+        self.truncate_pointer(length);
+    }
+
+    /// Pushes the binding for the value that was just converted, restores the flattening and removes the last attribute
+    /// of the path, whose pointer had `length`.
+    fn end_attribute(&mut self, comments: Vec<String>, flatten_set_keys: FlattenSetKeys, length: usize) {
+        let value = self.exprs.pop().expect("the value is converted");
+        self.flatten_set_keys = flatten_set_keys;
+        self.bindings.push(Binding {
+            comments,
+            ..Binding::new(self.path.clone(), value)
+        });
+        self.path.pop();
+        self.truncate_pointer(length);
+    }
+
+    /// Fails if the list or set at the current pointer is nested deeper than [`Json2NixConfig::max_depth`].
+    fn check_depth(&self) -> Result<(), Json2NixError> {
+        match self.config.max_depth {
+            Some(max_depth) if self.depth >= max_depth => Err(Json2NixError::TooDeep {
+                pointer: self.pointer.clone(),
+                max_depth,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a set at the end of an attribute path with `depth` attributes is merged into the path.
+    fn flatten(&self, depth: usize) -> bool {
+        match self.flatten_set_keys {
+            FlattenSetKeys::Never => false,
+            FlattenSetKeys::Always => true,
+            FlattenSetKeys::MaxDepth(max_depth) => depth < max_depth,
+        }
+    }
+
+    /// Returns the raw Nix expression of an object like `{ "$nix": "pkgs.hello" }`, if any.
+    fn raw_nix_object<'v>(&self, value: &'v Value) -> Option<&'v str> {
+        match value {
+            Value::Object(object) if self.config.raw_nix_objects && object.len() == 1 => object.get(RAW_NIX_KEY)?.as_str(),
+            _ => None,
+        }
+    }
+
+    fn has_rules(&self) -> bool {
+        self.config.rules.iter().any(|rule| rule.matches(&self.pointer))
+    }
+
+    fn push_pointer(&mut self, token: &str) -> usize {
+        self.depth += 1;
+        push_pointer(&mut self.pointer, token)
+    }
+
+    fn truncate_pointer(&mut self, length: usize) {
+        self.depth -= 1;
+        self.pointer.truncate(length);
+    }
+
+    fn comments_at_pointer(&self) -> Vec<String> {
+        self.comments.get(&self.pointer).cloned().unwrap_or_default()
+    }
+
+    fn commented(&self, expr: NixExpr) -> NixExpr {
+        match self.comments_at_pointer() {
+            comments if comments.is_empty() => expr,
+            comments => NixExpr::Commented {
+                comments,
+                expr: Box::new(expr),
+            },
+        }
+    }
+}
+
+// Lists and sets are converted by tasks, scalars right away.
+fn is_container(value: &Value) -> bool {
+    value.is_array() || value.is_object()
+}
+
+// Pushes the tasks for the attributes of `object` in reverse order, so they are converted in the configured order.
+// `comments` are moved to the first attribute.
+fn push_attributes<'v>(object: &'v Map<String, Value>, mut comments: Vec<String>, config: &Json2NixConfig, tasks: &mut Vec<Task<'v>>) {
+    let start = tasks.len();
+    for (key, value) in ordered_attributes(object, config) {
+        tasks.push(Task::Attribute {
+            key,
+            value,
+            comments: std::mem::take(&mut comments),
+        });
+    }
+    tasks[start..].reverse();
+}
+
+// The converted values are only left over if the conversion failed, and may be nested too deeply to be dropped recursively.
+impl Drop for Converter<'_> {
+    fn drop(&mut self) {
+        self.exprs.drain(..).for_each(drop_expr);
+        self.bindings.drain(..).for_each(|binding| drop_expr(binding.value));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn convert(value: &Value, compact_set_keys: bool) -> NixExpr {
+        to_nix_expr(value, &Comments::new(), &Json2NixConfig::new(0, 2, compact_set_keys), &mut Vec::new()).unwrap()
+    }
+
+    fn path(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn scalars() {
+        let input = json!([null, true, 1, -2.5, "abc"]);
+        let expected = NixExpr::List(vec![
+            NixExpr::Null,
+            NixExpr::Bool(true),
+            NixExpr::Int(1),
+            NixExpr::Float(-2.5),
+            NixExpr::String("abc".to_string()),
+        ]);
+        assert_eq!(expected, convert(&input, false));
+    }
+
+    #[test]
+    fn nested_sets() {
+        let input = json!({ "a": { "b": { "c": 1 } }, "d": {} });
+        let expected = NixExpr::AttrSet(vec![
+            Binding::new(
+                path(&["a"]),
+                NixExpr::AttrSet(vec![Binding::new(
+                    path(&["b"]),
+                    NixExpr::AttrSet(vec![Binding::new(path(&["c"]), NixExpr::Int(1))]),
+                )]),
+            ),
+            Binding::new(path(&["d"]), NixExpr::AttrSet(vec![])),
+        ]);
+        assert_eq!(expected, convert(&input, false));
+    }
+
+    #[test]
+    fn nested_sets_compact_set_keys() {
+        let input = json!({ "a": { "b": { "c": 1 } }, "d": { "e": { "f": 2, "g": 3 } }, "h": { "i": {} } });
+        let expected = NixExpr::AttrSet(vec![
+            Binding::new(path(&["a", "b", "c"]), NixExpr::Int(1)),
+            Binding::new(
+                path(&["d", "e"]),
+                NixExpr::AttrSet(vec![
+                    Binding::new(path(&["f"]), NixExpr::Int(2)),
+                    Binding::new(path(&["g"]), NixExpr::Int(3)),
+                ]),
+            ),
+            Binding::new(path(&["h", "i"]), NixExpr::AttrSet(vec![])),
+        ]);
+        assert_eq!(expected, convert(&input, true));
+    }
+
+    #[test]
+    fn flatten_set_keys() {
+        let input = json!({ "a": { "b": { "x": 1, "y": { "z": 2 } }, "c": [{ "d": { "e": 3 } }], "f": {} } });
+        let config = Json2NixConfig {
+            flatten_set_keys: FlattenSetKeys::Always,
+            ..Json2NixConfig::default()
+        };
+        let expected = NixExpr::AttrSet(vec![
+            Binding::new(path(&["a", "b", "x"]), NixExpr::Int(1)),
+            Binding::new(path(&["a", "b", "y", "z"]), NixExpr::Int(2)),
+            Binding::new(
+                path(&["a", "c"]),
+                NixExpr::List(vec![NixExpr::AttrSet(vec![Binding::new(path(&["d", "e"]), NixExpr::Int(3))])]),
+            ),
+            Binding::new(path(&["a", "f"]), NixExpr::AttrSet(vec![])),
+        ]);
+        assert_eq!(expected, to_nix_expr(&input, &Comments::new(), &config, &mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn flatten_set_keys_max_depth() {
+        let input = json!({ "a": { "b": { "x": 1, "y": { "z": 2 } }, "c": 3 }, "d": 4 });
+        let nested = NixExpr::AttrSet(vec![
+            Binding::new(path(&["x"]), NixExpr::Int(1)),
+            Binding::new(path(&["y"]), NixExpr::AttrSet(vec![Binding::new(path(&["z"]), NixExpr::Int(2))])),
+        ]);
+        let expected = NixExpr::AttrSet(vec![
+            Binding::new(path(&["a", "b"]), nested),
+            Binding::new(path(&["a", "c"]), NixExpr::Int(3)),
+            Binding::new(path(&["d"]), NixExpr::Int(4)),
+        ]);
+        let config = Json2NixConfig {
+            flatten_set_keys: FlattenSetKeys::MaxDepth(2),
+            ..Json2NixConfig::default()
+        };
+        assert_eq!(expected, to_nix_expr(&input, &Comments::new(), &config, &mut Vec::new()).unwrap());
+
+        // sets with one attribute are still compacted below the maximum depth
+        let expected = NixExpr::AttrSet(vec![
+            Binding::new(
+                path(&["a", "b"]),
+                NixExpr::AttrSet(vec![
+                    Binding::new(path(&["x"]), NixExpr::Int(1)),
+                    Binding::new(path(&["y", "z"]), NixExpr::Int(2)),
+                ]),
+            ),
+            Binding::new(path(&["a", "c"]), NixExpr::Int(3)),
+            Binding::new(path(&["d"]), NixExpr::Int(4)),
+        ]);
+        let config = Json2NixConfig {
+            flatten_set_keys: FlattenSetKeys::MaxDepth(2),
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        assert_eq!(expected, to_nix_expr(&input, &Comments::new(), &config, &mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn flatten_set_keys_comments() {
+        let input = json!({ "a": { "b": 1, "c": 2 } });
+        let comments = Comments::from([("/a".to_string(), vec!["a".to_string()]), ("/a/c".to_string(), vec!["c".to_string()])]);
+        let config = Json2NixConfig {
+            flatten_set_keys: FlattenSetKeys::Always,
+            ..Json2NixConfig::default()
+        };
+        let expected = NixExpr::AttrSet(vec![
+            Binding {
+                comments: vec!["a".to_string()],
+                ..Binding::new(path(&["a", "b"]), NixExpr::Int(1))
+            },
+            Binding {
+                comments: vec!["c".to_string()],
+                ..Binding::new(path(&["a", "c"]), NixExpr::Int(2))
+            },
+        ]);
+        assert_eq!(expected, to_nix_expr(&input, &comments, &config, &mut Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn unrepresentable_number() {
+        let input: Value = serde_json::from_str("[18446744073709551615]").unwrap();
+        let result = to_nix_expr(&input, &Comments::new(), &Json2NixConfig::default(), &mut Vec::new());
+        assert!(matches!(result, Err(Json2NixError::UnrepresentableNumber { .. })));
+    }
+
+    #[test]
+    fn nul_characters() {
+        for (input, expected_pointer) in [(json!({ "a": ["b\0"] }), "/a/0"), (json!({ "a": { "b\0": 1 } }), "/a/b\0")] {
+            let result = to_nix_expr(&input, &Comments::new(), &Json2NixConfig::default(), &mut Vec::new());
+            assert!(matches!(result, Err(Json2NixError::NulCharacter { pointer }) if pointer == expected_pointer));
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let input = json!({ "a": [1, 2], "b/c": { "d": { "e": null } } });
+        let comments = Comments::from([
+            (String::new(), vec!["root".to_string()]),
+            ("/a".to_string(), vec!["a".to_string()]),
+            ("/a/1".to_string(), vec!["second".to_string()]),
+            ("/b~1c".to_string(), vec!["b/c".to_string()]),
+            ("/b~1c/d/e".to_string(), vec!["e".to_string()]),
+        ]);
+        let expected = NixExpr::Commented {
+            comments: vec!["root".to_string()],
+            expr: Box::new(NixExpr::AttrSet(vec![
+                Binding {
+                    comments: vec!["a".to_string()],
+                    ..Binding::new(
+                        path(&["a"]),
+                        NixExpr::List(vec![
+                            NixExpr::Int(1),
+                            NixExpr::Commented {
+                                comments: vec!["second".to_string()],
+                                expr: Box::new(NixExpr::Int(2)),
+                            },
+                        ]),
+                    )
+                },
+                Binding {
+                    comments: vec!["b/c".to_string(), "e".to_string()],
+                    ..Binding::new(path(&["b/c", "d", "e"]), NixExpr::Null)
+                },
+            ])),
+        };
+        let result = to_nix_expr(&input, &comments, &Json2NixConfig::new(0, 2, true), &mut Vec::new()).unwrap();
+        assert_eq!(expected, result);
+    }
+}
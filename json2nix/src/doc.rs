@@ -0,0 +1,225 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Write},
+};
+
+/// A document in the style of Wadler's "A prettier printer", which describes the possible layouts of some text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Doc<'a> {
+    /// Text without line breaks, which is borrowed from the expression if possible.
+    Text(Cow<'a, str>),
+    /// A line break, or a space if the enclosing group is rendered on one line.
+    Line,
+    /// A line break that is never rendered as space, which forces all enclosing groups to be broken.
+    HardLine,
+    /// Increases the indentation of all line breaks inside by the given amount.
+    Nest(usize, Box<Doc<'a>>),
+    /// Renders its content on one line if it fits, otherwise all of its direct line breaks are rendered as line breaks.
+    Group(Box<Doc<'a>>),
+    Concat(Vec<Doc<'a>>),
+}
+
+impl<'a> Doc<'a> {
+    pub fn text(text: impl Into<Cow<'a, str>>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    pub fn nest(indentation: usize, doc: Doc<'a>) -> Self {
+        Doc::Nest(indentation, Box::new(doc))
+    }
+
+    pub fn group(doc: Doc<'a>) -> Self {
+        Doc::Group(Box::new(doc))
+    }
+}
+
+/// Drops `doc` without recursion, which would overflow the stack for documents of deeply nested expressions. Only the
+/// nested documents that contain documents of their own are taken out, the others are dropped in place.
+pub fn drop_doc<'a>(doc: Doc<'a>) {
+    let mut stack = vec![doc];
+    while let Some(mut doc) = stack.pop() {
+        let mut take = |doc: &mut Doc<'a>| {
+            if matches!(doc, Doc::Nest(..) | Doc::Group(_) | Doc::Concat(_)) {
+                stack.push(std::mem::replace(doc, Doc::Line));
+            }
+        };
+        match &mut doc {
+            Doc::Nest(_, nested) | Doc::Group(nested) => take(nested),
+            Doc::Concat(docs) => docs.iter_mut().for_each(take),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Renders `doc` starting at `indentation`. Groups are only rendered on one line if they fit into `max_line_width`, so
+/// without a width every group is broken.
+///
+/// Indentation is written lazily, so empty lines do not get trailing whitespace.
+pub fn render(doc: &Doc, indentation: usize, max_line_width: Option<usize>) -> String {
+    let mut output = String::new();
+    render_to(doc, indentation, max_line_width, &mut output).expect("writing to a string cannot fail");
+    output
+}
+
+/// Like [`render`], but writes into `output` in a single pass. The indentation is tracked as state and written in front of
+/// the first text of every line, so no text is copied more than once.
+pub fn render_to(doc: &Doc, indentation: usize, max_line_width: Option<usize>, output: &mut impl Write) -> fmt::Result {
+    let mut pending_indentation = indentation;
+    let mut column = indentation;
+    let mut stack = vec![(indentation, Mode::Break, doc)];
+
+    while let Some((indentation, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                if text.is_empty() {
+                    continue;
+                }
+                write_indentation(output, pending_indentation)?;
+                output.write_str(text)?;
+                pending_indentation = 0;
+                column += text.chars().count();
+            }
+            Doc::Line if mode == Mode::Flat => {
+                output.write_char(' ')?;
+                column += 1;
+            }
+            Doc::Line | Doc::HardLine => {
+                output.write_char('\n')?;
+                pending_indentation = indentation;
+                column = indentation;
+            }
+            Doc::Nest(increment, doc) => stack.push((indentation + increment, mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indentation, mode, doc))),
+            Doc::Group(doc) => {
+                let fits_on_line =
+                    mode == Mode::Flat || max_line_width.is_some_and(|max_line_width| fits(max_line_width as isize - column as isize, doc, &stack));
+                stack.push((indentation, if fits_on_line { Mode::Flat } else { Mode::Break }, doc));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_indentation(output: &mut impl Write, mut indentation: usize) -> fmt::Result {
+    const SPACES: &str = "                                                                ";
+
+    while indentation > 0 {
+        let spaces = indentation.min(SPACES.len());
+        output.write_str(&SPACES[..spaces])?;
+        indentation -= spaces;
+    }
+    Ok(())
+}
+
+// Checks whether `doc` in flat mode and everything following it up to the next line break fit into `width`.
+fn fits(mut width: isize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    while width >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => width -= 1,
+            Doc::Line => return true,
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::Nest(_, doc) | Doc::Group(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn list(elements: &[&'static str]) -> Doc<'static> {
+        let mut docs = Vec::new();
+        for element in elements {
+            docs.push(Doc::Line);
+            docs.push(Doc::text(*element));
+        }
+        Doc::group(Doc::Concat(vec![
+            Doc::text("["),
+            Doc::nest(2, Doc::Concat(docs)),
+            Doc::Line,
+            Doc::text("]"),
+        ]))
+    }
+
+    #[test]
+    fn without_width() {
+        assert_eq!("[\n  a\n  b\n]", render(&list(&["a", "b"]), 0, None));
+    }
+
+    #[test]
+    fn fits() {
+        assert_eq!("[ a b ]", render(&list(&["a", "b"]), 0, Some(7)));
+    }
+
+    #[test]
+    fn does_not_fit() {
+        assert_eq!("[\n  a\n  b\n]", render(&list(&["a", "b"]), 0, Some(6)));
+    }
+
+    #[test]
+    fn indentation_counts_towards_width() {
+        assert_eq!("  [ a b ]", render(&list(&["a", "b"]), 2, Some(9)));
+        assert_eq!("  [\n    a\n    b\n  ]", render(&list(&["a", "b"]), 2, Some(8)));
+    }
+
+    #[test]
+    fn text_after_group_counts_towards_width() {
+        let doc = Doc::Concat(vec![list(&["a"]), Doc::text(";")]);
+        assert_eq!("[ a ];", render(&doc, 0, Some(6)));
+        assert_eq!("[\n  a\n];", render(&doc, 0, Some(5)));
+    }
+
+    #[test]
+    fn nested_groups() {
+        let inner = list(&["b", "c"]);
+        let doc = Doc::group(Doc::Concat(vec![
+            Doc::text("["),
+            Doc::nest(2, Doc::Concat(vec![Doc::Line, Doc::text("a"), Doc::Line, inner])),
+            Doc::Line,
+            Doc::text("]"),
+        ]));
+        assert_eq!("[ a [ b c ] ]", render(&doc, 0, Some(20)));
+        assert_eq!("[\n  a\n  [ b c ]\n]", render(&doc, 0, Some(10)));
+        assert_eq!("[\n  a\n  [\n    b\n    c\n  ]\n]", render(&doc, 0, Some(6)));
+    }
+
+    #[test]
+    fn hard_line_breaks_group() {
+        let doc = Doc::group(Doc::Concat(vec![
+            Doc::text("a"),
+            Doc::HardLine,
+            Doc::text("b"),
+            Doc::Line,
+            Doc::text("c"),
+        ]));
+        assert_eq!("a\nb\nc", render(&doc, 0, Some(80)));
+    }
+
+    #[test]
+    fn empty_lines_without_indentation() {
+        let doc = Doc::nest(2, Doc::Concat(vec![Doc::text("a"), Doc::HardLine, Doc::HardLine, Doc::text("b")]));
+        assert_eq!("a\n\n  b", render(&doc, 0, None));
+    }
+}
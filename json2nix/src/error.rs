@@ -0,0 +1,221 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    io,
+};
+
+#[derive(Debug)]
+pub enum Json2NixError {
+    /// The input is not valid. `line` and `column` are 1-based and point to the position where the problem was detected, or
+    /// are 0 if the position is unknown.
+    Parse {
+        line: usize,
+        column: usize,
+        category: ParseErrorCategory,
+        message: String,
+    },
+    /// A number is out of the range of Nix and [`crate::UnrepresentableNumbers`] cannot represent it.
+    UnrepresentableNumber { number: String },
+    /// The string or attribute name at the JSON Pointer `pointer` contains a NUL character, which Nix strings cannot
+    /// contain.
+    NulCharacter { pointer: String },
+    /// A raw Nix expression at the JSON Pointer `pointer` is not lexically balanced, see
+    /// [`crate::Json2NixConfig::raw_nix_objects`].
+    InvalidRawNix { pointer: String, message: String },
+    /// The list or attribute set at the JSON Pointer `pointer` is nested deeper than [`crate::Json2NixConfig::max_depth`].
+    TooDeep { pointer: String, max_depth: usize },
+    /// The given [`crate::Json2NixConfig`] cannot be used for the conversion.
+    InvalidConfig(String),
+    /// A value passed to [`crate::to_nix_string`] cannot be converted, e.g. a map with keys that are not scalars or a
+    /// float that is not finite.
+    Serialize(String),
+    /// Reading the input or writing the output failed.
+    Io(io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorCategory {
+    /// The input is not syntactically valid.
+    Syntax,
+    /// The input is syntactically valid, but contains a value that cannot be used, e.g. a value of an unexpected type.
+    Data,
+    /// The input ended unexpectedly.
+    Eof,
+    /// The input is valid, but uses a construct that cannot be converted, e.g. a function in a Nix expression.
+    Unsupported,
+}
+
+impl Json2NixError {
+    /// Creates a [`Json2NixError::Parse`] for the problem at the byte `offset` of `input`.
+    pub(crate) fn parse_at(input: &str, offset: usize, category: ParseErrorCategory, message: impl Into<String>) -> Self {
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+        Json2NixError::Parse {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            category,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ParseErrorCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorCategory::Syntax => write!(f, "syntax error"),
+            ParseErrorCategory::Data => write!(f, "invalid value"),
+            ParseErrorCategory::Eof => write!(f, "unexpected end of input"),
+            ParseErrorCategory::Unsupported => write!(f, "unsupported expression"),
+        }
+    }
+}
+
+impl Display for Json2NixError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json2NixError::Parse {
+                line,
+                column,
+                category,
+                message,
+            } => write!(
+                f,
+                "Could not parse the input, {} at line {}, column {}: {}",
+                category, line, column, message
+            ),
+            Json2NixError::UnrepresentableNumber { number } if number.contains(['.', 'e', 'E']) => {
+                write!(f, "The float {} is out of the range of the 64-bit floats of Nix", number)
+            }
+            Json2NixError::UnrepresentableNumber { number } => {
+                write!(
+                    f,
+                    "The integer {} is out of the range of Nix, which only supports integers from {} to {}",
+                    number,
+                    i64::MIN,
+                    i64::MAX
+                )
+            }
+            Json2NixError::NulCharacter { pointer } => {
+                write!(
+                    f,
+                    "The string at '{}' contains a NUL character, which Nix does not support",
+                    pointer.escape_debug()
+                )
+            }
+            Json2NixError::InvalidRawNix { pointer, message } => {
+                write!(f, "The raw Nix expression at '{}' is invalid: {}", pointer, message)
+            }
+            Json2NixError::TooDeep { pointer, max_depth } => {
+                write!(f, "The value at '{}' is nested deeper than the maximum depth of {}", pointer, max_depth)
+            }
+            Json2NixError::InvalidConfig(message) => write!(f, "Invalid configuration: {}", message),
+            Json2NixError::Serialize(message) => write!(f, "Could not serialize the value: {}", message),
+            Json2NixError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl Error for Json2NixError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Json2NixError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl serde::ser::Error for Json2NixError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Json2NixError::Serialize(msg.to_string())
+    }
+}
+
+impl From<io::Error> for Json2NixError {
+    fn from(err: io::Error) -> Self {
+        Json2NixError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Json2NixError {
+    fn from(err: serde_json::Error) -> Self {
+        let category = match err.classify() {
+            serde_json::error::Category::Io => return Json2NixError::Io(err.into()),
+            serde_json::error::Category::Syntax => ParseErrorCategory::Syntax,
+            serde_json::error::Category::Data => ParseErrorCategory::Data,
+            serde_json::error::Category::Eof => ParseErrorCategory::Eof,
+        };
+
+        // the message of serde_json ends with the position, which is already part of the error
+        let position = format!(" at line {} column {}", err.line(), err.column());
+        let message = err.to_string();
+        let message = message.strip_suffix(&position).unwrap_or(&message).to_string();
+
+        Json2NixError::Parse {
+            line: err.line(),
+            column: err.column(),
+            category,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_error(input: &str) -> Json2NixError {
+        serde_json::from_str::<serde_json::Value>(input).unwrap_err().into()
+    }
+
+    #[test]
+    fn syntax_error() {
+        let err = parse_error("{\n  \"a\": 1,\n  \"b\" 2\n}");
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                line: 3,
+                column: 7,
+                category: ParseErrorCategory::Syntax,
+                ..
+            }
+        ));
+        assert_eq!(
+            "Could not parse the input, syntax error at line 3, column 7: expected `:`",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn eof_error() {
+        let err = parse_error("[1, 2");
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                line: 1,
+                column: 5,
+                category: ParseErrorCategory::Eof,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn data_error() {
+        let err: Json2NixError = serde_json::from_str::<bool>("1").unwrap_err().into();
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                category: ParseErrorCategory::Data,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn io_error() {
+        let err: Json2NixError = io::Error::new(io::ErrorKind::NotFound, "missing").into();
+        assert!(err.source().is_some());
+        assert_eq!("I/O error: missing", err.to_string());
+    }
+}
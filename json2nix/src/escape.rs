@@ -1,25 +1,30 @@
-use std::ops::Not;
+use std::{borrow::Cow, ops::Not};
 
-pub fn escape_attribute_set_key(value: &str) -> String {
-    if needs_escape(&value) {
-        format!("\"{}\"", value.replace("\"", "\\\""))
+use crate::string::escape_string;
+
+pub fn escape_attribute_set_key(value: &str) -> Cow<'_, str> {
+    if needs_escape(value) {
+        Cow::Owned(escape_string(value))
     } else {
-        value.to_string()
+        Cow::Borrowed(value)
     }
 }
 
-const KEYWORDS: [&str; 13] = [
-    "true", "false", "null", "import", "inherit", "rec", "with", "assert", "if", "then", "else", "let", "in",
-];
+// `or` is missing on purpose: it is a keyword only after a selection, as attribute name it is allowed by the Nix grammar
+pub const KEYWORDS: [&str; 9] = ["if", "then", "else", "assert", "with", "let", "in", "rec", "inherit"];
+
+// the Nix lexer turns this identifier into its own token, which evaluates to the current position
+pub const CUR_POS: &str = "__curPos";
+
+// an identifier according to the Nix lexer: [a-zA-Z_][a-zA-Z0-9_'-]*
+pub fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '\'' || c == '-')
+}
 
 fn needs_escape(value: &str) -> bool {
-    value.is_empty()
-        || KEYWORDS.contains(&value)
-        || value.chars().take(1).all(|c| c.is_ascii_alphabetic().not())
-        || value
-            .chars()
-            .all(|c| c.is_ascii_alphabetic() || c.is_ascii_digit() || c == '-' || c == '_')
-            .not()
+    is_identifier(value).not() || KEYWORDS.contains(&value) || value == CUR_POS
 }
 
 #[cfg(test)]
@@ -28,7 +33,10 @@ mod test {
 
     #[test]
     fn needs_no_escape() {
-        let values = ["abc", "a-b", "aa-bb-cc", "AA_BB_CC", "a1", "A1-3"];
+        let values = [
+            "abc", "a-b", "aa-bb-cc", "AA_BB_CC", "a1", "A1-3", "_", "_a", "__", "_1", "a'", "a''b", "a'-_", "x--", "or", "true", "false", "null",
+            "import", "builtins", "__curPo", "_curPos", "if-then", "in_", "letx", "rec'", "inherits", "Else",
+        ];
         for value in values {
             assert_eq!(value, escape_attribute_set_key(value));
         }
@@ -42,9 +50,17 @@ mod test {
         }
     }
 
+    #[test]
+    fn cur_pos() {
+        assert_eq!("\"__curPos\"", escape_attribute_set_key(CUR_POS));
+    }
+
     #[test]
     fn simple_escapes() {
-        let values = ["", " ", "   ", ".", "/", "1", "1a", "1a-", "-", "-a", "_", "_a", "a b"];
+        let values = [
+            "", " ", "   ", ".", "/", "1", "1a", "1a-", "-", "-a", "'", "'a", "a b", "a.b", "a/b", "a:b", "a+b", "a=b", "a;b", "a#b", "a@b", "$a",
+            "ä", "aä", "πr", "🦀",
+        ];
         for value in values {
             let expected = format!("\"{}\"", value);
             assert_eq!(expected, escape_attribute_set_key(value));
@@ -58,4 +74,36 @@ mod test {
             assert_eq!(expected, escape_attribute_set_key(value));
         }
     }
+
+    #[test]
+    fn escapes_inside_quotes() {
+        let values_and_expected = [
+            (r"a\b", r#""a\\b""#),
+            (r"\", r#""\\""#),
+            ("${HOME}", r#""\${HOME}""#),
+            ("a${b}c", r#""a\${b}c""#),
+            ("$HOME", r#""$HOME""#),
+            ("a\nb", r#""a\nb""#),
+            ("a\rb", r#""a\rb""#),
+            ("a\tb", r#""a\tb""#),
+            ("\"${x}\\\"", r#""\"\${x}\\\"""#),
+        ];
+        for (value, expected) in values_and_expected {
+            assert_eq!(expected, escape_attribute_set_key(value));
+        }
+    }
+
+    #[test]
+    fn all_ascii_characters() {
+        for c in (0..=0x7f).map(|c| char::from_u32(c).unwrap()) {
+            let first_is_valid = c.is_ascii_alphabetic() || c == '_';
+            let rest_is_valid = c.is_ascii_alphanumeric() || matches!(c, '_' | '\'' | '-');
+
+            let key = c.to_string();
+            assert_eq!(first_is_valid.not(), escape_attribute_set_key(&key).starts_with('"'), "key: {:?}", key);
+
+            let key = format!("a{}", c);
+            assert_eq!(rest_is_valid.not(), escape_attribute_set_key(&key).starts_with('"'), "key: {:?}", key);
+        }
+    }
 }
@@ -0,0 +1,503 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{Binding, NixExpr, StringPart, drop_expr},
+    escape::{KEYWORDS, is_identifier},
+};
+
+// names that must not be shadowed by the let bindings, in addition to the identifiers used in raw expressions
+const RESERVED_NAMES: [&str; 8] = ["true", "false", "null", "or", "builtins", "config", "lib", "pkgs"];
+
+/// Hoists lists and attribute sets that occur more than once and contain at least `min_size` values into a `let … in`
+/// header and references them by name. The names are derived from the attribute of the first occurrence, e.g. `palette`
+/// for `{ palette = { … }; }`. Comments of the root stay in front of the `let`. All passes keep their work on a stack
+/// instead of the call stack, so expressions of any depth can be processed.
+pub fn extract_repeated(expr: NixExpr, min_size: usize) -> NixExpr {
+    if let NixExpr::Commented { comments, expr } = expr {
+        return NixExpr::Commented {
+            comments,
+            expr: Box::new(extract_repeated(*expr, min_size)),
+        };
+    }
+
+    let mut counter = Counter {
+        min_size,
+        nodes: Vec::new(),
+        classes: HashMap::new(),
+        counts: Vec::new(),
+        identifiers: HashSet::new(),
+    };
+    counter.visit(&expr);
+
+    let Counter {
+        nodes, counts, identifiers, ..
+    } = counter;
+    let mut extractor = Extractor {
+        nodes,
+        counts,
+        reserved: identifiers,
+        cursor: 0,
+        names: HashMap::new(),
+        numbers: HashMap::new(),
+        definitions: Vec::new(),
+    };
+    let body = extractor.rewrite(expr);
+
+    inline_single_references(extractor.definitions, body)
+}
+
+/// Everything of an expression but its nested expressions, which are compared by their class instead.
+#[derive(PartialEq, Eq, Hash)]
+enum Shape<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(u64),
+    String(&'a str),
+    InterpolatedString(Vec<(bool, &'a str)>),
+    Path(&'a str),
+    List,
+    AttrSet(Vec<(&'a [String], &'a [String])>),
+    Raw(&'a str),
+    Apply(&'a str),
+    Function(&'a [String]),
+    Let(Vec<(&'a [String], &'a [String])>),
+    Commented(&'a [String]),
+}
+
+impl<'a> Shape<'a> {
+    fn new(expr: &'a NixExpr) -> Self {
+        let bindings = |bindings: &'a [Binding]| {
+            bindings
+                .iter()
+                .map(|binding| (binding.path.as_slice(), binding.comments.as_slice()))
+                .collect()
+        };
+
+        match expr {
+            NixExpr::Null => Shape::Null,
+            NixExpr::Bool(bool) => Shape::Bool(*bool),
+            NixExpr::Int(integer) => Shape::Int(*integer),
+            NixExpr::Float(float) => Shape::Float(float.to_bits()),
+            NixExpr::String(string) => Shape::String(string),
+            NixExpr::InterpolatedString(parts) => Shape::InterpolatedString(
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        StringPart::Literal(literal) => (false, literal.as_str()),
+                        StringPart::Interpolation(interpolation) => (true, interpolation.as_str()),
+                    })
+                    .collect(),
+            ),
+            NixExpr::Path(path) => Shape::Path(path),
+            NixExpr::List(_) => Shape::List,
+            NixExpr::AttrSet(attributes) => Shape::AttrSet(bindings(attributes)),
+            NixExpr::Raw(raw) => Shape::Raw(raw),
+            NixExpr::Apply { function, .. } => Shape::Apply(function),
+            NixExpr::Function { formals, .. } => Shape::Function(formals),
+            NixExpr::Let { bindings: attributes, .. } => Shape::Let(bindings(attributes)),
+            NixExpr::Commented { comments, .. } => Shape::Commented(comments),
+        }
+    }
+}
+
+// The first pass assigns every expression to a class of structurally identical ones, and every list and attribute set
+// that is large enough to the group of its class. The nodes are stored in pre-order, so the second pass can find them
+// again while it consumes the expression.
+struct Counter<'a> {
+    min_size: usize,
+    /// The number of nodes in the subtree and the group of every node in pre-order.
+    nodes: Vec<(usize, Option<usize>)>,
+    /// The classes by the shape of their expressions and the classes of the nested expressions.
+    classes: HashMap<(Shape<'a>, Vec<usize>), usize>,
+    /// The number of occurrences of every class.
+    counts: Vec<usize>,
+    /// All identifiers that appear in raw expressions, interpolations and function applications.
+    identifiers: HashSet<String>,
+}
+
+enum Visit<'a> {
+    /// Adds the node of the expression and visits the nested expressions.
+    Enter(&'a NixExpr),
+    /// Assigns the class to the expression with the given node, whose nested expressions were visited and left their
+    /// classes and sizes from the given index on.
+    Exit(&'a NixExpr, usize, usize),
+}
+
+impl<'a> Counter<'a> {
+    fn visit(&mut self, expr: &'a NixExpr) {
+        let mut tasks = vec![Visit::Enter(expr)];
+        // the classes and sizes of the visited expressions, which are taken by the expression they are nested in
+        let mut visited: Vec<(usize, usize)> = Vec::new();
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Visit::Enter(expr) => {
+                    tasks.push(Visit::Exit(expr, self.nodes.len(), visited.len()));
+                    tasks.extend(expr.children().rev().map(Visit::Enter));
+                    self.nodes.push((0, None));
+                }
+                Visit::Exit(expr, index, start) => {
+                    match expr {
+                        NixExpr::InterpolatedString(parts) => {
+                            for part in parts {
+                                if let StringPart::Interpolation(interpolation) = part {
+                                    self.add_identifiers(interpolation);
+                                }
+                            }
+                        }
+                        NixExpr::Raw(raw) | NixExpr::Apply { function: raw, .. } => self.add_identifiers(raw),
+                        _ => {}
+                    }
+
+                    let (classes, sizes): (Vec<usize>, Vec<usize>) = visited.drain(start..).unzip();
+                    // comments do not count as values
+                    let size = sizes.into_iter().sum::<usize>() + usize::from(!matches!(expr, NixExpr::Commented { .. }));
+                    let class = self.class(Shape::new(expr), classes);
+
+                    let group = match expr {
+                        NixExpr::List(elements) if !elements.is_empty() && size >= self.min_size => Some(class),
+                        NixExpr::AttrSet(bindings) if !bindings.is_empty() && size >= self.min_size => Some(class),
+                        _ => None,
+                    };
+                    self.nodes[index] = (self.nodes.len() - index, group);
+                    visited.push((class, size));
+                }
+            }
+        }
+    }
+
+    fn class(&mut self, shape: Shape<'a>, children: Vec<usize>) -> usize {
+        let next = self.counts.len();
+        let class = *self.classes.entry((shape, children)).or_insert(next);
+        if class == next {
+            self.counts.push(0);
+        }
+        self.counts[class] += 1;
+        class
+    }
+
+    fn add_identifiers(&mut self, raw: &str) {
+        let identifiers = raw.split(|c: char| !(c.is_ascii_alphanumeric() || "_'-".contains(c)));
+        self.identifiers
+            .extend(identifiers.filter(|identifier| !identifier.is_empty()).map(str::to_string));
+    }
+}
+
+// The second pass replaces every occurrence of a group that occurs more than once by a reference. The first occurrence
+// becomes the definition, the others are skipped.
+struct Extractor {
+    nodes: Vec<(usize, Option<usize>)>,
+    counts: Vec<usize>,
+    reserved: HashSet<String>,
+    cursor: usize,
+    /// The names of the groups that were extracted.
+    names: HashMap<usize, String>,
+    /// The last number that was tried for every name, so the numbered names are not tried again.
+    numbers: HashMap<String, usize>,
+    definitions: Vec<(String, Option<NixExpr>)>,
+}
+
+enum Rewrite {
+    /// Rewrites the expression, which is named after the hint if it is extracted.
+    Expr(NixExpr, String),
+    /// Puts the rewritten nested expressions from the given index on back into the expression. If it is extracted, it
+    /// becomes the definition with the given index.
+    Build(NixExpr, usize, Option<usize>),
+}
+
+impl Extractor {
+    fn rewrite(&mut self, expr: NixExpr) -> NixExpr {
+        let mut tasks = vec![Rewrite::Expr(expr, "shared".to_string())];
+        let mut rewritten = Vec::new();
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Rewrite::Expr(mut expr, hint) => {
+                    let (node_count, group) = self.nodes[self.cursor];
+
+                    let definition = match group.filter(|group| self.counts[*group] > 1) {
+                        None => None,
+                        Some(group) => match self.names.get(&group) {
+                            Some(name) => {
+                                self.cursor += node_count;
+                                rewritten.push(NixExpr::Raw(name.clone()));
+                                drop_expr(expr);
+                                continue;
+                            }
+                            None => {
+                                let name = self.name(&hint);
+                                self.names.insert(group, name.clone());
+                                self.definitions.push((name, None));
+                                Some(self.definitions.len() - 1)
+                            }
+                        },
+                    };
+                    self.cursor += 1;
+
+                    let hint = definition.map_or(hint, |index| self.definitions[index].0.clone());
+                    let hints = child_hints(&expr, &hint);
+                    let children = take_children(&mut expr);
+                    tasks.push(Rewrite::Build(expr, rewritten.len(), definition));
+                    tasks.extend(children.into_iter().zip(hints).rev().map(|(child, hint)| Rewrite::Expr(child, hint)));
+                }
+                Rewrite::Build(mut expr, start, definition) => {
+                    restore_children(&mut expr, rewritten.drain(start..));
+                    match definition {
+                        Some(index) => {
+                            rewritten.push(NixExpr::Raw(self.definitions[index].0.clone()));
+                            self.definitions[index].1 = Some(expr);
+                        }
+                        None => rewritten.push(expr),
+                    }
+                }
+            }
+        }
+
+        rewritten.pop().expect("the expression is rewritten")
+    }
+
+    // attribute names like `terminal-colors` or `font size` become `terminal-colors` and `fontSize`
+    fn name(&mut self, hint: &str) -> String {
+        let mut name = String::new();
+        for (index, word) in hint
+            .split(|c: char| !(c.is_ascii_alphanumeric() || "_'-".contains(c)))
+            .filter(|word| !word.is_empty())
+            .enumerate()
+        {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                name.push(if index == 0 { first } else { first.to_ascii_uppercase() });
+                name.extend(chars);
+            }
+        }
+        if !is_identifier(&name) {
+            name = "shared".to_string();
+        }
+
+        let number = self.numbers.entry(name.clone()).or_insert(0);
+        let name = (*number + 1..)
+            .map(|next| {
+                *number = next;
+                if next == 1 { name.clone() } else { format!("{}{}", name, next) }
+            })
+            .find(|name| !KEYWORDS.contains(&name.as_str()) && !RESERVED_NAMES.contains(&name.as_str()) && !self.reserved.contains(name))
+            .expect("there are infinitely many names");
+        self.reserved.insert(name.clone());
+        name
+    }
+}
+
+// The names for the nested expressions of `expr`: elements are named after the list, values after their attribute.
+fn child_hints(expr: &NixExpr, hint: &str) -> Vec<String> {
+    let binding_hint = |binding: &Binding| binding.path.last().map_or(hint, String::as_str).to_string();
+    match expr {
+        NixExpr::List(elements) => vec![format!("{}Element", hint); elements.len()],
+        NixExpr::AttrSet(bindings) => bindings.iter().map(binding_hint).collect(),
+        NixExpr::Let { bindings, .. } => bindings.iter().map(binding_hint).chain([hint.to_string()]).collect(),
+        expr => expr.children().map(|_| hint.to_string()).collect(),
+    }
+}
+
+// Takes the nested expressions out of `expr`, so they can be processed one after another and put back with
+// `restore_children`.
+fn take_children(expr: &mut NixExpr) -> Vec<NixExpr> {
+    expr.children_mut().map(|child| std::mem::replace(child, NixExpr::Null)).collect()
+}
+
+fn restore_children(expr: &mut NixExpr, children: impl Iterator<Item = NixExpr>) {
+    for (child, value) in expr.children_mut().zip(children) {
+        *child = value;
+    }
+}
+
+// Sets that occur in other extracted sets are extracted as well, even if they do not occur anywhere else. Those are
+// only referenced once and put back in place.
+fn inline_single_references(definitions: Vec<(String, Option<NixExpr>)>, body: NixExpr) -> NixExpr {
+    let mut references = HashMap::new();
+    for (name, _) in &definitions {
+        references.insert(name.clone(), 0);
+    }
+    count_references(&body, &mut references);
+    for (_, definition) in &definitions {
+        count_references(definition.as_ref().expect("all definitions are complete"), &mut references);
+    }
+
+    let (kept, inlined): (Vec<_>, Vec<_>) = definitions.into_iter().partition(|(name, _)| references[name] > 1);
+    let mut inlined = inlined.into_iter().collect::<HashMap<_, _>>();
+
+    let bindings = kept
+        .into_iter()
+        .map(|(name, definition)| {
+            let value = inline(definition.expect("all definitions are complete"), &mut inlined);
+            Binding::new(vec![name], value)
+        })
+        .collect::<Vec<_>>();
+    let body = inline(body, &mut inlined);
+
+    match bindings.is_empty() {
+        true => body,
+        false => NixExpr::Let {
+            bindings,
+            body: Box::new(body),
+        },
+    }
+}
+
+fn count_references(expr: &NixExpr, references: &mut HashMap<String, usize>) {
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        if let NixExpr::Raw(raw) = expr
+            && let Some(count) = references.get_mut(raw)
+        {
+            *count += 1;
+        }
+        stack.extend(expr.children());
+    }
+}
+
+enum Inline {
+    /// Replaces the expression by its definition if it is a reference that is inlined, or inlines its nested expressions.
+    Expr(NixExpr),
+    /// Puts the nested expressions from the given index on back into the expression.
+    Build(NixExpr, usize),
+}
+
+fn inline(expr: NixExpr, inlined: &mut HashMap<String, Option<NixExpr>>) -> NixExpr {
+    let mut tasks = vec![Inline::Expr(expr)];
+    let mut exprs = Vec::new();
+
+    while let Some(task) = tasks.pop() {
+        match task {
+            Inline::Expr(NixExpr::Raw(raw)) => match inlined.get_mut(&raw).and_then(Option::take) {
+                Some(definition) => tasks.push(Inline::Expr(definition)),
+                None => exprs.push(NixExpr::Raw(raw)),
+            },
+            Inline::Expr(mut expr) => {
+                let children = take_children(&mut expr);
+                tasks.push(Inline::Build(expr, exprs.len()));
+                tasks.extend(children.into_iter().rev().map(Inline::Expr));
+            }
+            Inline::Build(mut expr, start) => {
+                restore_children(&mut expr, exprs.drain(start..));
+                exprs.push(expr);
+            }
+        }
+    }
+
+    exprs.pop().expect("the expression is inlined")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn set(bindings: Vec<(&str, NixExpr)>) -> NixExpr {
+        NixExpr::AttrSet(
+            bindings
+                .into_iter()
+                .map(|(key, value)| Binding::new(vec![key.to_string()], value))
+                .collect(),
+        )
+    }
+
+    fn raw(raw: &str) -> NixExpr {
+        NixExpr::Raw(raw.to_string())
+    }
+
+    fn color() -> NixExpr {
+        set(vec![
+            ("fg", NixExpr::String("#fff".to_string())),
+            ("bg", NixExpr::String("#000".to_string())),
+        ])
+    }
+
+    #[test]
+    fn repeated_sets() {
+        let expr = set(vec![
+            ("header", color()),
+            ("footer", set(vec![("colors", color()), ("height", NixExpr::Int(1))])),
+            ("list", NixExpr::List(vec![color()])),
+        ]);
+        let expected = NixExpr::Let {
+            bindings: vec![Binding::new(vec!["header".to_string()], color())],
+            body: Box::new(set(vec![
+                ("header", raw("header")),
+                ("footer", set(vec![("colors", raw("header")), ("height", NixExpr::Int(1))])),
+                ("list", NixExpr::List(vec![raw("header")])),
+            ])),
+        };
+        assert_eq!(expected, extract_repeated(expr, 3));
+    }
+
+    #[test]
+    fn below_threshold() {
+        let expr = set(vec![("a", color()), ("b", color())]);
+        assert_eq!(expr, extract_repeated(expr.clone(), 4));
+        assert_eq!(
+            set(vec![("a", NixExpr::Int(1)), ("b", NixExpr::Int(1))]),
+            extract_repeated(set(vec![("a", NixExpr::Int(1)), ("b", NixExpr::Int(1))]), 1)
+        );
+    }
+
+    #[test]
+    fn nested_repetitions() {
+        // the colors only occur inside the repeated block, so they are not extracted on their own
+        let block = || set(vec![("colors", color()), ("style", NixExpr::String("round".to_string()))]);
+        let expr = NixExpr::List(vec![block(), block(), set(vec![("palette", color())])]);
+
+        let expected = NixExpr::Let {
+            bindings: vec![
+                Binding::new(
+                    vec!["sharedElement".to_string()],
+                    set(vec![("colors", raw("colors")), ("style", NixExpr::String("round".to_string()))]),
+                ),
+                Binding::new(vec!["colors".to_string()], color()),
+            ],
+            body: Box::new(NixExpr::List(vec![
+                raw("sharedElement"),
+                raw("sharedElement"),
+                set(vec![("palette", raw("colors"))]),
+            ])),
+        };
+        assert_eq!(expected, extract_repeated(expr, 3));
+
+        let expr = NixExpr::List(vec![block(), block()]);
+        let expected = NixExpr::Let {
+            bindings: vec![Binding::new(vec!["sharedElement".to_string()], block())],
+            body: Box::new(NixExpr::List(vec![raw("sharedElement"), raw("sharedElement")])),
+        };
+        assert_eq!(expected, extract_repeated(expr, 3));
+    }
+
+    #[test]
+    fn names() {
+        let expr = set(vec![
+            ("terminal colors", color()),
+            ("in", color()),
+            ("pkgs", set(vec![("x", raw("lib.mkDefault pkgs.hello"))])),
+            ("lib", set(vec![("x", raw("lib.mkDefault pkgs.hello"))])),
+            ("1", NixExpr::List(vec![NixExpr::Int(1), NixExpr::Int(2)])),
+            ("2", NixExpr::List(vec![NixExpr::Int(1), NixExpr::Int(2)])),
+        ]);
+        let NixExpr::Let { bindings, .. } = extract_repeated(expr, 2) else {
+            panic!("the expression was not extracted");
+        };
+        let names = bindings.iter().map(|binding| binding.path.join(".")).collect::<Vec<_>>();
+        assert_eq!(vec!["terminalColors", "pkgs2", "shared"], names);
+    }
+
+    #[test]
+    fn comments() {
+        let commented = |expr: NixExpr| NixExpr::Commented {
+            comments: vec!["comment".to_string()],
+            expr: Box::new(expr),
+        };
+        let expr = commented(NixExpr::List(vec![commented(color()), color()]));
+        let expected = commented(NixExpr::Let {
+            bindings: vec![Binding::new(vec!["sharedElement".to_string()], color())],
+            body: Box::new(NixExpr::List(vec![commented(raw("sharedElement")), raw("sharedElement")])),
+        });
+        assert_eq!(expected, extract_repeated(expr, 3));
+    }
+}
@@ -0,0 +1,433 @@
+use serde_json::{Map, Number, Value};
+
+use crate::{Json2NixError, ParseErrorCategory};
+
+/// Parses INI files like git-config. `[section "subsection"]` becomes a nested attribute set, repeated keys become a list
+/// and unquoted booleans and numbers are typed. Keys before the first section are at the top level.
+///
+/// Unlike git-config, `#` and `;` only start a comment at the beginning of a value or after whitespace, so URLs with
+/// fragments survive. Old-style `[section.subsection]` headers are not split, since dots are common in the section
+/// names of other INI files.
+pub fn parse_ini(input: &str) -> Result<Value, Json2NixError> {
+    let mut parser = Parser { input, position: 0 };
+    let mut root = Map::new();
+    let mut section = Vec::new();
+
+    loop {
+        parser.skip_whitespace_and_line_breaks();
+        match parser.peek() {
+            None => return Ok(Value::Object(root)),
+            Some('#' | ';') => parser.skip_line(),
+            Some('[') => {
+                let start = parser.position;
+                section = parser.section_header()?;
+                section_object(&mut root, &section).map_err(|message| parser.error_at(start, message))?;
+            }
+            Some(_) => {
+                let start = parser.position;
+                let (key, value) = parser.key_value()?;
+                let object = section_object(&mut root, &section).map_err(|message| parser.error_at(start, message))?;
+                insert(object, key, value).map_err(|message| parser.error_at(start, message))?;
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// The byte offset of the next character.
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.position += bytes;
+    }
+
+    fn error_at(&self, position: usize, message: impl Into<String>) -> Json2NixError {
+        Json2NixError::parse_at(self.input, position, ParseErrorCategory::Syntax, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.advance(rest.len() - rest.trim_start_matches([' ', '\t']).len());
+    }
+
+    fn skip_whitespace_and_line_breaks(&mut self) {
+        let rest = self.rest();
+        self.advance(rest.len() - rest.trim_start().len());
+    }
+
+    fn skip_line(&mut self) {
+        let rest = self.rest();
+        self.advance(rest.find('\n').unwrap_or(rest.len()));
+    }
+
+    // Only whitespace and a comment may follow a section header.
+    fn end_of_line(&mut self) -> Result<(), Json2NixError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('\n' | '\r' | '#' | ';') => {
+                self.skip_line();
+                Ok(())
+            }
+            Some(c) => Err(self.error_at(self.position, format!("expected the end of the line, found `{}`", c))),
+        }
+    }
+
+    fn section_header(&mut self) -> Result<Vec<String>, Json2NixError> {
+        let start = self.position;
+        self.advance(1);
+
+        let rest = self.rest();
+        let Some(length) = rest.find([']', '"', '\n']) else {
+            return Err(self.error_at(start, "unterminated section header"));
+        };
+        let name = rest[..length].trim().to_string();
+        if name.is_empty() {
+            return Err(self.error_at(start, "expected a section name"));
+        }
+        self.advance(length);
+
+        let mut section = vec![name];
+        if self.peek() == Some('"') {
+            section.push(self.subsection()?);
+        }
+
+        if self.peek() != Some(']') {
+            return Err(self.error_at(start, "unterminated section header"));
+        }
+        self.advance(1);
+        self.end_of_line()?;
+
+        Ok(section)
+    }
+
+    fn subsection(&mut self) -> Result<String, Json2NixError> {
+        let start = self.position;
+        self.advance(1);
+        let mut subsection = String::new();
+
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err(self.error_at(start, "unterminated subsection name")),
+                Some('"') => {
+                    self.advance(1);
+                    return Ok(subsection);
+                }
+                Some('\\') => {
+                    self.advance(1);
+                    if let Some(c) = self.peek().filter(|c| *c != '\n') {
+                        self.advance(c.len_utf8());
+                        subsection.push(c);
+                    }
+                }
+                Some(c) => {
+                    self.advance(c.len_utf8());
+                    subsection.push(c);
+                }
+            }
+        }
+    }
+
+    fn key_value(&mut self) -> Result<(String, Value), Json2NixError> {
+        let start = self.position;
+        let rest = self.rest();
+        let length = rest.find(['=', '\n', '#', ';']).unwrap_or(rest.len());
+        let key = rest[..length].trim_end().to_string();
+        if key.is_empty() {
+            return Err(self.error_at(start, "expected a key"));
+        }
+        self.advance(length);
+
+        // a key without a value is a boolean, e.g. `[core] bare`
+        if self.peek() != Some('=') {
+            self.skip_line();
+            return Ok((key, Value::Bool(true)));
+        }
+        self.advance(1);
+        self.skip_whitespace();
+
+        Ok((key, self.value()?))
+    }
+
+    // Values follow git-config: quotes can surround parts of a value, backslashes escape quotes, backslashes, `\n`, `\t`
+    // and `\b`, and a backslash at the end of a line continues the value on the next line.
+    fn value(&mut self) -> Result<Value, Json2NixError> {
+        let start = self.position;
+        let mut value = String::new();
+        // the length of the value without trailing whitespace outside of quotes
+        let mut trimmed_length = 0;
+        let mut in_quotes = false;
+        let mut has_quotes = false;
+
+        loop {
+            let Some(c) = self.peek() else {
+                break;
+            };
+
+            match c {
+                '\r' if self.rest().starts_with("\r\n") && !in_quotes => break,
+                '\n' if in_quotes => return Err(self.error_at(start, "unterminated quote")),
+                '\n' => break,
+                '"' => {
+                    in_quotes = !in_quotes;
+                    has_quotes = true;
+                    self.advance(1);
+                    trimmed_length = value.len();
+                }
+                '#' | ';' if !in_quotes && (value.is_empty() || value.ends_with([' ', '\t'])) => {
+                    self.skip_line();
+                    break;
+                }
+                '\\' => {
+                    self.advance(1);
+                    match self.peek() {
+                        Some('\n') => self.advance(1),
+                        Some('\r') if self.rest().starts_with("\r\n") => self.advance(2),
+                        Some(escaped @ ('n' | 't' | 'b' | '"' | '\\')) => {
+                            self.advance(1);
+                            value.push(match escaped {
+                                'n' => '\n',
+                                't' => '\t',
+                                'b' => '\u{8}',
+                                escaped => escaped,
+                            });
+                        }
+                        // unknown escapes are kept, so Windows paths survive
+                        _ => value.push('\\'),
+                    }
+                    trimmed_length = value.len();
+                }
+                c => {
+                    self.advance(c.len_utf8());
+                    value.push(c);
+                    if in_quotes || !matches!(c, ' ' | '\t') {
+                        trimmed_length = value.len();
+                    }
+                }
+            }
+        }
+
+        if in_quotes {
+            return Err(self.error_at(start, "unterminated quote"));
+        }
+        value.truncate(trimmed_length);
+
+        Ok(if has_quotes { Value::String(value) } else { typed_value(value) })
+    }
+}
+
+fn typed_value(value: String) -> Value {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => return Value::Bool(true),
+        "false" | "no" | "off" => return Value::Bool(false),
+        _ => {}
+    }
+
+    // only numbers in their canonical spelling are typed, so `0022`, `+1` or `1.10` stay strings instead of losing characters
+    let digits = value.strip_prefix('-').unwrap_or(&value);
+    let is_number = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    let number = match digits.split_once('.') {
+        None if is_number(digits) => value.parse::<i64>().ok().map(Number::from),
+        Some((integer, fraction)) if is_number(integer) && is_number(fraction) => value.parse::<f64>().ok().and_then(Number::from_f64),
+        _ => None,
+    };
+    match number {
+        Some(number) if number.to_string() == value => Value::Number(number),
+        _ => Value::String(value),
+    }
+}
+
+fn section_object<'a>(root: &'a mut Map<String, Value>, section: &[String]) -> Result<&'a mut Map<String, Value>, String> {
+    let mut object = root;
+    for name in section {
+        object = match object.entry(name.clone()).or_insert_with(|| Value::Object(Map::new())) {
+            Value::Object(nested) => nested,
+            _ => return Err(format!("the section `{}` conflicts with the key of the same name", section.join("."))),
+        };
+    }
+    Ok(object)
+}
+
+fn insert(object: &mut Map<String, Value>, key: String, value: Value) -> Result<(), String> {
+    match object.get_mut(&key) {
+        None => {
+            object.insert(key, value);
+        }
+        Some(Value::Object(_)) => return Err(format!("the key `{}` conflicts with the subsection of the same name", key)),
+        // keys can be repeated, e.g. `fetch` of remotes in git-config
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(input: &str) -> Value {
+        parse_ini(input).unwrap()
+    }
+
+    #[test]
+    fn gitconfig() {
+        let input = r#"
+# user settings
+[user]
+	name = Jane Doe
+	email = jane@example.com ; inline comment
+[core]
+	autocrlf = input
+	bare
+[remote "origin"]
+	url = https://example.com/repo.git#fragment
+	fetch = +refs/heads/*:refs/remotes/origin/*
+	fetch = +refs/tags/*:refs/tags/*
+[branch "feature/\"quoted\""]
+	merge = refs/heads/main
+[pull]
+	rebase = false
+	depth = 10
+	ratio = -0.5
+"#;
+        let expected = json!({
+            "user": { "name": "Jane Doe", "email": "jane@example.com" },
+            "core": { "autocrlf": "input", "bare": true },
+            "remote": { "origin": {
+                "url": "https://example.com/repo.git#fragment",
+                "fetch": ["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"],
+            } },
+            "branch": { "feature/\"quoted\"": { "merge": "refs/heads/main" } },
+            "pull": { "rebase": false, "depth": 10, "ratio": -0.5 },
+        });
+        assert_eq!(expected, parse(input));
+    }
+
+    #[test]
+    fn quoted_values() {
+        let input = r##"
+[alias]
+	lg = "log --graph" --oneline # comment
+	quoted = "true"
+	escapes = "a\tb\\c\"d" \
+	  continued
+	spaces = "  padded  "
+	hash = "# not a comment"
+	path = C:\Users\jane
+	empty =
+"##;
+        let expected = json!({ "alias": {
+            "lg": "log --graph --oneline",
+            "quoted": "true",
+            "escapes": "a\tb\\c\"d \t  continued",
+            "spaces": "  padded  ",
+            "hash": "# not a comment",
+            "path": "C:\\Users\\jane",
+            "empty": "",
+        } });
+        assert_eq!(expected, parse(input));
+    }
+
+    #[test]
+    fn generic_ini() {
+        let input = "global = 1\r\n\r\n[Desktop Entry]\r\nName=Firefox\r\nExec=firefox %u\r\n\r\n[Service]\r\nEnvironment=A=1\r\nEnvironment=B=2\r\n[Service]\r\nType=simple";
+        let expected = json!({
+            "global": 1,
+            "Desktop Entry": { "Name": "Firefox", "Exec": "firefox %u" },
+            "Service": { "Environment": ["A=1", "B=2"], "Type": "simple" },
+        });
+        assert_eq!(expected, parse(input));
+    }
+
+    #[test]
+    fn sections_and_subsections_are_merged() {
+        let input = "[remote]\npushDefault = origin\n[remote \"origin\"]\nurl = a\n[empty]";
+        assert_eq!(
+            json!({ "remote": { "pushDefault": "origin", "origin": { "url": "a" } }, "empty": {} }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn comments_and_dotted_sections() {
+        let input = "[branch.main]\nremote = origin;comment\nurl = a#b ; comment\n; comment\n# comment\nmerge = # comment";
+        assert_eq!(
+            json!({ "branch.main": { "remote": "origin;comment", "url": "a#b", "merge": "" } }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn typed_values() {
+        let cases = [
+            ("yes", json!(true)),
+            ("Off", json!(false)),
+            ("42", json!(42)),
+            ("-42", json!(-42)),
+            ("0", json!(0)),
+            ("1.5", json!(1.5)),
+            ("-0.25", json!(-0.25)),
+            ("+42", json!("+42")),
+            ("-0", json!("-0")),
+            ("0755", json!("0755")),
+            ("0022", json!("0022")),
+            ("1.10", json!("1.10")),
+            ("1.0", json!(1.0)),
+            ("00.5", json!("00.5")),
+            ("1.", json!("1.")),
+            ("1e5", json!("1e5")),
+            ("99999999999999999999", json!("99999999999999999999")),
+            ("0.1000000000000000000001", json!("0.1000000000000000000001")),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(expected, typed_value(input.to_string()), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("[section\nkey = 1", (1, 1, "unterminated section header")),
+            ("[section \"sub]\n", (1, 10, "unterminated subsection name")),
+            ("[a] b", (1, 5, "expected the end of the line, found `b`")),
+            ("[a]\n= 1", (2, 1, "expected a key")),
+            ("[a]\nb = \"1\n", (2, 5, "unterminated quote")),
+            ("a = 1\n[a]", (2, 1, "the section `a` conflicts with the key of the same name")),
+            (
+                "[a \"b\"]\n[a]\nb = 1",
+                (3, 1, "the key `b` conflicts with the subsection of the same name"),
+            ),
+        ];
+
+        for (input, (line, column, message)) in cases {
+            match parse_ini(input) {
+                Err(Json2NixError::Parse {
+                    line: actual_line,
+                    column: actual_column,
+                    message: actual_message,
+                    ..
+                }) => assert_eq!(
+                    (line, column, message.to_string()),
+                    (actual_line, actual_column, actual_message),
+                    "input: {}",
+                    input
+                ),
+                result => panic!("unexpected result for {}: {:?}", input, result),
+            }
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use std::io::BufRead;
+
+use serde_json::{Map, Number, Value};
+
+use crate::{Json2NixError, ParseErrorCategory};
+
+/// Parses JSON with the [`Parser`] instead of serde_json, which is recursive and rejects input that is nested more than 128
+/// levels deep. The values and errors are the same as those of serde_json, except for duplicate keys, see [`insert`].
+pub fn parse_json(input: &str) -> Result<Value, Json2NixError> {
+    let mut parser = Parser::new(input.as_bytes());
+    let mut builder = ValueBuilder::new();
+    while let Some(event) = parser.next()? {
+        builder.event(event).map_err(|message| parser.error(ParseErrorCategory::Data, &message))?;
+    }
+    Ok(builder.finish())
+}
+
+/// Builds a [`Value`] from the [`Event`]s of a JSON document.
+pub(crate) struct ValueBuilder {
+    /// The lists and objects that are not complete yet, with the key of the next value of objects.
+    containers: Vec<(Value, String)>,
+    root: Option<Value>,
+}
+
+impl ValueBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            containers: Vec::new(),
+            root: None,
+        }
+    }
+
+    /// Adds the next event of the document. The error is the message for a duplicate key that cannot be merged.
+    pub(crate) fn event(&mut self, event: Event) -> Result<(), String> {
+        let value = match event {
+            Event::StartList => {
+                self.containers.push((Value::Array(Vec::new()), String::new()));
+                return Ok(());
+            }
+            Event::StartObject => {
+                self.containers.push((Value::Object(Map::new()), String::new()));
+                return Ok(());
+            }
+            Event::Key(key) => {
+                self.containers.last_mut().expect("keys are inside of objects").1 = key;
+                return Ok(());
+            }
+            Event::Scalar(value) => value,
+            Event::End => self.containers.pop().expect("the container was started").0,
+        };
+
+        match self.containers.last_mut() {
+            Some((Value::Array(elements), _)) => elements.push(value),
+            Some((Value::Object(object), key)) => insert(object, std::mem::take(key), value)?,
+            Some(_) => unreachable!("only lists and objects are containers"),
+            None => self.root = Some(value),
+        }
+        Ok(())
+    }
+
+    /// Returns the value after the events of the whole document were added.
+    pub(crate) fn finish(mut self) -> Value {
+        self.root.take().expect("the document is complete")
+    }
+}
+
+impl Drop for ValueBuilder {
+    // the partially built values are only left over if the document is invalid
+    fn drop(&mut self) {
+        self.containers
+            .drain(..)
+            .map(|(value, _)| value)
+            .chain(self.root.take())
+            .for_each(drop_value);
+    }
+}
+
+/// Inserts `value` at `key` and handles duplicate keys like Nix handles duplicate attributes: the attributes of two
+/// objects are merged, anything else is rejected. [`crate::json2nix_stream`] writes duplicate keys as they are, so the
+/// output is evaluated in the same way without keeping all keys in memory.
+fn insert(object: &mut Map<String, Value>, key: String, value: Value) -> Result<(), String> {
+    let Some(existing) = object.get_mut(&key) else {
+        object.insert(key, value);
+        return Ok(());
+    };
+
+    match (existing, value) {
+        (Value::Object(existing), Value::Object(new)) => {
+            let mut new = new.into_iter();
+            while let Some((nested_key, nested_value)) = new.next() {
+                if existing.contains_key(&nested_key) {
+                    new.map(|(_, value)| value).chain([nested_value]).for_each(drop_value);
+                    return Err(format!("duplicate key `{}` in the objects of the duplicate key `{}`", nested_key, key));
+                }
+                existing.insert(nested_key, nested_value);
+            }
+            Ok(())
+        }
+        (_, value) => {
+            drop_value(value);
+            Err(format!("duplicate key `{}`", key))
+        }
+    }
+}
+
+/// Drops `value` without recursion, which would overflow the stack for deeply nested values.
+pub fn drop_value(value: Value) {
+    // only the nested lists and objects are taken out, scalars are dropped in place
+    let mut stack = vec![value];
+    while let Some(mut value) = stack.pop() {
+        let nested: &mut dyn Iterator<Item = &mut Value> = match &mut value {
+            Value::Array(elements) => &mut elements.iter_mut(),
+            Value::Object(object) => &mut object.values_mut(),
+            _ => continue,
+        };
+        stack.extend(
+            nested
+                .filter(|value| matches!(value, Value::Array(_) | Value::Object(_)))
+                .map(Value::take),
+        );
+    }
+}
+
+/// A part of a JSON document, in the order of the document.
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    /// The start of a list, which is followed by the events of its elements and [`Event::End`].
+    StartList,
+    /// The start of an object, which is followed by a [`Event::Key`] and the events of the value for each of its entries
+    /// and [`Event::End`].
+    StartObject,
+    Key(String),
+    /// A value that is neither a list nor an object.
+    Scalar(Value),
+    /// The end of the innermost list or object.
+    End,
+}
+
+/// A pull parser for JSON that keeps the lists and objects around the current value on a stack instead of the call stack,
+/// so it accepts input of any depth. Strings and numbers are passed on to serde_json, and the errors are the same as the
+/// ones of serde_json.
+pub struct Parser<R: BufRead> {
+    reader: R,
+    /// The line of the next byte, starting at 1.
+    line: usize,
+    /// The number of bytes in front of the next byte on its line.
+    column: usize,
+    /// Whether each of the lists and objects around the current value is an object.
+    objects: Vec<bool>,
+    state: State,
+    /// The bytes of the current string or number.
+    token: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// A value is expected.
+    Value,
+    /// The first element of a list or the end of an empty list is expected.
+    FirstElement,
+    /// The first key of an object or the end of an empty object is expected.
+    FirstKey,
+    /// A key is expected after a comma.
+    Key,
+    /// A value is complete, so a comma, the end of a list or object or the end of the input is expected.
+    AfterValue,
+    Done,
+}
+
+impl<R: BufRead> Parser<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 1,
+            column: 0,
+            objects: Vec::new(),
+            state: State::Value,
+            token: Vec::new(),
+        }
+    }
+
+    /// Returns the next event, or `None` after the root value if only whitespace follows it.
+    pub fn next(&mut self) -> Result<Option<Event>, Json2NixError> {
+        loop {
+            self.skip_whitespace()?;
+
+            match self.state {
+                State::Value => return self.value().map(Some),
+                State::FirstElement => match self.peek()? {
+                    Some(b']') => return Ok(Some(self.end())),
+                    Some(_) => return self.value().map(Some),
+                    None => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing a list")),
+                },
+                State::FirstKey => match self.peek()? {
+                    Some(b'}') => return Ok(Some(self.end())),
+                    _ => return self.key().map(Some),
+                },
+                State::Key => return self.key().map(Some),
+                State::AfterValue => {
+                    let is_object = match self.objects.last() {
+                        Some(is_object) => *is_object,
+                        None => {
+                            return match self.peek()? {
+                                Some(_) => Err(self.error(ParseErrorCategory::Syntax, "trailing characters")),
+                                None => {
+                                    self.state = State::Done;
+                                    Ok(None)
+                                }
+                            };
+                        }
+                    };
+
+                    match (is_object, self.peek()?) {
+                        (false, Some(b']')) | (true, Some(b'}')) => return Ok(Some(self.end())),
+                        (_, Some(b',')) => {
+                            self.consume();
+                            self.skip_whitespace()?;
+                            match (is_object, self.peek()?) {
+                                (false, Some(b']')) | (true, Some(b'}')) => return Err(self.error(ParseErrorCategory::Syntax, "trailing comma")),
+                                (true, None) => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing a value")),
+                                _ => {}
+                            }
+                            self.state = if is_object { State::Key } else { State::Value };
+                        }
+                        (false, Some(_)) => return Err(self.error(ParseErrorCategory::Syntax, "expected `,` or `]`")),
+                        (true, Some(_)) => return Err(self.error(ParseErrorCategory::Syntax, "expected `,` or `}`")),
+                        (false, None) => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing a list")),
+                        (true, None) => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing an object")),
+                    }
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Event, Json2NixError> {
+        let event = match self.peek()? {
+            Some(b'[') => {
+                self.consume();
+                self.objects.push(false);
+                self.state = State::FirstElement;
+                return Ok(Event::StartList);
+            }
+            Some(b'{') => {
+                self.consume();
+                self.objects.push(true);
+                self.state = State::FirstKey;
+                return Ok(Event::StartObject);
+            }
+            Some(b'"') => Event::Scalar(Value::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => Event::Scalar(Value::Number(self.number()?)),
+            Some(b't') => self.literal("true", Value::Bool(true))?,
+            Some(b'f') => self.literal("false", Value::Bool(false))?,
+            Some(b'n') => self.literal("null", Value::Null)?,
+            Some(_) => return Err(self.error(ParseErrorCategory::Syntax, "expected value")),
+            None => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing a value")),
+        };
+
+        self.state = State::AfterValue;
+        Ok(event)
+    }
+
+    // Consumes the closing bracket of the innermost list or object.
+    fn end(&mut self) -> Event {
+        self.consume();
+        self.objects.pop();
+        self.state = State::AfterValue;
+        Event::End
+    }
+
+    // Parses a key and the colon behind it.
+    fn key(&mut self) -> Result<Event, Json2NixError> {
+        let key = match self.peek()? {
+            Some(b'"') => self.string()?,
+            Some(_) => return Err(self.error(ParseErrorCategory::Syntax, "key must be a string")),
+            None => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing an object")),
+        };
+
+        self.skip_whitespace()?;
+        match self.peek()? {
+            Some(b':') => {
+                self.consume();
+                self.state = State::Value;
+                Ok(Event::Key(key))
+            }
+            Some(_) => Err(self.error(ParseErrorCategory::Syntax, "expected `:`")),
+            None => Err(self.error(ParseErrorCategory::Eof, "EOF while parsing an object")),
+        }
+    }
+
+    // Strings with escape sequences are unescaped by serde_json, so only their end has to be found, which is the first
+    // quote that is not escaped. Control characters are invalid and end the string as well, so serde_json reports them.
+    fn string(&mut self) -> Result<String, Json2NixError> {
+        let (line, column) = (self.line, self.column);
+        let mut escaped = false;
+        self.token.clear();
+        self.take();
+
+        loop {
+            match self.peek()? {
+                Some(b'"') => {
+                    self.take();
+                    // most strings have no escape sequences, so they only have to be valid UTF-8
+                    if !escaped && let Ok(string) = std::str::from_utf8(&self.token[1..self.token.len() - 1]) {
+                        return Ok(string.to_string());
+                    }
+                    break;
+                }
+                Some(b'\\') => {
+                    escaped = true;
+                    self.take();
+                    if self.peek()?.is_some() {
+                        self.take();
+                    }
+                }
+                Some(byte) if byte < 0x20 => {
+                    self.take();
+                    break;
+                }
+                Some(_) => self.take_plain_text()?,
+                None => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing a string")),
+            }
+        }
+
+        serde_json::from_slice(&self.token).map_err(|err| token_error(line, column, err))
+    }
+
+    fn number(&mut self) -> Result<Number, Json2NixError> {
+        let (line, column) = (self.line, self.column);
+        self.token.clear();
+
+        if self.peek()? == Some(b'-') {
+            self.take();
+        }
+        self.take_digits()?;
+        if self.peek()? == Some(b'.') {
+            self.take();
+            self.take_digits()?;
+        }
+        if let Some(b'e' | b'E') = self.peek()? {
+            self.take();
+            if let Some(b'+' | b'-') = self.peek()? {
+                self.take();
+            }
+            self.take_digits()?;
+        }
+
+        match serde_json::from_slice(&self.token) {
+            Ok(number) => Ok(number),
+            // the number is incomplete, but serde_json did not see the character behind it
+            Err(err) if err.is_eof() && self.peek()?.is_some() => Err(self.error(ParseErrorCategory::Syntax, "invalid number")),
+            Err(err) => Err(token_error(line, column, err)),
+        }
+    }
+
+    fn take_digits(&mut self) -> Result<(), Json2NixError> {
+        while let Some(b'0'..=b'9') = self.peek()? {
+            self.take();
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &str, value: Value) -> Result<Event, Json2NixError> {
+        for expected in literal.bytes() {
+            match self.peek()? {
+                Some(byte) if byte == expected => self.consume(),
+                Some(_) => return Err(self.error(ParseErrorCategory::Syntax, "expected ident")),
+                None => return Err(self.error(ParseErrorCategory::Eof, "EOF while parsing a value")),
+            }
+        }
+        Ok(Event::Scalar(value))
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, Json2NixError> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    // Consumes the byte that was just peeked, which is still buffered.
+    fn consume(&mut self) {
+        let byte = self.buffered();
+        self.reader.consume(1);
+        if byte == Some(b'\n') {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    // Like `consume`, but also appends the byte to the token.
+    fn take(&mut self) {
+        let byte = self.buffered();
+        self.token.extend(byte);
+        self.consume();
+    }
+
+    // Appends all buffered bytes up to the next quote, backslash or control character to the token at once, as taking them
+    // one by one is much slower. The bytes contain no line breaks, which are control characters.
+    fn take_plain_text(&mut self) -> Result<(), Json2NixError> {
+        let buffer = self.reader.fill_buf()?;
+        let length = buffer
+            .iter()
+            .position(|byte| *byte == b'"' || *byte == b'\\' || *byte < 0x20)
+            .unwrap_or(buffer.len());
+        self.token.extend_from_slice(&buffer[..length]);
+        self.reader.consume(length);
+        self.column += length;
+        Ok(())
+    }
+
+    fn buffered(&mut self) -> Option<u8> {
+        self.reader.fill_buf().ok().and_then(|buffer| buffer.first().copied())
+    }
+
+    // Skips all buffered whitespace at once, like `take_plain_text`.
+    fn skip_whitespace(&mut self) -> Result<(), Json2NixError> {
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            let length = buffer
+                .iter()
+                .position(|byte| !matches!(byte, b' ' | b'\n' | b'\t' | b'\r'))
+                .unwrap_or(buffer.len());
+            match buffer[..length].iter().rposition(|byte| *byte == b'\n') {
+                Some(line_break) => {
+                    self.line += buffer[..length].iter().filter(|byte| **byte == b'\n').count();
+                    self.column = length - line_break - 1;
+                }
+                None => self.column += length,
+            }
+            let done = length < buffer.len() || length == 0;
+            self.reader.consume(length);
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    // Like serde_json, the column includes the next byte, if there is one.
+    fn error(&mut self, category: ParseErrorCategory, message: &str) -> Json2NixError {
+        Json2NixError::Parse {
+            line: self.line,
+            column: self.column + usize::from(self.buffered().is_some()),
+            category,
+            message: message.to_string(),
+        }
+    }
+}
+
+// Moves an error of serde_json in the token that starts at `line` and `column` to its position in the input.
+fn token_error(line: usize, column: usize, err: serde_json::Error) -> Json2NixError {
+    match Json2NixError::from(err) {
+        Json2NixError::Parse {
+            line: token_line,
+            column: token_column,
+            category,
+            message,
+        } => Json2NixError::Parse {
+            line: line + token_line - 1,
+            column: if token_line == 1 { column + token_column } else { token_column },
+            category,
+            message,
+        },
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn nested(depth: usize, inner: &str) -> String {
+        format!("{}{}{}", r#"{"a":["#.repeat(depth), inner, "]}".repeat(depth))
+    }
+
+    #[test]
+    fn deep() {
+        let value = parse_json(&nested(100_000, r#"1, "b\"ä", -2.5e3, true, false, null, [], {}"#)).unwrap();
+
+        let mut inner = &value;
+        for _ in 0..100_000 {
+            inner = &inner["a"][0];
+        }
+        assert_eq!(&json!(1), inner);
+        drop_value(value);
+    }
+
+    #[test]
+    fn events() {
+        let mut parser = Parser::new(r#" { "a" : [ 1 , { } ] , "" : [ ] } "#.as_bytes());
+        let mut events = Vec::new();
+        while let Some(event) = parser.next().unwrap() {
+            events.push(event);
+        }
+
+        let expected = vec![
+            Event::StartObject,
+            Event::Key("a".to_string()),
+            Event::StartList,
+            Event::Scalar(json!(1)),
+            Event::StartObject,
+            Event::End,
+            Event::End,
+            Event::Key(String::new()),
+            Event::StartList,
+            Event::End,
+            Event::End,
+        ];
+        assert_eq!(expected, events);
+    }
+
+    #[test]
+    fn same_as_serde_json() {
+        let input = r#" { "a" : [ 1 , -0.5 , 1e3 , 1E-3, 18446744073709551615 , "\n😀\"\\" , "\ud83d\ude00" ] , "b" : { } , "" : [ ] } "#;
+        assert_eq!(serde_json::from_str::<Value>(input).unwrap(), parse_json(input).unwrap());
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        assert_eq!(
+            json!({ "a": { "b": 1, "c": [2] }, "d": 3 }),
+            parse_json(r#"{ "a": { "b": 1 }, "d": 3, "a": { "c": [2] } }"#).unwrap()
+        );
+
+        for (input, message) in [
+            (r#"{ "a": 1, "a": 1 }"#, "line 1, column 17: duplicate key `a`"),
+            (r#"{ "a": {}, "a": [] }"#, "line 1, column 19: duplicate key `a`"),
+            (
+                r#"{ "a": { "b": 1 }, "a": { "b": {} } }"#,
+                "line 1, column 36: duplicate key `b` in the objects of the duplicate key `a`",
+            ),
+        ] {
+            let err = parse_json(input).unwrap_err().to_string();
+            assert_eq!(format!("Could not parse the input, invalid value at {}", message), err);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for input in [
+            "",
+            "[1, 2",
+            "[1 2]",
+            "[1,]",
+            "{\"a\": 1,}",
+            "{\"a\" 1}",
+            "{1: 2}",
+            "{\"a\": 1 \"b\": 2}",
+            "{\"a\":",
+            "[tru]",
+            "[nul",
+            "[\"a\\x\"]",
+            "[\"\u{e4}\\x\"]",
+            "[\n  \"a\nb\"]",
+            "[\"\\ud83d\"]",
+            "[\"a",
+            "[\"a\\",
+            "[01]",
+            "[-]",
+            "[-a]",
+            "[1.]",
+            "[1.5.3]",
+            "[1e5e5]",
+            "[1e+]",
+            "[.5]",
+            "[1] 2",
+            "[",
+            "[1,",
+            "{",
+            "{\"a\": 1,",
+            "[\n  \n  x]",
+        ] {
+            let expected = Json2NixError::from(serde_json::from_str::<Value>(input).unwrap_err());
+            assert_eq!(expected.to_string(), parse_json(input).unwrap_err().to_string(), "input: {:?}", input);
+        }
+
+        // the partially parsed values are dropped without recursion, too
+        assert!(parse_json(&nested(100_000, "x")).is_err());
+    }
+}
@@ -0,0 +1,565 @@
+use serde_json::{Map, Number, Value};
+
+use crate::{
+    Json2NixError, ParseErrorCategory,
+    convert::{Comments, push_pointer},
+};
+
+/// Parses JSON5, which also covers JSONC: comments, trailing commas, unquoted keys, single-quoted strings, hexadecimal
+/// numbers and more. Comments in front of a key, a list element or the root value are attached to it, all other
+/// comments are dropped.
+///
+/// Like `serde_json`, the parser is recursive and rejects lists and objects that are nested more than 128 levels deep.
+pub fn parse_json5(input: &str) -> Result<(Value, Comments), Json2NixError> {
+    let mut parser = Parser {
+        input,
+        position: 0,
+        depth: 0,
+        pointer: String::new(),
+        comments: Comments::new(),
+        pending_comments: Vec::new(),
+    };
+
+    parser.skip_trivia()?;
+    parser.attach_comments();
+    let value = parser.value()?;
+    parser.skip_trivia()?;
+
+    match parser.peek() {
+        None => Ok((value, parser.comments)),
+        Some(c) => Err(parser.error(ParseErrorCategory::Syntax, format!("expected end of input, found `{}`", c))),
+    }
+}
+
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a str,
+    /// The byte offset of the next character.
+    position: usize,
+    /// The number of lists and objects that enclose the current value.
+    depth: usize,
+    /// The JSON Pointer of the value that is currently parsed.
+    pointer: String,
+    comments: Comments,
+    /// Comments that were skipped, but are not yet attached to a value.
+    pending_comments: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.position += bytes;
+    }
+
+    fn error(&self, category: ParseErrorCategory, message: impl Into<String>) -> Json2NixError {
+        Json2NixError::parse_at(self.input, self.position, category, message)
+    }
+
+    fn unexpected(&self, expected: &str) -> Json2NixError {
+        match self.peek() {
+            None => self.error(ParseErrorCategory::Eof, format!("expected {}", expected)),
+            Some(c) => self.error(ParseErrorCategory::Syntax, format!("expected {}, found `{}`", expected, c)),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Json2NixError> {
+        if self.peek() != Some(expected) {
+            return Err(self.unexpected(&format!("`{}`", expected)));
+        }
+        self.advance(expected.len_utf8());
+        Ok(())
+    }
+
+    // Skips whitespace and collects comments.
+    fn skip_trivia(&mut self) -> Result<(), Json2NixError> {
+        loop {
+            let rest = self.rest();
+            if let Some(comment) = rest.strip_prefix("//") {
+                let length = comment.find(['\n', '\r', '\u{2028}', '\u{2029}']).unwrap_or(comment.len());
+                self.pending_comments.push(comment[..length].trim().to_string());
+                self.advance(2 + length);
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                let Some(length) = comment.find("*/") else {
+                    return Err(self.error(ParseErrorCategory::Eof, "unterminated comment"));
+                };
+                if let Some(comment) = block_comment(&comment[..length]) {
+                    self.pending_comments.push(comment);
+                }
+                self.advance(4 + length);
+            } else if let Some(c) = self.peek()
+                && (c.is_whitespace() || c == '\u{FEFF}')
+            {
+                self.advance(c.len_utf8());
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn attach_comments(&mut self) {
+        if !self.pending_comments.is_empty() {
+            let comments = std::mem::take(&mut self.pending_comments);
+            self.comments.entry(self.pointer.clone()).or_default().extend(comments);
+        }
+    }
+
+    fn push_pointer(&mut self, token: &str) -> usize {
+        push_pointer(&mut self.pointer, token)
+    }
+
+    fn value(&mut self) -> Result<Value, Json2NixError> {
+        match self.peek() {
+            None => Err(self.error(ParseErrorCategory::Eof, "expected a value")),
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some(quote @ ('"' | '\'')) => self.string(quote).map(Value::String),
+            Some(c) if c.is_ascii_digit() || matches!(c, '+' | '-' | '.') => self.number(),
+            Some(c) if is_identifier_start(c) => {
+                let start = self.position;
+                match self.identifier() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "null" => Ok(Value::Null),
+                    "Infinity" | "NaN" => {
+                        self.position = start;
+                        self.number()
+                    }
+                    identifier => {
+                        self.position = start;
+                        Err(self.error(ParseErrorCategory::Syntax, format!("expected a value, found `{}`", identifier)))
+                    }
+                }
+            }
+            Some(_) => Err(self.unexpected("a value")),
+        }
+    }
+
+    // Parses a list or object with `parse`, unless that would exceed the maximum depth.
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Value, Json2NixError>) -> Result<Value, Json2NixError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(ParseErrorCategory::Syntax, "recursion limit exceeded"));
+        }
+        self.depth += 1;
+        let value = parse(self)?;
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn object(&mut self) -> Result<Value, Json2NixError> {
+        self.advance(1);
+        let mut object = Map::new();
+
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some('}') {
+                self.pending_comments.clear();
+                self.advance(1);
+                return Ok(Value::Object(object));
+            }
+
+            let key = match self.peek() {
+                Some(quote @ ('"' | '\'')) => self.string(quote)?,
+                Some(c) if is_identifier_start(c) => self.identifier().to_string(),
+                _ => return Err(self.unexpected("a key")),
+            };
+            let length = self.push_pointer(&key);
+            self.attach_comments();
+
+            self.skip_trivia()?;
+            self.expect(':')?;
+            self.skip_trivia()?;
+            self.attach_comments();
+            let value = self.value()?;
+            self.pointer.truncate(length);
+            object.insert(key, value);
+
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(',') => self.advance(1),
+                Some('}') => {}
+                _ => return Err(self.unexpected("`,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, Json2NixError> {
+        self.advance(1);
+        let mut array = Vec::new();
+
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(']') {
+                self.pending_comments.clear();
+                self.advance(1);
+                return Ok(Value::Array(array));
+            }
+
+            let length = self.push_pointer(&array.len().to_string());
+            self.attach_comments();
+            let value = self.value()?;
+            self.pointer.truncate(length);
+            array.push(value);
+
+            self.skip_trivia()?;
+            match self.peek() {
+                Some(',') => self.advance(1),
+                Some(']') => {}
+                _ => return Err(self.unexpected("`,` or `]`")),
+            }
+        }
+    }
+
+    // Identifiers are a simplified version of ECMAScript identifier names without escape sequences.
+    fn identifier(&mut self) -> &'a str {
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '$' | '_' | '\u{200C}' | '\u{200D}')))
+            .unwrap_or(rest.len());
+        self.advance(length);
+        &rest[..length]
+    }
+
+    fn string(&mut self, quote: char) -> Result<String, Json2NixError> {
+        let start = self.position;
+        self.advance(1);
+        let mut string = String::new();
+
+        loop {
+            let Some(c) = self.peek() else {
+                self.position = start;
+                return Err(self.error(ParseErrorCategory::Eof, "unterminated string"));
+            };
+
+            match c {
+                c if c == quote => {
+                    self.advance(1);
+                    return Ok(string);
+                }
+                '\\' => {
+                    self.advance(1);
+                    self.escape_sequence(&mut string)?;
+                }
+                '\n' | '\r' => return Err(self.error(ParseErrorCategory::Syntax, "line breaks in strings must be escaped")),
+                c => {
+                    self.advance(c.len_utf8());
+                    string.push(c);
+                }
+            }
+        }
+    }
+
+    fn escape_sequence(&mut self, string: &mut String) -> Result<(), Json2NixError> {
+        let Some(c) = self.peek() else {
+            return Err(self.error(ParseErrorCategory::Eof, "unterminated string"));
+        };
+        self.advance(c.len_utf8());
+
+        match c {
+            'b' => string.push('\u{8}'),
+            'f' => string.push('\u{c}'),
+            'n' => string.push('\n'),
+            'r' => string.push('\r'),
+            't' => string.push('\t'),
+            'v' => string.push('\u{b}'),
+            '0' if !self.peek().is_some_and(|c| c.is_ascii_digit()) => string.push('\0'),
+            'x' => {
+                let code = self.hex_digits(2)?;
+                string.push(char::from_u32(code).expect("two hex digits are a valid char"));
+            }
+            'u' => {
+                let code = self.hex_digits(4)?;
+                let code = match code {
+                    // a high surrogate must be followed by a low surrogate
+                    0xD800..=0xDBFF if self.rest().starts_with("\\u") => {
+                        self.advance(2);
+                        let low = self.hex_digits(4)?;
+                        if !(0xDC00..=0xDFFF).contains(&low) {
+                            return Err(self.error(ParseErrorCategory::Syntax, "invalid unicode escape"));
+                        }
+                        0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+                    }
+                    code => code,
+                };
+                let c = char::from_u32(code).ok_or_else(|| self.error(ParseErrorCategory::Syntax, "invalid unicode escape"))?;
+                string.push(c);
+            }
+            // line continuation
+            '\r' if self.peek() == Some('\n') => self.advance(1),
+            '\n' | '\r' | '\u{2028}' | '\u{2029}' => {}
+            '0'..='9' => return Err(self.error(ParseErrorCategory::Syntax, "invalid escape sequence")),
+            c => string.push(c),
+        }
+
+        Ok(())
+    }
+
+    fn hex_digits(&mut self, count: usize) -> Result<u32, Json2NixError> {
+        let digits = self.rest().get(..count).filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()));
+        match digits {
+            Some(digits) => {
+                self.advance(count);
+                Ok(u32::from_str_radix(digits, 16).expect("'digits' are hex digits"))
+            }
+            None => Err(self.error(ParseErrorCategory::Syntax, "invalid escape sequence")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, Json2NixError> {
+        let start = self.position;
+        let negative = self.peek() == Some('-');
+        if matches!(self.peek(), Some('+' | '-')) {
+            self.advance(1);
+        }
+
+        let rest = self.rest();
+        if rest.starts_with("Infinity") || rest.starts_with("NaN") {
+            self.position = start;
+            return Err(self.error(ParseErrorCategory::Data, "Infinity and NaN cannot be represented in Nix"));
+        }
+
+        if let Some(digits) = rest.strip_prefix("0x").or_else(|| rest.strip_prefix("0X")) {
+            let length = digits.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(digits.len());
+            let magnitude =
+                u64::from_str_radix(&digits[..length], 16).map_err(|_| self.error(ParseErrorCategory::Syntax, "invalid hexadecimal number"))?;
+            self.advance(2 + length);
+            return match negative {
+                false => Ok(Value::from(magnitude)),
+                true => 0i64
+                    .checked_sub_unsigned(magnitude)
+                    .map(Value::from)
+                    .ok_or_else(|| Json2NixError::parse_at(self.input, start, ParseErrorCategory::Data, "number out of range")),
+            };
+        }
+
+        let integer_digits = count_digits(rest);
+        let mut length = integer_digits;
+        let mut is_float = false;
+        if rest[length..].starts_with('.') {
+            is_float = true;
+            length += 1 + count_digits(&rest[length + 1..]);
+        }
+        if integer_digits == 0 && length <= 1 {
+            return Err(self.unexpected("a number"));
+        }
+        if let Some(exponent) = rest[length..].strip_prefix(['e', 'E']) {
+            let sign = usize::from(exponent.starts_with(['+', '-']));
+            let exponent_digits = count_digits(&exponent[sign..]);
+            if exponent_digits == 0 {
+                self.advance(length + 1 + sign);
+                return Err(self.unexpected("the digits of the exponent"));
+            }
+            is_float = true;
+            length += 1 + sign + exponent_digits;
+        }
+        self.advance(length);
+
+        // like in serde_json, integers that are too large keep all of their digits
+        let literal = &self.input[start..self.position];
+        let literal = literal.strip_prefix('+').unwrap_or(literal);
+        if !is_float {
+            if let Ok(integer) = literal.parse::<i64>() {
+                return Ok(Value::from(integer));
+            }
+            if let Ok(number) = literal.parse::<Number>() {
+                return Ok(Value::Number(number));
+            }
+        }
+        let float = literal.parse::<f64>().expect("'literal' is a valid float");
+        Number::from_f64(float)
+            .map(Value::Number)
+            .ok_or_else(|| Json2NixError::parse_at(self.input, start, ParseErrorCategory::Data, "number out of range"))
+    }
+}
+
+fn count_digits(input: &str) -> usize {
+    input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len())
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '$' || c == '_'
+}
+
+// Removes the leading `*` of the lines of doc-style comments and empty lines at the start and end.
+fn block_comment(content: &str) -> Option<String> {
+    let lines = content
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            line.strip_prefix('*').map_or(line, |line| line.strip_prefix(' ').unwrap_or(line))
+        })
+        .collect::<Vec<_>>();
+
+    let first = lines.iter().position(|line| !line.is_empty())?;
+    let last = lines.iter().rposition(|line| !line.is_empty())?;
+    Some(lines[first..=last].join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(input: &str) -> Value {
+        parse_json5(input).unwrap().0
+    }
+
+    #[test]
+    fn jsonc() {
+        let input = r#"{
+            // the font
+            "editor.fontSize": 14,
+            "files.exclude": { "**/.git": true, },
+            /* the theme */
+            "workbench.colorTheme": "Default Dark+",
+        }"#;
+        assert_eq!(
+            json!({ "editor.fontSize": 14, "files.exclude": { "**/.git": true }, "workbench.colorTheme": "Default Dark+" }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn json5() {
+        let input = r#"{
+            unquoted: 'and you can quote me on that',
+            singleQuotes: 'I can use "double quotes" here',
+            lineBreaks: "Look, Mom! \
+No \\n's!",
+            hexadecimal: 0xdecaf,
+            leadingDecimalPoint: .8675309, andTrailing: 8675309.,
+            positiveSign: +1,
+            negativeHex: -0x10,
+            trailingComma: 'in objects', andIn: ['arrays',],
+            "backwardsCompatible": "with JSON",
+            $special_keys: ['\x41ä🦀\0\v'],
+        }"#;
+        let expected = json!({
+            "unquoted": "and you can quote me on that",
+            "singleQuotes": "I can use \"double quotes\" here",
+            "lineBreaks": "Look, Mom! No \\n's!",
+            "hexadecimal": 912559,
+            "leadingDecimalPoint": 0.8675309,
+            "andTrailing": 8675309.0,
+            "positiveSign": 1,
+            "negativeHex": -16,
+            "trailingComma": "in objects",
+            "andIn": ["arrays"],
+            "backwardsCompatible": "with JSON",
+            "$special_keys": ["Aä🦀\0\u{b}"],
+        });
+        assert_eq!(expected, parse(input));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            json!([
+                1,
+                -1,
+                1.5,
+                1e3,
+                18446744073709551615u64,
+                "100000000000000000000".parse::<Number>().unwrap()
+            ]),
+            parse("[1, -1, 1.5, 1e3, 18446744073709551615, 100000000000000000000]")
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let input = r#"
+            // root
+            {
+                /**
+                 * first
+                 * second
+                 */
+                a: [
+                    1,
+                    // element
+                    2, // trailing, attached to the next element
+                    3,
+                    // dropped
+                ],
+                b: // after the key
+                    null,
+                "c/d": {
+                    /* nested */ e: 1,
+                },
+                // dropped
+            }
+            // dropped
+        "#;
+        let (_, comments) = parse_json5(input).unwrap();
+        let expected = Comments::from([
+            (String::new(), vec!["root".to_string()]),
+            ("/a".to_string(), vec!["first\nsecond".to_string()]),
+            ("/a/1".to_string(), vec!["element".to_string()]),
+            ("/a/2".to_string(), vec!["trailing, attached to the next element".to_string()]),
+            ("/b".to_string(), vec!["after the key".to_string()]),
+            ("/c~1d/e".to_string(), vec!["nested".to_string()]),
+        ]);
+        assert_eq!(expected, comments);
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let depth = 200_000;
+        let input = format!("{}{}", "{ a: [".repeat(depth), "] }".repeat(depth));
+        assert!(matches!(parse_json5(&input), Err(Json2NixError::Parse { message, .. }) if message == "recursion limit exceeded"));
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("[1, 2", (1, 6, ParseErrorCategory::Eof, "expected `,` or `]`")),
+            ("{ a 1 }", (1, 5, ParseErrorCategory::Syntax, "expected `:`, found `1`")),
+            ("[NaN]", (1, 2, ParseErrorCategory::Data, "Infinity and NaN cannot be represented in Nix")),
+            (
+                "[-Infinity]",
+                (1, 2, ParseErrorCategory::Data, "Infinity and NaN cannot be represented in Nix"),
+            ),
+            (
+                "{\n  a: undefined }",
+                (2, 6, ParseErrorCategory::Syntax, "expected a value, found `undefined`"),
+            ),
+            ("'abc", (1, 1, ParseErrorCategory::Eof, "unterminated string")),
+            ("\"a\nb\"", (1, 3, ParseErrorCategory::Syntax, "line breaks in strings must be escaped")),
+            ("[1 2]", (1, 4, ParseErrorCategory::Syntax, "expected `,` or `]`, found `2`")),
+            (
+                "[1e]",
+                (1, 4, ParseErrorCategory::Syntax, "expected the digits of the exponent, found `]`"),
+            ),
+            ("1 2", (1, 3, ParseErrorCategory::Syntax, "expected end of input, found `2`")),
+            (
+                &format!("{}{}", "[".repeat(129), "]".repeat(129)),
+                (1, 129, ParseErrorCategory::Syntax, "recursion limit exceeded"),
+            ),
+        ];
+
+        for (input, (line, column, category, message)) in cases {
+            match parse_json5(input) {
+                Err(Json2NixError::Parse {
+                    line: actual_line,
+                    column: actual_column,
+                    category: actual_category,
+                    message: actual_message,
+                }) => assert_eq!(
+                    (line, column, category, message.to_string()),
+                    (actual_line, actual_column, actual_category, actual_message),
+                    "input: {}",
+                    input
+                ),
+                result => panic!("unexpected result for {}: {:?}", input, result),
+            }
+        }
+    }
+}
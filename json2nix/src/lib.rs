@@ -1,15 +1,86 @@
+mod ast;
+mod convert;
+mod doc;
+mod error;
 mod escape;
-mod indent;
+mod extract;
+mod ini;
+mod json;
+mod json5;
+mod number;
+mod order;
+mod parse;
+#[cfg(feature = "plist")]
+mod plist;
+mod printer;
+mod raw;
+mod rewrite;
+mod rules;
+mod serialize;
+mod stream;
+mod string;
+#[cfg(feature = "toml")]
+mod toml;
+#[cfg(feature = "yaml")]
+mod yaml;
 
-use escape::escape_attribute_set_key;
-use indent::indent;
+use std::fmt::{self, Display, Formatter};
+
+use ast::drop_expr;
+use convert::{Comments, to_nix_expr};
+use json::drop_value;
+use parse::parse_nix;
+use printer::write_binding;
 use serde_json::Value;
 
+pub use ast::{Binding, NixExpr, StringPart};
+pub use error::{Json2NixError, ParseErrorCategory};
+pub use parse::parse_attr_path;
+pub use printer::{print_nix_expr, write_nix_expr};
+pub use rewrite::StringRewrite;
+pub use rules::{Rule, RuleAction, parse_rules};
+pub use serialize::{to_nix_string, to_nix_writer};
+pub use stream::json2nix_stream;
+
 #[derive(Debug)]
 pub struct Json2NixConfig {
     pub initial_indentation: usize,
     pub indentation_increment: usize,
     pub compact_set_keys: bool,
+    /// Merges nested sets into fully qualified attribute paths (`a.b.x = 1; a.b.y = 2;`), regardless of their number of
+    /// attributes.
+    pub flatten_set_keys: FlattenSetKeys,
+    pub string_style: StringStyle,
+    pub unrepresentable_numbers: UnrepresentableNumbers,
+    /// Lists and attribute sets are rendered on one line if they fit into this width, otherwise they are always expanded.
+    pub max_line_width: Option<usize>,
+    pub style: OutputStyle,
+    pub input_format: InputFormat,
+    /// Nests the expression under this attribute path, e.g. `["programs", "git", "settings"]`. The path is compacted
+    /// like any other if `compact_set_keys` is set.
+    pub attr_path: Vec<String>,
+    /// Wraps the expression in a NixOS or home-manager module: `{ config, lib, pkgs, ... }: …`
+    pub module: bool,
+    /// Rules that wrap or transform values, e.g. in `lib.mkDefault`. All rules that match a value are applied in order,
+    /// so later rules wrap earlier ones. Sets that are matched are not merged into attribute paths.
+    pub rules: Vec<Rule>,
+    /// Converts objects like `{ "$nix": "pkgs.hello" }` to the Nix expression they contain, which is inserted as is.
+    pub raw_nix_objects: bool,
+    /// Converts strings with this prefix to the Nix expression after it, e.g. `"nix:pkgs.hello"` with the prefix `nix:`.
+    pub raw_nix_prefix: Option<String>,
+    /// Rewrites strings with machine-specific paths into Nix expressions, see [`StringRewrite`].
+    pub rewrites: Vec<StringRewrite>,
+    /// Moves lists and attribute sets that occur more than once and contain at least this number of values into a
+    /// `let … in` header, where they are named after the attribute of their first occurrence.
+    pub extract_repeated: Option<usize>,
+    /// The maximum number of lists and attribute sets that may be nested in each other, deeper input is rejected with
+    /// [`Json2NixError::TooDeep`]. Without a limit, JSON and plist input of any depth is converted. The JSON5, YAML and
+    /// TOML parsers are recursive and reject deeply nested input on their own.
+    pub max_depth: Option<usize>,
+    /// The order of the attributes of every set. The attributes named in `key_priority` come first regardless.
+    pub key_order: KeyOrder,
+    /// Attribute names that are put in front of all others in this order, e.g. `["enable"]`.
+    pub key_priority: Vec<String>,
 }
 
 impl Json2NixConfig {
@@ -18,111 +89,298 @@ impl Json2NixConfig {
             initial_indentation,
             indentation_increment,
             compact_set_keys,
+            flatten_set_keys: FlattenSetKeys::default(),
+            string_style: StringStyle::default(),
+            unrepresentable_numbers: UnrepresentableNumbers::default(),
+            max_line_width: None,
+            style: OutputStyle::default(),
+            input_format: InputFormat::default(),
+            attr_path: Vec::new(),
+            module: false,
+            rules: Vec::new(),
+            raw_nix_objects: false,
+            raw_nix_prefix: None,
+            rewrites: Vec::new(),
+            extract_repeated: None,
+            max_depth: None,
+            key_order: KeyOrder::default(),
+            key_priority: Vec::new(),
         }
     }
 }
 
-pub fn json2nix(input: &str, config: &Json2NixConfig) -> Result<String, String> {
-    let json: Value = match serde_json::from_str(input) {
-        Ok(value) => value,
-        Err(err) => {
-            return Err(format!("Could not parse the input as JSON: {}", err.to_string()));
-        }
-    };
+impl Json2NixConfig {
+    /// Whether [`json2nix_stream`] can be used with this configuration. Streaming only supports JSON input and options that
+    /// do not need to look ahead, so not `compact_set_keys`, `flatten_set_keys`, `max_line_width`, the nixfmt style,
+    /// `raw_nix_objects`, `extract_repeated`, `key_order` and `key_priority`.
+    pub fn supports_streaming(&self) -> bool {
+        stream::unsupported_option(self).is_none()
+    }
+}
 
-    Ok(indent(
-        &to_nix(&json, config.initial_indentation, config.indentation_increment, config.compact_set_keys),
-        config.initial_indentation,
-    ))
+impl Default for Json2NixConfig {
+    fn default() -> Self {
+        Self::new(0, 2, false)
+    }
 }
 
-fn to_nix(value: &Value, indentation: usize, indentation_increment: usize, compact_set_keys: bool) -> String {
-    match value {
-        Value::Null => "null".to_string(),
-        Value::Bool(bool) => bool.to_string(),
-        Value::Number(number) => number.to_string(),
-        Value::String(string) => format!(r#""{}""#, string),
-        Value::Array(array) => match array.len() {
-            0 => "[ ]".to_string(),
-            _ => {
-                let mut formatted_elements = Vec::with_capacity(array.len());
-                for element in array {
-                    if compact_set_keys && element.as_object().is_some_and(|o| o.len() == 1) {
-                        formatted_elements.push(format!(
-                            "{{\n{}\n{}",
-                            indent(
-                                &to_nix(element, indentation + indentation_increment, indentation_increment, compact_set_keys),
-                                indentation + indentation_increment * 2
-                            ),
-                            indent("}", indentation + indentation_increment),
-                        ));
-                    } else {
-                        formatted_elements.push(to_nix(
-                            element,
-                            indentation + indentation_increment,
-                            indentation_increment,
-                            compact_set_keys,
-                        ));
-                    }
-                }
-                format!(
-                    "[\n{}\n{}",
-                    formatted_elements
-                        .iter()
-                        .map(|fe| indent(fe, indentation + indentation_increment))
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                    indent("]", indentation)
-                )
-            }
-        },
-        Value::Object(object) => match object.len() {
-            0 => "{ }".to_string(),
-            1 if compact_set_keys => {
-                let (key, child) = object.iter().next().expect("'object' contains exactly one element");
-
-                if child.as_object().is_some_and(|o| o.len() == 1) {
-                    format!(
-                        "{}.{}",
-                        escape_attribute_set_key(key),
-                        to_nix(child, indentation, indentation_increment, compact_set_keys)
-                    )
-                } else {
-                    format!(
-                        "{} = {};",
-                        escape_attribute_set_key(key),
-                        to_nix(child, indentation, indentation_increment, compact_set_keys)
-                    )
+/// The format of the input. Formats other than JSON are enabled by cargo features.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// JSON. Duplicate keys are handled like duplicate attributes in Nix: two objects are merged, anything else is
+    /// rejected.
+    #[default]
+    Json,
+    /// JSON5, which includes JSONC. Comments in front of keys and list elements are carried over as Nix comments.
+    Json5,
+    /// INI like git-config. `[section "subsection"]` becomes a nested attribute set, repeated keys become lists and
+    /// unquoted booleans and canonically written numbers are typed.
+    Ini,
+    /// An XML or binary property list as exported by `defaults export`. Binary property lists can only be read with
+    /// [`json2nix_from_slice`].
+    #[cfg(feature = "plist")]
+    Plist,
+    /// YAML, where a stream of multiple documents is converted to a list.
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "toml")]
+    Toml { datetimes: TomlDatetimes },
+}
+
+/// How to convert TOML datetimes, which have no equivalent in Nix.
+#[cfg(feature = "toml")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TomlDatetimes {
+    /// A string in the format of RFC 3339 like in the TOML document, e.g. `"1979-05-27T07:32:00Z"`.
+    #[default]
+    String,
+    /// An attribute set with the components that are present, e.g. `{ year = 1979; month = 5; day = 27; }`. Offsets are
+    /// given in minutes as `offsetMinutes`.
+    AttrSet,
+}
+
+impl InputFormat {
+    /// Returns the format for a file extension like `yml`, if it is known and enabled.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(InputFormat::Json),
+            "json5" | "jsonc" => Some(InputFormat::Json5),
+            "ini" | "gitconfig" | "gitmodules" => Some(InputFormat::Ini),
+            #[cfg(feature = "plist")]
+            "plist" => Some(InputFormat::Plist),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(InputFormat::Yaml),
+            #[cfg(feature = "toml")]
+            "toml" => Some(InputFormat::Toml {
+                datetimes: TomlDatetimes::default(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the format for a file name like `config.yml` or `.gitconfig`, if its extension is known and enabled.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let (_, extension) = file_name.rsplit_once('.')?;
+        Self::from_extension(extension)
+    }
+
+    fn parse(self, input: &str) -> Result<(Value, Comments), Json2NixError> {
+        let value = match self {
+            InputFormat::Json => json::parse_json(input)?,
+            InputFormat::Json5 => return json5::parse_json5(input),
+            InputFormat::Ini => ini::parse_ini(input)?,
+            #[cfg(feature = "plist")]
+            InputFormat::Plist => plist::parse_plist(input.as_bytes())?,
+            #[cfg(feature = "yaml")]
+            InputFormat::Yaml => yaml::parse_yaml(input)?,
+            #[cfg(feature = "toml")]
+            InputFormat::Toml { datetimes } => toml::parse_toml(input, datetimes)?,
+        };
+        Ok((value, Comments::new()))
+    }
+
+    fn parse_slice(self, input: &[u8]) -> Result<(Value, Comments), Json2NixError> {
+        match self {
+            #[cfg(feature = "plist")]
+            InputFormat::Plist => Ok((plist::parse_plist(input)?, Comments::new())),
+            _ => match std::str::from_utf8(input) {
+                Ok(input) => self.parse(input),
+                Err(err) => {
+                    let valid = std::str::from_utf8(&input[..err.valid_up_to()]).expect("the input is valid up to this offset");
+                    Err(Json2NixError::parse_at(
+                        valid,
+                        valid.len(),
+                        ParseErrorCategory::Data,
+                        "the input is not valid UTF-8",
+                    ))
                 }
+            },
+        }
+    }
+}
+
+/// The layout rules used when rendering expressions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStyle {
+    /// Lay out expressions according to `indentation_increment` and `max_line_width`.
+    #[default]
+    Default,
+    /// Produce the same output as `nixfmt` (RFC 166): an indentation of 2, a line width of 100, lists and attribute sets
+    /// with more than one element are always expanded, and the output ends with a newline. `indentation_increment` and
+    /// `max_line_width` are ignored.
+    Nixfmt,
+}
+
+/// Which nested sets are merged into the attribute paths of their parent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FlattenSetKeys {
+    /// Only merge sets with one attribute if [`Json2NixConfig::compact_set_keys`] is set.
+    #[default]
+    Never,
+    /// Merge all non-empty sets, so every binding has a fully qualified attribute path.
+    Always,
+    /// Merge non-empty sets until the attribute paths have this number of attributes. Sets below are rendered as usual.
+    MaxDepth(usize),
+}
+
+/// The order of the attributes of sets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// Keep the order of the input.
+    #[default]
+    Preserve,
+    /// Sort attribute names by their bytes, which is the order in which Nix itself lists attributes.
+    Nix,
+    /// Sort attribute names like [`KeyOrder::Nix`], but compare numbers in them by value, so `item2` comes before `item10`.
+    Natural,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StringStyle {
+    /// Render all strings as double-quoted strings (`"…"`).
+    #[default]
+    DoubleQuoted,
+    /// Render strings as indented strings (`'' … ''`) if they contain a newline or, if a threshold is given, more than
+    /// `double_quote_threshold` double quotes. All other strings are rendered as double-quoted strings.
+    Indented { double_quote_threshold: Option<usize> },
+}
+
+impl StringStyle {
+    fn use_indented_string(&self, value: &str) -> bool {
+        match self {
+            StringStyle::DoubleQuoted => false,
+            StringStyle::Indented { double_quote_threshold } => {
+                value.contains('\n') || double_quote_threshold.is_some_and(|threshold| value.matches('"').count() > threshold)
             }
-            _ => {
-                let mut formatted_elements = Vec::with_capacity(object.len());
-                for (key, value) in object {
-                    if compact_set_keys && value.as_object().is_some_and(|o| o.len() == 1) {
-                        formatted_elements.push(format!(
-                            "{}.{}",
-                            escape_attribute_set_key(key),
-                            to_nix(value, indentation + indentation_increment, indentation_increment, compact_set_keys)
-                        ));
-                    } else {
-                        formatted_elements.push(format!(
-                            "{} = {};",
-                            escape_attribute_set_key(key),
-                            to_nix(value, indentation + indentation_increment, indentation_increment, compact_set_keys)
-                        ));
-                    }
-                }
-                format!(
-                    "{{\n{}\n{}",
-                    formatted_elements
-                        .iter()
-                        .map(|fe| indent(fe, indentation + indentation_increment))
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                    indent("}", indentation)
+        }
+    }
+}
+
+/// How to handle integers that are out of the range of the signed 64-bit integers of Nix. Floats that are out of the
+/// range of 64-bit floats can only be rendered as strings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnrepresentableNumbers {
+    /// Fail the conversion.
+    #[default]
+    Error,
+    /// Render the number as a string containing its digits.
+    String,
+    /// Render the number as the nearest float and emit a [`Warning::LossyNumber`].
+    Float,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// An integer could not be represented exactly and was replaced by a float.
+    LossyNumber { number: String, replacement: String },
+    /// A string at the JSON Pointer `pointer` was rewritten by a [`StringRewrite`] to the Nix expression `replacement`.
+    RewrittenString {
+        pointer: String,
+        original: String,
+        replacement: String,
+    },
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::LossyNumber { number, replacement } => {
+                write!(
+                    f,
+                    "The integer {} is out of the range of Nix and was replaced by the float {}",
+                    number, replacement
                 )
             }
-        },
+            Warning::RewrittenString {
+                pointer,
+                original,
+                replacement,
+            } => write!(f, "The string {:?} at '{}' was rewritten to {}", original, pointer, replacement),
+        }
+    }
+}
+
+pub fn json2nix(input: &str, config: &Json2NixConfig) -> Result<String, Json2NixError> {
+    json2nix_with_warnings(input, config).map(|(nix, _)| nix)
+}
+
+pub fn json2nix_with_warnings(input: &str, config: &Json2NixConfig) -> Result<(String, Vec<Warning>), Json2NixError> {
+    let (json, comments) = config.input_format.parse(input)?;
+
+    let mut warnings = Vec::new();
+    let nix = value_to_nix(&json, &comments, config, &mut warnings);
+    drop_value(json);
+
+    Ok((nix?, warnings))
+}
+
+/// Like [`json2nix_with_warnings`], but reads raw bytes, which allows binary formats like binary property lists. Text
+/// formats have to be valid UTF-8.
+pub fn json2nix_from_slice(input: &[u8], config: &Json2NixConfig) -> Result<(String, Vec<Warning>), Json2NixError> {
+    let (json, comments) = config.input_format.parse_slice(input)?;
+
+    let mut warnings = Vec::new();
+    let nix = value_to_nix(&json, &comments, config, &mut warnings);
+    drop_value(json);
+
+    Ok((nix?, warnings))
+}
+
+/// Parses `input` according to [`Json2NixConfig::input_format`] and converts it to a [`NixExpr`], which can be
+/// post-processed and rendered with [`print_nix_expr`]. Dropping the expression may overflow the stack for very deeply
+/// nested input, so [`Json2NixConfig::max_depth`] should be set for such input.
+pub fn json2nix_expr(input: &str, config: &Json2NixConfig) -> Result<(NixExpr, Vec<Warning>), Json2NixError> {
+    let (json, comments) = config.input_format.parse(input)?;
+
+    let mut warnings = Vec::new();
+    let expr = to_nix_expr(&json, &comments, config, &mut warnings);
+    drop_value(json);
+
+    Ok((expr?, warnings))
+}
+
+/// Parses the pure-data subset of Nix (attribute sets, lists, strings, numbers, booleans and `null`) and converts it to
+/// pretty-printed JSON. Expressions that need evaluation, like variables or functions, are rejected with
+/// [`ParseErrorCategory::Unsupported`]. Values that are nested more than 128 levels deep are rejected, too.
+pub fn nix2json(input: &str) -> Result<String, Json2NixError> {
+    let value = parse_nix(input)?;
+    Ok(serde_json::to_string_pretty(&value).expect("a JSON value can always be serialized"))
+}
+
+fn value_to_nix(value: &Value, comments: &Comments, config: &Json2NixConfig, warnings: &mut Vec<Warning>) -> Result<String, Json2NixError> {
+    let expr = to_nix_expr(value, comments, config, warnings)?;
+    let mut nix = String::new();
+    write_nix(&expr, config, &mut nix).expect("writing to a string cannot fail");
+    drop_expr(expr);
+
+    Ok(nix)
+}
+
+fn write_nix(expr: &NixExpr, config: &Json2NixConfig, output: &mut impl fmt::Write) -> fmt::Result {
+    match expr {
+        // a single compacted attribute is rendered without braces, ready to be pasted into an existing attribute set
+        NixExpr::AttrSet(bindings) if config.compact_set_keys && bindings.len() == 1 => write_binding(&bindings[0], config, output),
+        expr => write_nix_expr(expr, config, output),
     }
 }
 
@@ -133,6 +391,10 @@ mod test {
     use super::*;
     use serde_json::{Map, Value, json, value::Number};
 
+    fn convert(value: &Value, config: &Json2NixConfig) -> String {
+        value_to_nix(value, &Comments::new(), config, &mut Vec::new()).unwrap()
+    }
+
     fn trim_indent(input: &str) -> String {
         let common_indent = input
             .lines()
@@ -152,88 +414,166 @@ mod test {
 
     #[test]
     fn null() {
-        assert_eq!("null", to_nix(&Value::Null, 0, 0, false));
+        assert_eq!("null", convert(&Value::Null, &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn bool_false() {
-        assert_eq!("false", to_nix(&Value::Bool(false), 0, 0, false));
+        assert_eq!("false", convert(&Value::Bool(false), &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn bool_true() {
-        assert_eq!("true", to_nix(&Value::Bool(true), 0, 0, false));
+        assert_eq!("true", convert(&Value::Bool(true), &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn number_positive_integer() {
         let input = Value::Number(Number::from(123));
-        assert_eq!("123", to_nix(&input, 0, 0, false));
+        assert_eq!("123", convert(&input, &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn number_negative_integer() {
         let input = Value::Number(Number::from(-123));
-        assert_eq!("-123", to_nix(&input, 0, 0, false));
+        assert_eq!("-123", convert(&input, &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn number_positive_float() {
         let input = Value::Number(Number::from_f64(123.5505).unwrap());
-        assert_eq!("123.5505", to_nix(&input, 0, 0, false));
+        assert_eq!("123.5505", convert(&input, &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn number_negative_float() {
         let input = Value::Number(Number::from_f64(-123.5505).unwrap());
-        assert_eq!("-123.5505", to_nix(&input, 0, 0, false));
+        assert_eq!("-123.5505", convert(&input, &Json2NixConfig::new(0, 0, false)));
+    }
+
+    #[test]
+    fn number_float_exponent() {
+        let input: Value = serde_json::from_str("1e100").unwrap();
+        assert_eq!("1.0e100", convert(&input, &Json2NixConfig::new(0, 0, false)));
+    }
+
+    #[test]
+    fn number_negative_in_array() {
+        let input: Value = serde_json::from_str("[1, -1, -1.5, -9223372036854775808]").unwrap();
+        let expected = "[\n  1\n  (-1)\n  (-1.5)\n  (-9223372036854775807 - 1)\n]";
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 2, false)));
+    }
+
+    #[test]
+    fn number_unrepresentable() {
+        let input = r#"{ "a": 18446744073709551615 }"#;
+        let mut config = Json2NixConfig::new(0, 2, false);
+        assert!(matches!(json2nix(input, &config), Err(Json2NixError::UnrepresentableNumber { .. })));
+
+        config.unrepresentable_numbers = UnrepresentableNumbers::String;
+        let (nix, warnings) = json2nix_with_warnings(input, &config).unwrap();
+        assert_eq!("{\n  a = \"18446744073709551615\";\n}", nix);
+        assert!(warnings.is_empty());
+
+        config.unrepresentable_numbers = UnrepresentableNumbers::Float;
+        let (nix, warnings) = json2nix_with_warnings(input, &config).unwrap();
+        assert_eq!("{\n  a = 1.8446744073709552e19;\n}", nix);
+        assert_eq!(1, warnings.len());
     }
 
     #[test]
     fn string_empty() {
-        assert_eq!(r#""""#, to_nix(&Value::String("".to_string()), 0, 0, false));
+        assert_eq!(r#""""#, convert(&Value::String("".to_string()), &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn string_simple() {
         let input = Value::String("Hello, world!".to_string());
         let expected = r#""Hello, world!""#;
-        assert_eq!(expected, to_nix(&input, 0, 0, false));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 0, false)));
+    }
+
+    #[test]
+    fn string_with_escapes() {
+        let input = Value::String("echo \"${HOME}\"\n\\".to_string());
+        let expected = r#""echo \"\${HOME}\"\n\\""#;
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 0, false)));
+    }
+
+    #[test]
+    fn string_indented() {
+        let config = Json2NixConfig {
+            string_style: StringStyle::Indented {
+                double_quote_threshold: None,
+            },
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let input = json!({
+            "single": "no newline",
+            "script": "#!/bin/sh\necho \"${HOME}\"\n",
+            "list": ["a\nb"]
+        });
+        let expected = r#"
+          {
+            single = "no newline";
+            script = ''
+              #!/bin/sh
+              echo "''${HOME}"
+            '';
+            list = [
+              ''
+                a
+                b''
+            ];
+          }"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+    }
+
+    #[test]
+    fn string_indented_double_quote_threshold() {
+        let config = Json2NixConfig {
+            string_style: StringStyle::Indented {
+                double_quote_threshold: Some(2),
+            },
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        assert_eq!(r#""say \"hi\"""#, convert(&json!(r#"say "hi""#), &config));
+        assert_eq!(r#"''"a" "b"''"#, convert(&json!(r#""a" "b""#), &config));
     }
 
     #[test]
     fn array_empty() {
         let input = Value::Array(vec![]);
         let expected = "[ ]";
-        assert_eq!(expected, to_nix(&input, 0, 0, false));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn array_single_bool() {
         let input = Value::Array(vec![Value::Bool(true)]);
         let expected = "[\n  true\n]";
-        assert_eq!(expected, to_nix(&input, 0, 2, false));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 2, false)));
     }
 
     #[test]
     fn array_multiple_bool() {
         let input = json!([true, false]);
         let expected = "[\n  true\n  false\n]";
-        assert_eq!(expected, to_nix(&input, 0, 2, false));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 2, false)));
     }
 
     #[test]
     fn object_empty() {
         let input = Value::Object(Map::new());
         let expected = "{ }";
-        assert_eq!(expected, to_nix(&input, 0, 0, false));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 0, false)));
     }
 
     #[test]
     fn object_single_key() {
         let input = json!({ "key": "value" });
         let expected = "{\n  key = \"value\";\n}";
-        assert_eq!(expected, to_nix(&input, 0, 2, false));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 2, false)));
     }
 
     #[test]
@@ -265,7 +605,7 @@ mod test {
             "1" = 1;
             obj1 = {
               key1 = true;
-              "null" = null;
+              null = null;
               arr1 = [
                 false
                 "abc"
@@ -279,7 +619,7 @@ mod test {
               ];
             };
           }"#;
-        assert_eq!(trim_indent(expected), to_nix(&input, 0, 2, false));
+        assert_eq!(trim_indent(expected), convert(&input, &Json2NixConfig::new(0, 2, false)));
     }
 
     #[test]
@@ -311,14 +651,14 @@ mod test {
               }
             ]
           ]"#;
-        assert_eq!(trim_indent(expected), to_nix(&input, 0, 2, false));
+        assert_eq!(trim_indent(expected), convert(&input, &Json2NixConfig::new(0, 2, false)));
     }
 
     #[test]
     fn object_compact_set_keys() {
         let input = json!({ "key": { "value": true } });
         let expected = "key.value = true;";
-        assert_eq!(expected, to_nix(&input, 0, 2, true));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 2, true)));
     }
 
     #[test]
@@ -363,7 +703,7 @@ mod test {
             "1" = 1;
             obj1 = {
               key1 = true;
-              "null" = null;
+              null = null;
               arr1 = [
                 false
                 "abc"
@@ -378,14 +718,14 @@ mod test {
               y.z = 2;
             };
           }"#;
-        assert_eq!(trim_indent(expected), to_nix(&input, 0, 2, true));
+        assert_eq!(trim_indent(expected), convert(&input, &Json2NixConfig::new(0, 2, true)));
     }
 
     #[test]
     fn array_compact_set_keys() {
         let input = json!([ { "key": { "value": true } } ]);
         let expected = "[\n  {\n    key.value = true;\n  }\n]";
-        assert_eq!(expected, to_nix(&input, 0, 2, true));
+        assert_eq!(expected, convert(&input, &Json2NixConfig::new(0, 2, true)));
     }
 
     #[test]
@@ -441,6 +781,531 @@ mod test {
               };
             }
           ]"#;
-        assert_eq!(trim_indent(expected), to_nix(&input, 0, 2, true));
+        assert_eq!(trim_indent(expected), convert(&input, &Json2NixConfig::new(0, 2, true)));
+    }
+
+    #[test]
+    fn expr_post_processing() {
+        let config = Json2NixConfig::new(0, 2, false);
+        let (expr, _) = json2nix_expr(r#"{ "enable": true }"#, &config).unwrap();
+        let NixExpr::AttrSet(mut bindings) = expr else {
+            panic!("expected an attribute set");
+        };
+        bindings.push(Binding::new(vec!["package".to_string()], NixExpr::Raw("pkgs.hello".to_string())));
+
+        let expected = "{\n  enable = true;\n  package = pkgs.hello;\n}";
+        assert_eq!(expected, print_nix_expr(&NixExpr::AttrSet(bindings), &config));
+    }
+
+    #[test]
+    fn array_compact_set_keys_nested_set() {
+        let input = json!([ { "a": { "x": 1, "y": 2 } } ]);
+        let expected = r#"
+          [
+            {
+              a = {
+                x = 1;
+                y = 2;
+              };
+            }
+          ]"#;
+        assert_eq!(trim_indent(expected), convert(&input, &Json2NixConfig::new(0, 2, true)));
+    }
+
+    #[test]
+    fn nix2json_round_trip() {
+        let input = json!({
+            "a": { "b": { "c": [1, -2, 1.5e100, null] } },
+            "text": "line one\n  ''line'' ${two}\n\ttabbed $\n",
+            "keys": { "with space": true, "if": false, "": {} },
+            "list": [[], {}, [{ "x": "y" }]],
+            "numbers": { "min": i64::MIN, "max": i64::MAX, "in_list": [i64::MIN, -1, 0.5, 0.1, -2.5e-10, 1e16] },
+            "__curPos": { "or": "inherit" },
+            "control": { "\u{1b}[0m": "$\u{7}'\u{1f}${", "\u{7f}": ["'\u{1b}", "a\u{1b}\n$\u{7}"] },
+        });
+        let configs = [
+            Json2NixConfig::new(0, 2, true),
+            Json2NixConfig {
+                string_style: StringStyle::Indented {
+                    double_quote_threshold: Some(0),
+                },
+                style: OutputStyle::Nixfmt,
+                ..Json2NixConfig::default()
+            },
+            Json2NixConfig {
+                flatten_set_keys: FlattenSetKeys::Always,
+                max_line_width: Some(40),
+                ..Json2NixConfig::default()
+            },
+        ];
+
+        for config in configs {
+            let nix = convert(&input, &config);
+            let json: Value = serde_json::from_str(&nix2json(&nix).unwrap()).unwrap();
+            assert_eq!(input, json, "Nix: {}", nix);
+        }
+
+        let nix = convert(&json!(i64::MIN), &Json2NixConfig::default());
+        assert_eq!(i64::MIN.to_string(), nix2json(&nix).unwrap());
+    }
+
+    #[test]
+    fn json5_comments() {
+        let input = r#"
+            // editor settings
+            {
+                // the size in pixels
+                editor: { fontSize: 14 },
+                rulers: [
+                    80,
+                    /* for commit messages */ 72,
+                ],
+            }"#;
+        let expected = r#"
+          # editor settings
+          {
+            # the size in pixels
+            editor.fontSize = 14;
+            rulers = [
+              80
+              # for commit messages
+              72
+            ];
+          }"#;
+        let config = Json2NixConfig {
+            input_format: InputFormat::Json5,
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        assert_eq!(trim_indent(expected), json2nix(input, &config).unwrap());
+    }
+
+    #[test]
+    fn gitconfig() {
+        let input = "[user]\n\tname = Jane Doe\n[remote \"origin\"]\n\tfetch = a\n\tfetch = b\n\tprune = true\n";
+        let expected = r#"
+          {
+            user.name = "Jane Doe";
+            remote.origin = {
+              fetch = [
+                "a"
+                "b"
+              ];
+              prune = true;
+            };
+          }"#;
+        let config = Json2NixConfig {
+            input_format: InputFormat::Ini,
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        assert_eq!(trim_indent(expected), json2nix(input, &config).unwrap());
+    }
+
+    #[test]
+    fn input_format_from_file_name() {
+        assert_eq!(Some(InputFormat::Ini), InputFormat::from_file_name(".gitconfig"));
+        assert_eq!(Some(InputFormat::Ini), InputFormat::from_file_name("settings.INI"));
+        assert_eq!(Some(InputFormat::Json5), InputFormat::from_file_name("tsconfig.jsonc"));
+        assert_eq!(None, InputFormat::from_file_name("README"));
+    }
+
+    #[test]
+    fn from_slice() {
+        let config = Json2NixConfig::default();
+        assert_eq!("\"ä\"", json2nix_from_slice("\"ä\"".as_bytes(), &config).unwrap().0);
+
+        let err = json2nix_from_slice(b"[\n\"\xff\"]", &config).unwrap_err();
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                line: 2,
+                column: 2,
+                category: ParseErrorCategory::Data,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn attr_path() {
+        let input = json!({ "a": 1, "b": 2 });
+        let config = Json2NixConfig {
+            attr_path: vec!["programs".to_string(), "oh-my-posh".to_string(), "settings".to_string()],
+            ..Json2NixConfig::default()
+        };
+        let expected = r#"
+          {
+            programs = {
+              oh-my-posh = {
+                settings = {
+                  a = 1;
+                  b = 2;
+                };
+              };
+            };
+          }"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+
+        let config = Json2NixConfig {
+            compact_set_keys: true,
+            ..config
+        };
+        let expected = r#"
+          programs.oh-my-posh.settings = {
+            a = 1;
+            b = 2;
+          };"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+
+        let config = Json2NixConfig {
+            flatten_set_keys: FlattenSetKeys::Always,
+            ..config
+        };
+        let expected = r#"
+          {
+            programs.oh-my-posh.settings.a = 1;
+            programs.oh-my-posh.settings.b = 2;
+          }"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+    }
+
+    #[test]
+    fn attr_path_compacts_single_attribute() {
+        let config = Json2NixConfig {
+            attr_path: vec!["services".to_string(), "foo".to_string()],
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        assert_eq!(
+            "services.foo.settings.port = 8080;",
+            convert(&json!({ "settings": { "port": 8080 } }), &config)
+        );
+        assert_eq!("services.foo = [ ];", convert(&json!([]), &config));
+    }
+
+    #[test]
+    fn module() {
+        let input = "// the prompt\n{ version: 2 }";
+        let config = Json2NixConfig {
+            input_format: InputFormat::Json5,
+            attr_path: vec!["programs".to_string(), "oh-my-posh".to_string(), "settings".to_string()],
+            module: true,
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        let expected = r#"
+          { config, lib, pkgs, ... }:
+
+          {
+            # the prompt
+            programs.oh-my-posh.settings.version = 2;
+          }"#;
+        assert_eq!(trim_indent(expected), json2nix(input, &config).unwrap());
+
+        let config = Json2NixConfig {
+            style: OutputStyle::Nixfmt,
+            ..config
+        };
+        let expected = r#"
+          {
+            config,
+            lib,
+            pkgs,
+            ...
+          }:
+          {
+            # the prompt
+            programs.oh-my-posh.settings.version = 2;
+          }"#;
+        assert_eq!(trim_indent(expected) + "\n", json2nix(input, &config).unwrap());
+    }
+
+    #[test]
+    fn rules() {
+        let input = json!({
+            "services": { "nginx": { "enable": true }, "sshd": { "enable": false, "ports": [22] } },
+            "boot": { "loader": { "timeout": 5 } },
+        });
+        let config = Json2NixConfig {
+            rules: vec![
+                Rule::new("/services/*/enable", RuleAction::MkDefault),
+                Rule::new("/services/sshd", RuleAction::MkIf("config.remote.enable".to_string())),
+                Rule::new("/**/timeout", RuleAction::MkOverride(50)),
+                Rule::new("/**/timeout", RuleAction::MkForce),
+            ],
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        let expected = r#"
+          {
+            services = {
+              nginx.enable = lib.mkDefault true;
+              sshd = lib.mkIf config.remote.enable {
+                enable = lib.mkDefault false;
+                ports = [
+                  22
+                ];
+              };
+            };
+            boot.loader.timeout = lib.mkForce (lib.mkOverride 50 5);
+          }"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+
+        let config = Json2NixConfig {
+            style: OutputStyle::Nixfmt,
+            ..config
+        };
+        let expected = r#"
+          {
+            services = {
+              nginx.enable = lib.mkDefault true;
+              sshd = lib.mkIf config.remote.enable {
+                enable = lib.mkDefault false;
+                ports = [ 22 ];
+              };
+            };
+            boot.loader.timeout = lib.mkForce (lib.mkOverride 50 5);
+          }"#;
+        assert_eq!(trim_indent(expected) + "\n", convert(&input, &config));
+    }
+
+    #[test]
+    fn invalid_rule() {
+        let config = Json2NixConfig {
+            rules: vec![Rule::new("services", RuleAction::MkDefault)],
+            ..Json2NixConfig::default()
+        };
+        assert!(matches!(json2nix("{}", &config), Err(Json2NixError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn raw_nix() {
+        let input = json!({
+            "package": { "$nix": "pkgs.hello" },
+            "wallpaper": "nix:./wallpaper.png",
+            "paths": [{ "$nix": "config.home.homeDirectory + \"/x\"" }, "nix:pkgs.git"],
+            "plain": { "$nix": "pkgs.hello", "other": 1 },
+        });
+        let config = Json2NixConfig {
+            raw_nix_objects: true,
+            raw_nix_prefix: Some("nix:".to_string()),
+            rules: vec![Rule::new("/package", RuleAction::MkDefault)],
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        let expected = r#"
+          {
+            package = lib.mkDefault pkgs.hello;
+            wallpaper = ./wallpaper.png;
+            paths = [
+              (config.home.homeDirectory + "/x")
+              pkgs.git
+            ];
+            plain = {
+              "$nix" = "pkgs.hello";
+              other = 1;
+            };
+          }"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+
+        // raw expressions are not merged into attribute paths
+        assert_eq!("a = pkgs.hello;", convert(&json!({ "a": { "$nix": "pkgs.hello" } }), &config));
+    }
+
+    #[test]
+    fn raw_nix_is_opt_in() {
+        let input = json!({ "a": { "$nix": "pkgs.hello" }, "b": "nix:pkgs.git" });
+        assert_eq!(
+            "{\n  a.\"$nix\" = \"pkgs.hello\";\n  b = \"nix:pkgs.git\";\n}",
+            convert(&input, &Json2NixConfig::new(0, 2, true))
+        );
+    }
+
+    #[test]
+    fn invalid_raw_nix() {
+        let config = Json2NixConfig {
+            raw_nix_objects: true,
+            ..Json2NixConfig::default()
+        };
+        let err = json2nix(r#"{ "a": [{ "$nix": "builtins.toJSON { a = 1;" }] }"#, &config).unwrap_err();
+        assert_eq!("The raw Nix expression at '/a/0' is invalid: `{` is not closed", err.to_string());
+    }
+
+    #[test]
+    fn rewrites() {
+        let config = Json2NixConfig {
+            rewrites: vec![
+                StringRewrite::HomeDirectory("/home/alice".to_string()),
+                StringRewrite::RelativePaths,
+                StringRewrite::StorePaths,
+            ],
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let input = r#"{
+            "wallpaper": "./wallpaper.png",
+            "editor": "/nix/store/0c0nv8ahhmsy1q5r5lhk10hdrqa5z1p7-neovim-0.10.0/bin/nvim",
+            "notes": ["~/notes", "plain"]
+        }"#;
+        let (nix, warnings) = json2nix_with_warnings(input, &config).unwrap();
+
+        let expected = trim_indent(
+            r#"
+            {
+              wallpaper = ./wallpaper.png;
+              editor = "${pkgs.neovim}/bin/nvim";
+              notes = [
+                "${config.home.homeDirectory}/notes"
+                "plain"
+              ];
+            }"#,
+        );
+        assert_eq!(expected, nix);
+        assert_eq!(
+            vec![
+                "The string \"./wallpaper.png\" at '/wallpaper' was rewritten to ./wallpaper.png".to_string(),
+                "The string \"/nix/store/0c0nv8ahhmsy1q5r5lhk10hdrqa5z1p7-neovim-0.10.0/bin/nvim\" at '/editor' was rewritten to \"${pkgs.neovim}/bin/nvim\"".to_string(),
+                "The string \"~/notes\" at '/notes/0' was rewritten to \"${config.home.homeDirectory}/notes\"".to_string(),
+            ],
+            warnings.iter().map(ToString::to_string).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn extract_repeated() {
+        let config = Json2NixConfig {
+            module: true,
+            extract_repeated: Some(3),
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let input = json!({
+            "blocks": [
+                { "type": "path", "colors": { "foreground": "#ffffff", "background": "#000000" } },
+                { "type": "git", "colors": { "foreground": "#ffffff", "background": "#000000" } },
+            ],
+            "small": [{ "a": 1 }, { "a": 1 }],
+        });
+        let expected = trim_indent(
+            r##"
+            { config, lib, pkgs, ... }:
+
+            let
+              colors = {
+                foreground = "#ffffff";
+                background = "#000000";
+              };
+            in
+            {
+              blocks = [
+                {
+                  type = "path";
+                  colors = colors;
+                }
+                {
+                  type = "git";
+                  colors = colors;
+                }
+              ];
+              small = [
+                {
+                  a = 1;
+                }
+                {
+                  a = 1;
+                }
+              ];
+            }"##,
+        );
+        assert_eq!(expected, convert(&input, &config));
+    }
+
+    #[test]
+    fn deep_input() {
+        let depth = 50_000;
+        let input = format!("{}1{}", r#"{"a":["#.repeat(depth), "]}".repeat(depth));
+
+        let expected = format!("{}1{}", "{\na = [\n".repeat(depth), "\n];\n}".repeat(depth));
+        assert_eq!(expected, json2nix(&input, &Json2NixConfig::new(0, 0, false)).unwrap());
+
+        let config = Json2NixConfig {
+            max_line_width: Some(80),
+            flatten_set_keys: FlattenSetKeys::Always,
+            ..Json2NixConfig::new(0, 0, true)
+        };
+        // only the innermost lists and sets fit on a line
+        let nix = json2nix(&input, &config).unwrap();
+        assert!(nix.starts_with("a = [\n{\na = [\n{\n"), "{}", &nix[..100]);
+        assert!(nix.contains("\n{ a = [ { a = [ { a = [ { a = [ { a = [ { a = [ 1 ]; } ]; } ]; } ]; } ]; } ]; }\n"));
+        assert!(nix.ends_with("\n];\n}\n];"));
+
+        let config = Json2NixConfig {
+            extract_repeated: Some(2),
+            ..Json2NixConfig::new(0, 0, false)
+        };
+        let nix = json2nix(&format!("[{},{}]", input, input), &config).unwrap();
+        assert_eq!(format!("let\nsharedElement = {};\nin\n[\nsharedElement\nsharedElement\n]", expected), nix);
+    }
+
+    #[test]
+    fn max_depth() {
+        let config = Json2NixConfig {
+            max_depth: Some(2),
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        assert_eq!("a = [\n  1\n];", json2nix(r#"{ "a": [1] }"#, &config).unwrap());
+        assert_eq!(
+            "The value at '/a/0' is nested deeper than the maximum depth of 2",
+            json2nix(r#"{ "a": [[1]] }"#, &config).unwrap_err().to_string()
+        );
+        // sets that are merged into attribute paths count as well
+        assert!(matches!(
+            json2nix(r#"{ "a": { "b": { "c": 1 } } }"#, &config),
+            Err(Json2NixError::TooDeep { pointer, max_depth: 2 }) if pointer == "/a/b"
+        ));
+    }
+
+    #[test]
+    fn key_order() {
+        let input = json!({
+            "services": { "sshd": { "ports": [22], "enable": true }, "nginx": { "enable": true } },
+            "boot": { "loader10": 1, "loader9": 2 },
+        });
+        let config = Json2NixConfig {
+            key_order: KeyOrder::Natural,
+            key_priority: vec!["enable".to_string()],
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let expected = r#"
+          {
+            boot = {
+              loader9 = 2;
+              loader10 = 1;
+            };
+            services = {
+              nginx = {
+                enable = true;
+              };
+              sshd = {
+                enable = true;
+                ports = [
+                  22
+                ];
+              };
+            };
+          }"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+
+        // attribute paths are sorted like nested sets
+        let config = Json2NixConfig {
+            key_order: KeyOrder::Nix,
+            flatten_set_keys: FlattenSetKeys::Always,
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let expected = r#"
+          {
+            boot.loader10 = 1;
+            boot.loader9 = 2;
+            services.nginx.enable = true;
+            services.sshd.enable = true;
+            services.sshd.ports = [
+              22
+            ];
+          }"#;
+        assert_eq!(trim_indent(expected), convert(&input, &config));
+        assert!(!config.supports_streaming());
     }
 }
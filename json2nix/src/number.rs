@@ -0,0 +1,199 @@
+use serde_json::Number;
+
+use crate::{Json2NixError, UnrepresentableNumbers, Warning, ast::NixExpr};
+
+/// Converts `number` to a Nix number with the same value.
+///
+/// Nix integers are signed 64-bit integers, so larger integers are handled according to `policy`. Thanks to the
+/// `arbitrary_precision` feature of serde_json, such integers keep all of their digits instead of becoming floats. The
+/// same goes for floats that are out of the range of `f64`, which only the string policy can represent.
+pub fn convert_number(number: &Number, policy: UnrepresentableNumbers, warnings: &mut Vec<Warning>) -> Result<NixExpr, Json2NixError> {
+    if let Some(integer) = number.as_i64() {
+        return Ok(NixExpr::Int(integer));
+    }
+
+    // only finite numbers with a fraction or an exponent are floats
+    if number.is_f64() {
+        return Ok(NixExpr::Float(number.as_f64().expect("the number is a finite float")));
+    }
+
+    let unrepresentable = || Json2NixError::UnrepresentableNumber { number: number.to_string() };
+    match policy {
+        UnrepresentableNumbers::Error => Err(unrepresentable()),
+        UnrepresentableNumbers::String => Ok(NixExpr::String(number.to_string())),
+        UnrepresentableNumbers::Float => {
+            let float = number.as_f64().ok_or_else(unrepresentable)?;
+            warnings.push(Warning::LossyNumber {
+                number: number.to_string(),
+                replacement: format_float(float),
+            });
+            Ok(NixExpr::Float(float))
+        }
+    }
+}
+
+/// Renders `integer` as Nix expression. Negative numbers are rendered using the unary minus, so they have to be wrapped in
+/// parentheses inside of lists.
+pub fn format_integer(integer: i64) -> String {
+    match integer {
+        // the literal 9223372036854775808 is out of range, so the negation of it cannot be written directly
+        i64::MIN => format!("-{} - 1", i64::MAX),
+        _ => integer.to_string(),
+    }
+}
+
+/// Renders the finite `float` as Nix expression. Like integers, negative floats have to be wrapped in parentheses inside of lists.
+///
+/// Nix float literals always need a dot: `(([1-9][0-9]*\.[0-9]*)|(0?\.[0-9]+))([Ee][+-]?[0-9]+)?`
+///
+/// Nix has no literal for negative zero, and `-0.0` is the negation of `0.0`, which evaluates to `0.0`. Therefore the sign
+/// of negative zero is dropped.
+pub fn format_float(float: f64) -> String {
+    // `-0.0 == 0.0`, so this replaces both zeros with the positive one
+    let float = if float == 0.0 { 0.0 } else { float };
+    let formatted = Number::from_f64(float).expect("JSON numbers are finite").to_string();
+
+    match formatted
+        .split_once('e')
+        .map(|(mantissa, exponent)| (mantissa, exponent.trim_start_matches('+')))
+    {
+        Some((mantissa, exponent)) if mantissa.contains('.') => format!("{}e{}", mantissa, exponent),
+        Some((mantissa, exponent)) => format!("{}.0e{}", mantissa, exponent),
+        None if formatted.contains('.') => formatted,
+        None => format!("{}.0", formatted),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn format(json: &str, policy: UnrepresentableNumbers) -> (Result<String, Json2NixError>, Vec<Warning>) {
+        let number: Number = serde_json::from_str(json).unwrap();
+        let mut warnings = Vec::new();
+        let formatted = convert_number(&number, policy, &mut warnings).map(|expr| match expr {
+            NixExpr::Int(integer) => format_integer(integer),
+            NixExpr::Float(float) => format_float(float),
+            NixExpr::String(string) => format!("\"{}\"", string),
+            expr => panic!("unexpected expression: {:?}", expr),
+        });
+        (formatted, warnings)
+    }
+
+    fn unwrap((result, warnings): (Result<String, Json2NixError>, Vec<Warning>)) -> (String, Vec<Warning>) {
+        (result.unwrap(), warnings)
+    }
+
+    #[test]
+    fn integers() {
+        let values_and_expected = [
+            ("0", "0"),
+            ("1", "1"),
+            ("-1", "-1"),
+            ("123", "123"),
+            ("-123", "-123"),
+            ("9223372036854775807", "9223372036854775807"),
+            ("-9223372036854775807", "-9223372036854775807"),
+            ("-9223372036854775808", "-9223372036854775807 - 1"),
+        ];
+        for (value, expected) in values_and_expected {
+            assert_eq!((expected.to_string(), vec![]), unwrap(format(value, UnrepresentableNumbers::Error)));
+        }
+    }
+
+    #[test]
+    fn floats() {
+        let values_and_expected = [
+            ("0.0", "0.0"),
+            ("-0.0", "0.0"),
+            ("-0e5", "0.0"),
+            ("1.0", "1.0"),
+            ("0.5", "0.5"),
+            ("123.5505", "123.5505"),
+            ("-123.5505", "-123.5505"),
+            ("1e100", "1.0e100"),
+            ("1E100", "1.0e100"),
+            ("1e+100", "1.0e100"),
+            ("-1e100", "-1.0e100"),
+            ("1.5e300", "1.5e300"),
+            ("1e-7", "1.0e-7"),
+            ("2.5e-10", "2.5e-10"),
+            ("1e2", "100.0"),
+            ("1e16", "1.0e16"),
+            ("0.1", "0.1"),
+            ("5e-324", "5.0e-324"),
+            ("1.7976931348623157e308", "1.7976931348623157e308"),
+        ];
+        for (value, expected) in values_and_expected {
+            assert_eq!(
+                (expected.to_string(), vec![]),
+                unwrap(format(value, UnrepresentableNumbers::Error)),
+                "value: {}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn floats_round_trip() {
+        let values = [0.1, 1.0 / 3.0, 1e-300, 6.02214076e23, f64::MAX, f64::MIN_POSITIVE, f64::EPSILON];
+        for value in values {
+            assert_eq!(value, format_float(value).parse::<f64>().unwrap());
+        }
+    }
+
+    #[test]
+    fn unrepresentable_error() {
+        let (result, warnings) = format("9223372036854775808", UnrepresentableNumbers::Error);
+        assert!(matches!(result, Err(Json2NixError::UnrepresentableNumber { number }) if number == "9223372036854775808"));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn unrepresentable_error_big_integers() {
+        for value in [
+            "18446744073709551615",
+            "123456789012345678901234567890",
+            "-9223372036854775809",
+            "-18446744073709551616",
+        ] {
+            let (result, _) = format(value, UnrepresentableNumbers::Error);
+            assert!(matches!(result, Err(Json2NixError::UnrepresentableNumber { number }) if number == value));
+        }
+    }
+
+    #[test]
+    fn unrepresentable_string() {
+        let values_and_expected = [
+            ("9223372036854775808", r#""9223372036854775808""#),
+            ("18446744073709551615", r#""18446744073709551615""#),
+            ("123456789012345678901234567890", r#""123456789012345678901234567890""#),
+            ("-18446744073709551616", r#""-18446744073709551616""#),
+            ("1e400", r#""1e+400""#),
+        ];
+        for (value, expected) in values_and_expected {
+            assert_eq!((expected.to_string(), vec![]), unwrap(format(value, UnrepresentableNumbers::String)));
+        }
+    }
+
+    #[test]
+    fn unrepresentable_float() {
+        let (result, warnings) = format("18446744073709551615", UnrepresentableNumbers::Float);
+        assert_eq!("1.8446744073709552e19", result.unwrap());
+        assert_eq!(
+            vec![Warning::LossyNumber {
+                number: "18446744073709551615".to_string(),
+                replacement: "1.8446744073709552e19".to_string(),
+            }],
+            warnings
+        );
+
+        let (result, warnings) = format("-18446744073709551616", UnrepresentableNumbers::Float);
+        assert_eq!("-1.8446744073709552e19", result.unwrap());
+        assert_eq!(1, warnings.len());
+
+        let (result, warnings) = format("1e400", UnrepresentableNumbers::Float);
+        assert!(matches!(result, Err(Json2NixError::UnrepresentableNumber { number }) if number == "1e+400"));
+        assert!(warnings.is_empty());
+    }
+}
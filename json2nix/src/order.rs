@@ -0,0 +1,124 @@
+use std::cmp::Ordering;
+
+use serde_json::{Map, Value};
+
+use crate::{Json2NixConfig, KeyOrder};
+
+/// Returns the attributes of `object` in the order of [`Json2NixConfig::key_order`], with the ones named in
+/// [`Json2NixConfig::key_priority`] in front.
+pub fn ordered_attributes<'v>(object: &'v Map<String, Value>, config: &Json2NixConfig) -> Vec<(&'v String, &'v Value)> {
+    let mut attributes = object.iter().collect::<Vec<_>>();
+    if config.key_order == KeyOrder::Preserve && config.key_priority.is_empty() {
+        return attributes;
+    }
+
+    let priority = |key: &str| {
+        config
+            .key_priority
+            .iter()
+            .position(|priority_key| priority_key == key)
+            .unwrap_or(config.key_priority.len())
+    };
+    // the sort is stable, so the order of the input is kept for attributes that are equal
+    attributes.sort_by(|(a, _), (b, _)| priority(a).cmp(&priority(b)).then_with(|| compare_keys(a, b, config.key_order)));
+    attributes
+}
+
+fn compare_keys(a: &str, b: &str, key_order: KeyOrder) -> Ordering {
+    match key_order {
+        KeyOrder::Preserve => Ordering::Equal,
+        KeyOrder::Nix => a.cmp(b),
+        // numbers with leading zeros like `01` and `1` are equal, so the bytes decide between them
+        KeyOrder::Natural => compare_natural(a.as_bytes(), b.as_bytes()).then_with(|| a.cmp(b)),
+    }
+}
+
+// Compares byte by byte, except for runs of ASCII digits, which are compared by their numeric value.
+fn compare_natural(mut a: &[u8], mut b: &[u8]) -> Ordering {
+    loop {
+        match (a.first(), b.first()) {
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (number_a, rest_a) = split_number(a);
+                let (number_b, rest_b) = split_number(b);
+                // without leading zeros, a longer number is a larger one
+                let ordering = number_a.len().cmp(&number_b.len()).then_with(|| number_a.cmp(number_b));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                (a, b) = (rest_a, rest_b);
+            }
+            (Some(x), Some(y)) if x == y => (a, b) = (&a[1..], &b[1..]),
+            (Some(x), Some(y)) => return x.cmp(y),
+            _ => return a.len().cmp(&b.len()),
+        }
+    }
+}
+
+// Splits the leading digits off `bytes` and returns them without leading zeros, and the rest.
+fn split_number(bytes: &[u8]) -> (&[u8], &[u8]) {
+    let length = bytes.iter().take_while(|byte| byte.is_ascii_digit()).count();
+    let (digits, rest) = bytes.split_at(length);
+    let zeros = digits.iter().take_while(|digit| **digit == b'0').count();
+    (&digits[zeros..], rest)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn keys(value: &Value, config: &Json2NixConfig) -> Vec<String> {
+        let object = value.as_object().unwrap();
+        ordered_attributes(object, config).into_iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    fn config(key_order: KeyOrder, key_priority: &[&str]) -> Json2NixConfig {
+        Json2NixConfig {
+            key_order,
+            key_priority: key_priority.iter().map(|key| key.to_string()).collect(),
+            ..Json2NixConfig::default()
+        }
+    }
+
+    #[test]
+    fn key_orders() {
+        let value = json!({ "item10": 0, "b": 0, "item2": 0, "B": 0, "item02": 0, "a": 0 });
+        assert_eq!(
+            ["item10", "b", "item2", "B", "item02", "a"],
+            keys(&value, &config(KeyOrder::Preserve, &[])).as_slice()
+        );
+        assert_eq!(
+            ["B", "a", "b", "item02", "item10", "item2"],
+            keys(&value, &config(KeyOrder::Nix, &[])).as_slice()
+        );
+        assert_eq!(
+            ["B", "a", "b", "item02", "item2", "item10"],
+            keys(&value, &config(KeyOrder::Natural, &[])).as_slice()
+        );
+    }
+
+    #[test]
+    fn key_priority() {
+        let value = json!({ "settings": 0, "package": 0, "enable": 0, "extraConfig": 0 });
+        assert_eq!(
+            ["enable", "package", "settings", "extraConfig"],
+            keys(&value, &config(KeyOrder::Preserve, &["enable", "package", "missing"])).as_slice()
+        );
+        assert_eq!(
+            ["enable", "extraConfig", "package", "settings"],
+            keys(&value, &config(KeyOrder::Nix, &["enable"])).as_slice()
+        );
+    }
+
+    #[test]
+    fn natural() {
+        let compare = |a: &str, b: &str| compare_keys(a, b, KeyOrder::Natural);
+        assert_eq!(Ordering::Less, compare("a9", "a10"));
+        assert_eq!(Ordering::Less, compare("a", "a1"));
+        assert_eq!(Ordering::Less, compare("1.9", "1.10"));
+        assert_eq!(Ordering::Less, compare("01", "1"));
+        assert_eq!(Ordering::Greater, compare("a2b", "a2a"));
+        assert_eq!(Ordering::Equal, compare("x", "x"));
+    }
+}
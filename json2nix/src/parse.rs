@@ -0,0 +1,889 @@
+use serde_json::{Map, Number, Value};
+
+use crate::{
+    Json2NixError, ParseErrorCategory,
+    escape::{CUR_POS, KEYWORDS},
+};
+
+/// Parses the pure-data subset of Nix: attribute sets (including attribute paths like `a.b = …;`), lists, strings, numbers,
+/// booleans and `null`. Everything that needs evaluation, like variables, functions, operators or string interpolation,
+/// is rejected with [`ParseErrorCategory::Unsupported`]. The only exception is `-9223372036854775807 - 1`, which is how
+/// json2nix writes the smallest integer. Like `serde_json`, the parser is recursive and rejects lists, attribute sets and
+/// parentheses that are nested more than 128 levels deep.
+pub fn parse_nix(input: &str) -> Result<Value, Json2NixError> {
+    let mut parser = Parser {
+        input,
+        position: 0,
+        depth: 0,
+    };
+
+    parser.skip_trivia()?;
+    let value = parser.value(false)?;
+    parser.skip_trivia()?;
+
+    match parser.peek() {
+        None => Ok(value),
+        Some(_) => Err(parser.unexpected_after_value("end of input")),
+    }
+}
+
+/// Parses an attribute path like `programs.git.settings` or `"foo.bar".baz`.
+pub fn parse_attr_path(input: &str) -> Result<Vec<String>, Json2NixError> {
+    let mut parser = Parser {
+        input,
+        position: 0,
+        depth: 0,
+    };
+
+    parser.skip_trivia()?;
+    let path = parser.attr_path()?;
+
+    match parser.peek() {
+        None => Ok(path),
+        Some(_) => Err(parser.unexpected("`.` or end of input")),
+    }
+}
+
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a str,
+    /// The byte offset of the next character.
+    position: usize,
+    /// The number of lists, attribute sets and parentheses around the next value.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.rest().chars().nth(1)
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.position += bytes;
+    }
+
+    fn error_at(&self, position: usize, category: ParseErrorCategory, message: impl Into<String>) -> Json2NixError {
+        Json2NixError::parse_at(self.input, position, category, message)
+    }
+
+    fn error(&self, category: ParseErrorCategory, message: impl Into<String>) -> Json2NixError {
+        self.error_at(self.position, category, message)
+    }
+
+    fn unexpected(&self, expected: &str) -> Json2NixError {
+        match self.peek() {
+            None => self.error(ParseErrorCategory::Eof, format!("expected {}", expected)),
+            Some(c) => self.error(ParseErrorCategory::Syntax, format!("expected {}, found `{}`", expected, c)),
+        }
+    }
+
+    // A value is complete, so anything but the expected token is most likely an operator, a function application or a
+    // function, which cannot be converted.
+    fn unexpected_after_value(&self, expected: &str) -> Json2NixError {
+        let message = match self.peek() {
+            Some(':' | '@') => "functions are not supported",
+            Some('.') => "selecting attributes is not supported",
+            Some('+' | '-' | '*' | '/' | '<' | '>' | '=' | '!' | '&' | '|' | '?') => "operators are not supported",
+            Some(c) if is_value_start(c) => "function applications are not supported",
+            _ => return self.unexpected(expected),
+        };
+        self.error(ParseErrorCategory::Unsupported, message)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Json2NixError> {
+        if self.peek() != Some(expected) {
+            return Err(self.unexpected_after_value(&format!("`{}`", expected)));
+        }
+        self.advance(expected.len_utf8());
+        Ok(())
+    }
+
+    // Skips whitespace, line comments and block comments.
+    fn skip_trivia(&mut self) -> Result<(), Json2NixError> {
+        loop {
+            let rest = self.rest();
+            if rest.starts_with('#') {
+                self.advance(rest.find('\n').unwrap_or(rest.len()));
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                match comment.find("*/") {
+                    Some(end) => self.advance(end + 4),
+                    None => return Err(self.error(ParseErrorCategory::Eof, "unterminated comment")),
+                }
+            } else if let Some(c) = self.peek()
+                && c.is_whitespace()
+            {
+                self.advance(c.len_utf8());
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn value(&mut self, in_list: bool) -> Result<Value, Json2NixError> {
+        let start = self.position;
+
+        match self.peek() {
+            None => Err(self.error(ParseErrorCategory::Eof, "expected a value")),
+            Some('{') => self.nested(Self::attr_set),
+            Some('[') => self.nested(Self::list),
+            Some('"') => self.string().map(Value::String),
+            Some('\'') if self.rest().starts_with("''") => self.indented_string().map(Value::String),
+            Some('(') => self.nested(Self::parenthesized),
+            Some('-') if in_list => Err(self.error(ParseErrorCategory::Syntax, "negative numbers in lists must be wrapped in parentheses")),
+            Some('-') => {
+                self.advance(1);
+                self.skip_trivia()?;
+                match self.peek() {
+                    Some(c) if c.is_ascii_digit() || c == '.' => self.number(start, true),
+                    _ => Err(self.error(ParseErrorCategory::Unsupported, "only numbers can be negated")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || (c == '.' && self.peek_second().is_some_and(|c| c.is_ascii_digit())) => self.number(start, false),
+            Some(c) if is_path_start(self.rest()) => {
+                Err(self.error(ParseErrorCategory::Unsupported, format!("paths are not supported, found `{}`", c)))
+            }
+            Some(c) if is_identifier_start(c) => {
+                let identifier = self.identifier();
+                match identifier {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "null" => Ok(Value::Null),
+                    "rec" => Err(self.error_at(start, ParseErrorCategory::Unsupported, "recursive attribute sets are not supported")),
+                    keyword if KEYWORDS.contains(&keyword) => Err(self.error_at(
+                        start,
+                        ParseErrorCategory::Unsupported,
+                        format!("`{}` expressions are not supported", keyword),
+                    )),
+                    variable => Err(self.error_at(
+                        start,
+                        ParseErrorCategory::Unsupported,
+                        format!("variables are not supported, found `{}`", variable),
+                    )),
+                }
+            }
+            Some(_) => Err(self.unexpected_after_value("a value")),
+        }
+    }
+
+    // Parses a nested value with `parse`, unless that would exceed the maximum depth.
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Value, Json2NixError>) -> Result<Value, Json2NixError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(ParseErrorCategory::Syntax, "recursion limit exceeded"));
+        }
+        self.depth += 1;
+        let value = parse(self)?;
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn parenthesized(&mut self) -> Result<Value, Json2NixError> {
+        self.advance(1);
+        self.skip_trivia()?;
+        let value = self.value(false)?;
+        self.skip_trivia()?;
+        self.expect(')')?;
+        Ok(value)
+    }
+
+    fn number(&mut self, start: usize, negative: bool) -> Result<Value, Json2NixError> {
+        let digits_start = self.position;
+        let rest = self.rest();
+        let mut length = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let mut is_float = false;
+
+        if rest[length..].starts_with('.') {
+            is_float = true;
+            length += 1;
+            length += rest[length..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - length);
+
+            // the exponent is only allowed for floats
+            let exponent = &rest[length..];
+            if let Some(sign_and_digits) = exponent.strip_prefix(['e', 'E']) {
+                let digits = sign_and_digits.strip_prefix(['+', '-']).unwrap_or(sign_and_digits);
+                let digit_count = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+                if digit_count > 0 {
+                    length += exponent.len() - digits.len() + digit_count;
+                }
+            }
+        }
+
+        let literal = &rest[..length];
+        self.advance(length);
+
+        if is_float {
+            // like the lexer of Nix: (([1-9][0-9]*\.[0-9]*)|(0?\.[0-9]+))([Ee][+-]?[0-9]+)?
+            let (integer_part, fraction) = literal.split_once('.').expect("floats contain a dot");
+            if integer_part.starts_with('0') && (integer_part.len() > 1 || !fraction.starts_with(|c: char| c.is_ascii_digit())) {
+                return Err(self.error_at(digits_start, ParseErrorCategory::Syntax, format!("invalid float `{}`", literal)));
+            }
+            let float = literal
+                .parse::<f64>()
+                .ok()
+                .filter(|float| float.is_finite())
+                .ok_or_else(|| self.error_at(digits_start, ParseErrorCategory::Data, format!("the float {} is out of range", literal)))?;
+            let float = if negative { -float } else { float };
+            Ok(Value::Number(Number::from_f64(float).expect("'float' is finite")))
+        } else {
+            let source = &self.input[start..self.position];
+            let digits = if negative { format!("-{}", literal) } else { literal.to_string() };
+            let integer = digits
+                .parse::<i64>()
+                .map_err(|_| self.error_at(start, ParseErrorCategory::Data, format!("the integer {} is out of range", source)))?;
+            if integer == -i64::MAX && self.is_followed_by_minus_one()? {
+                return Ok(Value::from(i64::MIN));
+            }
+            Ok(Value::from(integer))
+        }
+    }
+
+    // Consumes ` - 1` if it follows, so the smallest integer can be written as `-9223372036854775807 - 1`.
+    fn is_followed_by_minus_one(&mut self) -> Result<bool, Json2NixError> {
+        let position = self.position;
+        self.skip_trivia()?;
+        if self.peek() == Some('-') {
+            self.advance(1);
+            self.skip_trivia()?;
+            let rest = self.rest();
+            if rest.starts_with('1') && !rest[1..].starts_with(|c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '\'' | '-')) {
+                self.advance(1);
+                return Ok(true);
+            }
+        }
+        self.position = position;
+        Ok(false)
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '\'' | '-')))
+            .unwrap_or(rest.len());
+        self.advance(length);
+        &rest[..length]
+    }
+
+    fn string(&mut self) -> Result<String, Json2NixError> {
+        let start = self.position;
+        self.advance(1);
+        let mut string = String::new();
+
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error_at(start, ParseErrorCategory::Eof, "unterminated string"));
+            };
+            self.advance(c.len_utf8());
+
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let Some(escaped) = self.peek() else {
+                        return Err(self.error_at(start, ParseErrorCategory::Eof, "unterminated string"));
+                    };
+                    self.advance(escaped.len_utf8());
+                    string.push(unescape(escaped));
+                }
+                '$' => match self.peek() {
+                    Some('{') => {
+                        return Err(self.error_at(
+                            self.position - 1,
+                            ParseErrorCategory::Unsupported,
+                            "string interpolation is not supported",
+                        ));
+                    }
+                    // `$${` is not an interpolation
+                    Some(next) if next != '"' && next != '\\' => {
+                        self.advance(next.len_utf8());
+                        string.push('$');
+                        string.push(next);
+                    }
+                    _ => string.push('$'),
+                },
+                c => string.push(c),
+            }
+        }
+    }
+
+    // Follows the lexer of Nix, so the indentation is stripped in the same way. The string is split into parts of text and
+    // escape sequences, because escaped characters never count as indentation.
+    fn indented_string(&mut self) -> Result<String, Json2NixError> {
+        let start = self.position;
+        self.advance(2);
+
+        // the first line is skipped if it only contains spaces
+        let rest = self.rest();
+        let spaces = rest.find(|c| c != ' ').unwrap_or(rest.len());
+        if rest[spaces..].starts_with('\n') {
+            self.advance(spaces + 1);
+        }
+
+        let mut parts = Vec::new();
+        loop {
+            let rest = self.rest();
+            let part = if rest.starts_with("'''") {
+                self.advance(3);
+                IndentedStringPart::escaped("''")
+            } else if rest.starts_with("''$") {
+                self.advance(3);
+                IndentedStringPart::escaped("$")
+            } else if let Some(escaped) = rest.strip_prefix("''\\") {
+                let Some(c) = escaped.chars().next() else {
+                    return Err(self.error_at(start, ParseErrorCategory::Eof, "unterminated string"));
+                };
+                self.advance(3 + c.len_utf8());
+                IndentedStringPart::escaped(&unescape(c).to_string())
+            } else if rest.starts_with("''") {
+                self.advance(2);
+                break;
+            } else if rest.starts_with("${") {
+                return Err(self.error(ParseErrorCategory::Unsupported, "string interpolation is not supported"));
+            } else if rest.is_empty() {
+                return Err(self.error_at(start, ParseErrorCategory::Eof, "unterminated string"));
+            } else {
+                match text_length(rest) {
+                    0 => {
+                        // a single `$` or `'` that cannot be part of text
+                        self.advance(1);
+                        IndentedStringPart::escaped(&rest[..1])
+                    }
+                    length => {
+                        self.advance(length);
+                        IndentedStringPart {
+                            text: rest[..length].to_string(),
+                            is_indentation: true,
+                        }
+                    }
+                }
+            };
+            parts.push(part);
+        }
+
+        Ok(strip_indentation(&parts))
+    }
+
+    fn list(&mut self) -> Result<Value, Json2NixError> {
+        self.advance(1);
+        let mut elements = Vec::new();
+
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some(']') {
+                self.advance(1);
+                return Ok(Value::Array(elements));
+            }
+            elements.push(self.value(true)?);
+        }
+    }
+
+    fn attr_set(&mut self) -> Result<Value, Json2NixError> {
+        self.advance(1);
+        let mut attributes = Map::new();
+
+        loop {
+            self.skip_trivia()?;
+            if self.peek() == Some('}') {
+                self.advance(1);
+                return Ok(Value::Object(attributes));
+            }
+            if self.rest().starts_with("...") {
+                return Err(self.error(ParseErrorCategory::Unsupported, "functions are not supported"));
+            }
+
+            let start = self.position;
+            let path = self.attr_path()?;
+            self.skip_trivia()?;
+            if let Some(',' | '?' | '}') = self.peek() {
+                return Err(self.error(ParseErrorCategory::Unsupported, "functions are not supported"));
+            }
+            self.expect('=')?;
+            self.skip_trivia()?;
+
+            // every attribute of the path but the last is another set around the value
+            let path_depth = path.len() - 1;
+            if self.depth + path_depth > MAX_DEPTH {
+                return Err(self.error_at(start, ParseErrorCategory::Syntax, "recursion limit exceeded"));
+            }
+            self.depth += path_depth;
+            let value = self.value(false)?;
+            self.depth -= path_depth;
+
+            self.skip_trivia()?;
+            self.expect(';')?;
+
+            insert(&mut attributes, &path, value).map_err(|message| self.error_at(start, ParseErrorCategory::Data, message))?;
+        }
+    }
+
+    fn attr_path(&mut self) -> Result<Vec<String>, Json2NixError> {
+        let mut path = Vec::new();
+
+        loop {
+            let start = self.position;
+            let attribute = match self.peek() {
+                Some('"') => self.string()?,
+                Some('$') if self.rest().starts_with("${") => {
+                    return Err(self.error(ParseErrorCategory::Unsupported, "dynamic attributes are not supported"));
+                }
+                Some(c) if is_identifier_start(c) => match self.identifier() {
+                    "inherit" => return Err(self.error_at(start, ParseErrorCategory::Unsupported, "`inherit` is not supported")),
+                    CUR_POS => {
+                        return Err(self.error_at(
+                            start,
+                            ParseErrorCategory::Syntax,
+                            format!("`{}` must be quoted to be used as attribute name", CUR_POS),
+                        ));
+                    }
+                    keyword if keyword != "or" && KEYWORDS.contains(&keyword) => {
+                        return Err(self.error_at(
+                            start,
+                            ParseErrorCategory::Syntax,
+                            format!("the keyword `{}` must be quoted to be used as attribute name", keyword),
+                        ));
+                    }
+                    identifier => identifier.to_string(),
+                },
+                _ => return Err(self.unexpected("an attribute name")),
+            };
+            path.push(attribute);
+
+            self.skip_trivia()?;
+            if self.peek() != Some('.') {
+                return Ok(path);
+            }
+            self.advance(1);
+            self.skip_trivia()?;
+        }
+    }
+}
+
+// Inserts `value` at `path` like Nix does: attribute sets along the path are created or extended, and if the attribute is
+// already an attribute set and `value` is one as well, the attributes of both are merged.
+fn insert(attributes: &mut Map<String, Value>, path: &[String], value: Value) -> Result<(), String> {
+    let mut attributes = attributes;
+
+    for (index, key) in path[..path.len() - 1].iter().enumerate() {
+        let entry = attributes.entry(key.clone()).or_insert_with(|| Value::Object(Map::new()));
+        attributes = match entry {
+            Value::Object(nested) => nested,
+            _ => return Err(already_defined(&path[..=index])),
+        };
+    }
+
+    let key = &path[path.len() - 1];
+    match (attributes.get_mut(key), value) {
+        (None, value) => {
+            attributes.insert(key.clone(), value);
+        }
+        (Some(Value::Object(existing)), Value::Object(new)) => {
+            for (nested_key, nested_value) in new {
+                if existing.contains_key(&nested_key) {
+                    let mut nested_path = path.to_vec();
+                    nested_path.push(nested_key);
+                    return Err(already_defined(&nested_path));
+                }
+                existing.insert(nested_key, nested_value);
+            }
+        }
+        (Some(_), _) => return Err(already_defined(path)),
+    }
+
+    Ok(())
+}
+
+fn already_defined(path: &[String]) -> String {
+    format!("the attribute `{}` is already defined", path.join("."))
+}
+
+struct IndentedStringPart {
+    text: String,
+    /// Whether spaces at the start of a line count as indentation, which is not the case for escape sequences.
+    is_indentation: bool,
+}
+
+impl IndentedStringPart {
+    fn escaped(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            is_indentation: false,
+        }
+    }
+}
+
+// The length of the text at the start of `rest` up to the next escape sequence, interpolation or end of the string.
+fn text_length(rest: &str) -> usize {
+    let mut chars = rest.char_indices().peekable();
+
+    while let Some(&(index, c)) = chars.peek() {
+        let next = rest[index + c.len_utf8()..].chars().next();
+        let length = match (c, next) {
+            ('$', Some(next)) if next != '{' && next != '\'' => 2,
+            ('\'', Some(next)) if next != '\'' && next != '$' => 2,
+            ('$' | '\'', _) => return index,
+            _ => 1,
+        };
+        for _ in 0..length {
+            chars.next();
+        }
+    }
+
+    rest.len()
+}
+
+// Removes the common indentation of all lines like `stripIndentation` of Nix. Lines that only contain spaces do not count,
+// and the last line is removed if it only contains spaces.
+fn strip_indentation(parts: &[IndentedStringPart]) -> String {
+    let mut at_start_of_line = true;
+    let mut min_indentation = usize::MAX;
+    let mut current_indentation = 0;
+
+    for part in parts {
+        if !part.is_indentation {
+            if at_start_of_line {
+                at_start_of_line = false;
+                min_indentation = min_indentation.min(current_indentation);
+            }
+            continue;
+        }
+        for c in part.text.chars() {
+            if at_start_of_line {
+                match c {
+                    ' ' => current_indentation += 1,
+                    '\n' => current_indentation = 0,
+                    _ => {
+                        at_start_of_line = false;
+                        min_indentation = min_indentation.min(current_indentation);
+                    }
+                }
+            } else if c == '\n' {
+                at_start_of_line = true;
+                current_indentation = 0;
+            }
+        }
+    }
+
+    let mut result = String::new();
+    let mut at_start_of_line = true;
+    let mut dropped = 0;
+
+    for (index, part) in parts.iter().enumerate() {
+        let mut text = String::new();
+        for c in part.text.chars() {
+            if at_start_of_line {
+                match c {
+                    ' ' => {
+                        if dropped >= min_indentation {
+                            text.push(c);
+                        }
+                        dropped += 1;
+                    }
+                    '\n' => {
+                        dropped = 0;
+                        text.push(c);
+                    }
+                    _ => {
+                        at_start_of_line = false;
+                        dropped = 0;
+                        text.push(c);
+                    }
+                }
+            } else {
+                text.push(c);
+                if c == '\n' {
+                    at_start_of_line = true;
+                }
+            }
+        }
+
+        if index == parts.len() - 1
+            && let Some(last_line_start) = text.rfind('\n')
+            && text[last_line_start + 1..].chars().all(|c| c == ' ')
+        {
+            text.truncate(last_line_start + 1);
+        }
+        result.push_str(&text);
+    }
+
+    result
+}
+
+fn unescape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        c => c,
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_value_start(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '"' | '\'' | '[' | '{' | '(')
+}
+
+// Paths contain a slash, e.g. `./a`, `/etc/hosts`, `~/a` or `<nixpkgs>`. Identifiers followed by a slash are paths as
+// well, but those are reported as variables.
+fn is_path_start(rest: &str) -> bool {
+    rest.starts_with("./")
+        || rest.starts_with("../")
+        || rest.starts_with("~/")
+        || (rest.starts_with('/') && !rest.starts_with("//") && !rest.starts_with("/*"))
+        || (rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(input: &str) -> Value {
+        parse_nix(input).unwrap()
+    }
+
+    fn parse_error(input: &str) -> (usize, usize, ParseErrorCategory, String) {
+        match parse_nix(input).unwrap_err() {
+            Json2NixError::Parse {
+                line,
+                column,
+                category,
+                message,
+            } => (line, column, category, message),
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(json!(null), parse("null"));
+        assert_eq!(json!(true), parse("true"));
+        assert_eq!(json!(false), parse(" false "));
+        assert_eq!(json!(42), parse("42"));
+        assert_eq!(json!(-42), parse("-42"));
+        assert_eq!(json!(-42), parse("(- 42)"));
+        assert_eq!(json!(1.5), parse("1.5"));
+        assert_eq!(json!(0.5), parse(".5"));
+        assert_eq!(json!(1e100), parse("1.0e100"));
+        assert_eq!(json!(-2.5e-3), parse("-2.5E-3"));
+        assert_eq!(json!(i64::MIN), parse("-9223372036854775808"));
+        assert_eq!(json!(i64::MIN), parse("-9223372036854775807 - 1"));
+        assert_eq!(json!([i64::MIN]), parse("[ (-9223372036854775807 - 1) ]"));
+        assert_eq!(json!({ "a": i64::MIN }), parse("{ a = -9223372036854775807 /* min */ - 1; }"));
+        assert_eq!(json!(7), parse("007"));
+        assert_eq!(json!(0.5), parse("0.5"));
+        assert_eq!(json!(10.0), parse("10."));
+    }
+
+    #[test]
+    fn only_the_smallest_integer_is_subtracted() {
+        for input in [
+            "-9223372036854775806 - 1",
+            "-9223372036854775807 - 2",
+            "-9223372036854775807 - 1.0",
+            "9223372036854775807 - 1",
+        ] {
+            assert_eq!("operators are not supported", parse_error(input).3, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn floats_with_leading_zeros() {
+        for input in ["00.5", "01.5", "0.", "00.5e3"] {
+            let (_, _, category, message) = parse_error(input);
+            assert_eq!((ParseErrorCategory::Syntax, format!("invalid float `{}`", input)), (category, message));
+        }
+    }
+
+    #[test]
+    fn numbers_out_of_range() {
+        assert_eq!(ParseErrorCategory::Data, parse_error("9223372036854775808").2);
+        assert_eq!(ParseErrorCategory::Data, parse_error("1.0e999").2);
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(json!("a\"b\\c\n\r\t$x"), parse(r#""a\"b\\c\n\r\t\$x""#));
+        assert_eq!(json!("${x} $${y} $"), parse(r#""\${x} $${y} $""#));
+        assert_eq!(json!("ü 🦀"), parse(r#""ü 🦀""#));
+    }
+
+    #[test]
+    fn indented_strings() {
+        assert_eq!(json!("a\nb\n"), parse("''\n    a\n    b\n  ''"));
+        assert_eq!(json!("a\n  b"), parse("''\n  a\n    b''"));
+        assert_eq!(json!("one line"), parse("''one line''"));
+        assert_eq!(json!("''${x}\t$"), parse("''\n  '''''${x}''\\t$''"));
+        assert_eq!(json!("\n\nx\n"), parse("''\n\n\n    x\n''"));
+        // escaped spaces are not indentation
+        assert_eq!(json!(" a\n  b\n"), parse("''\n  ''\\ a\n    b\n''"));
+        assert_eq!(json!("$${a}"), parse("''$${a}''"));
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(json!([]), parse("[ ]"));
+        assert_eq!(json!([1, -2, "a", [null], {}]), parse("[ 1 (-2) \"a\" [ null ] { } ]"));
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let nested = |open: &str, inner: &str, close: &str, depth: usize| format!("{}{}{}", open.repeat(depth), inner, close.repeat(depth));
+        assert_eq!(json!([[[{ "a": 1 }]]]), parse(&nested("[", "{ a = (1); }", "]", 3)));
+        assert!(parse_nix(&nested("[", "1", "]", 128)).is_ok());
+
+        let expected = (1, 129, ParseErrorCategory::Syntax, "recursion limit exceeded".to_string());
+        assert_eq!(expected, parse_error(&nested("[", "1", "]", 129)));
+        assert_eq!(expected, parse_error(&nested("(", "1", ")", 129)));
+        assert!(parse_nix(&format!("{{ {}a = 1; }}", "a.".repeat(127))).is_ok());
+        assert_eq!(
+            (1, 3, ParseErrorCategory::Syntax, "recursion limit exceeded".to_string()),
+            parse_error(&format!("{{ {}a = 1; }}", "a.".repeat(128)))
+        );
+        assert_eq!(
+            "recursion limit exceeded",
+            parse_error(&format!("{{ {}a = 1; }}", "a.".repeat(200_000))).3
+        );
+        assert_eq!("recursion limit exceeded", parse_error(&format!("{{ a.b = {}", "{ a = ".repeat(127))).3);
+        for open in ["[ ", "{ a = ", "("] {
+            assert_eq!("recursion limit exceeded", parse_error(&open.repeat(200_000)).3, "open: {}", open);
+        }
+    }
+
+    #[test]
+    fn attr_sets() {
+        let input = r#"{
+          a = 1;
+          "b c" = [ ];
+          d.e.f = true;
+          d.e.g = { };
+          or = null;
+          "if" = 2;
+        }"#;
+        assert_eq!(
+            json!({ "a": 1, "b c": [], "d": { "e": { "f": true, "g": {} } }, "or": null, "if": 2 }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn attr_sets_are_merged() {
+        assert_eq!(json!({ "a": { "b": 1, "c": 2 } }), parse("{ a = { b = 1; }; a.c = 2; }"));
+        assert_eq!(json!({ "a": { "c": 2, "b": 1 } }), parse("{ a.c = 2; a = { b = 1; }; }"));
+        assert_eq!(json!({ "a": { "b": 1, "c": 2 } }), parse("{ a = { b = 1; }; a = { c = 2; }; }"));
+    }
+
+    #[test]
+    fn duplicate_attributes() {
+        assert_eq!(
+            (1, 10, ParseErrorCategory::Data, "the attribute `a` is already defined".to_string()),
+            parse_error("{ a = 1; a = 2; }")
+        );
+        assert_eq!("the attribute `a.b` is already defined", parse_error("{ a.b = 1; a.b.c = 2; }").3);
+        assert_eq!(
+            "the attribute `a.b` is already defined",
+            parse_error("{ a = { b = 1; }; a = { b = 2; }; }").3
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let input = "# leading\n{ /* block */ a = 1; # trailing\n b = [ /**/ 2 ]; }\n# end";
+        assert_eq!(json!({ "a": 1, "b": [2] }), parse(input));
+    }
+
+    #[test]
+    fn attr_paths() {
+        assert_eq!(
+            vec!["programs", "oh-my-posh", "settings"],
+            parse_attr_path("programs.oh-my-posh.settings").unwrap()
+        );
+        assert_eq!(vec!["a.b", "c"], parse_attr_path(r#" "a.b" . c "#).unwrap());
+        assert!(matches!(
+            parse_attr_path("a.${b}"),
+            Err(Json2NixError::Parse {
+                category: ParseErrorCategory::Unsupported,
+                ..
+            })
+        ));
+        assert!(parse_attr_path("").is_err());
+        assert!(parse_attr_path("a.").is_err());
+        assert!(parse_attr_path("a b").is_err());
+    }
+
+    #[test]
+    fn unsupported() {
+        let cases = [
+            ("pkgs.hello", (1, 1, "variables are not supported, found `pkgs`")),
+            ("{ a = x: x; }", (1, 7, "variables are not supported, found `x`")),
+            ("{ pkgs, ... }: { }", (1, 7, "functions are not supported")),
+            ("{ }: { }", (1, 4, "functions are not supported")),
+            ("let a = 1; in a", (1, 1, "`let` expressions are not supported")),
+            ("rec { a = 1; }", (1, 1, "recursive attribute sets are not supported")),
+            ("{ inherit a; }", (1, 3, "`inherit` is not supported")),
+            ("{ a = 1 + 2; }", (1, 9, "operators are not supported")),
+            ("[ 1 ] ++ [ 2 ]", (1, 7, "operators are not supported")),
+            ("{ a = { }.b; }", (1, 10, "selecting attributes is not supported")),
+            ("{ a = \"${b}\"; }", (1, 8, "string interpolation is not supported")),
+            ("''\n  ${b}''", (2, 3, "string interpolation is not supported")),
+            ("{ ${a} = 1; }", (1, 3, "dynamic attributes are not supported")),
+            ("./a.nix", (1, 1, "paths are not supported, found `.`")),
+            ("[ <nixpkgs> ]", (1, 3, "paths are not supported, found `<`")),
+            ("-true", (1, 2, "only numbers can be negated")),
+        ];
+
+        for (input, (line, column, message)) in cases {
+            assert_eq!(
+                (line, column, ParseErrorCategory::Unsupported, message.to_string()),
+                parse_error(input),
+                "input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let cases = [
+            ("{ a = 1 }", (1, 9, ParseErrorCategory::Syntax, "expected `;`, found `}`")),
+            (
+                "[ -1 ]",
+                (
+                    1,
+                    3,
+                    ParseErrorCategory::Syntax,
+                    "negative numbers in lists must be wrapped in parentheses",
+                ),
+            ),
+            (
+                "{ if = 1; }",
+                (
+                    1,
+                    3,
+                    ParseErrorCategory::Syntax,
+                    "the keyword `if` must be quoted to be used as attribute name",
+                ),
+            ),
+            (
+                "{ __curPos = 1; }",
+                (1, 3, ParseErrorCategory::Syntax, "`__curPos` must be quoted to be used as attribute name"),
+            ),
+            ("{\n  a = [ 1", (2, 10, ParseErrorCategory::Eof, "expected a value")),
+            ("\"abc", (1, 1, ParseErrorCategory::Eof, "unterminated string")),
+            ("/* abc", (1, 1, ParseErrorCategory::Eof, "unterminated comment")),
+            ("", (1, 1, ParseErrorCategory::Eof, "expected a value")),
+        ];
+
+        for (input, (line, column, category, message)) in cases {
+            assert_eq!((line, column, category, message.to_string()), parse_error(input), "input: {}", input);
+        }
+    }
+}
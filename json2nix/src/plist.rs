@@ -0,0 +1,242 @@
+use std::io::Cursor;
+
+use ::plist::Value as PlistValue;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::{Map, Number, Value};
+
+use crate::{Json2NixError, ParseErrorCategory, json::drop_value};
+
+/// Parses an XML or binary property list. Dates become strings like `"2024-01-31T12:00:00Z"` and data blobs become base64
+/// strings. Plists of any depth are accepted.
+pub fn parse_plist(input: &[u8]) -> Result<Value, Json2NixError> {
+    let plist = PlistValue::from_reader(Cursor::new(input)).map_err(|err| error(ParseErrorCategory::Syntax, err.to_string()))?;
+    to_json(plist)
+}
+
+// The remaining elements of an array or dictionary that is converted.
+enum Elements {
+    Array(std::vec::IntoIter<PlistValue>),
+    Dictionary(::plist::dictionary::IntoIter),
+}
+
+// Converts `value` without recursion, which would overflow the stack for deeply nested plists.
+fn to_json(value: PlistValue) -> Result<Value, Json2NixError> {
+    // the arrays and dictionaries that are not complete yet, with the key of the current value of dictionaries
+    let mut containers: Vec<(Elements, Value, String)> = Vec::new();
+    let mut next = Some(value);
+
+    let result = loop {
+        let value = match next.take() {
+            Some(PlistValue::Array(array)) => {
+                containers.push((Elements::Array(array.into_iter()), Value::Array(Vec::new()), String::new()));
+                continue;
+            }
+            Some(PlistValue::Dictionary(dictionary)) => {
+                containers.push((Elements::Dictionary(dictionary.into_iter()), Value::Object(Map::new()), String::new()));
+                continue;
+            }
+            Some(value) => match scalar(value) {
+                Ok(value) => value,
+                Err(err) => break Err(err),
+            },
+            None => {
+                let (elements, _, key) = containers.last_mut().expect("only containers have more values");
+                next = match elements {
+                    Elements::Array(elements) => elements.next(),
+                    Elements::Dictionary(entries) => entries.next().map(|(next_key, value)| {
+                        *key = next_key;
+                        value
+                    }),
+                };
+                if next.is_some() {
+                    continue;
+                }
+                containers.pop().expect("the container is complete").1
+            }
+        };
+
+        match containers.last_mut() {
+            Some((_, Value::Array(elements), _)) => elements.push(value),
+            Some((_, Value::Object(object), key)) => {
+                object.insert(std::mem::take(key), value);
+            }
+            Some(_) => unreachable!("only arrays and dictionaries are containers"),
+            None => break Ok(value),
+        }
+    };
+
+    // the partially converted values are only left over if the plist contains a value that cannot be converted
+    for (elements, value, _) in containers {
+        drop_value(value);
+        match elements {
+            Elements::Array(elements) => elements.for_each(drop_plist),
+            Elements::Dictionary(entries) => entries.for_each(|(_, value)| drop_plist(value)),
+        }
+    }
+    result
+}
+
+fn scalar(value: PlistValue) -> Result<Value, Json2NixError> {
+    Ok(match value {
+        PlistValue::Boolean(bool) => Value::Bool(bool),
+        PlistValue::Data(data) => Value::String(STANDARD.encode(data)),
+        PlistValue::Date(date) => Value::String(date.to_xml_format()),
+        PlistValue::Real(real) => Value::Number(
+            Number::from_f64(real).ok_or_else(|| error(ParseErrorCategory::Data, format!("the real {} cannot be represented in Nix", real)))?,
+        ),
+        PlistValue::Integer(integer) => match integer.as_signed() {
+            Some(signed) => Value::from(signed),
+            None => Value::from(integer.as_unsigned().expect("a plist integer is signed or unsigned")),
+        },
+        PlistValue::String(string) => Value::String(string),
+        // UIDs only appear in archives of NSKeyedArchiver, where they reference other objects by index
+        PlistValue::Uid(uid) => Value::from(uid.get()),
+        value => {
+            return Err(error(
+                ParseErrorCategory::Unsupported,
+                format!("the plist value {:?} is not supported", value),
+            ));
+        }
+    })
+}
+
+// Drops `value` like `drop_value`, taking out only the nested arrays and dictionaries.
+fn drop_plist(value: PlistValue) {
+    let mut stack = vec![value];
+    while let Some(mut value) = stack.pop() {
+        let nested: &mut dyn Iterator<Item = &mut PlistValue> = match &mut value {
+            PlistValue::Array(elements) => &mut elements.iter_mut(),
+            PlistValue::Dictionary(dictionary) => &mut dictionary.values_mut(),
+            _ => continue,
+        };
+        stack.extend(
+            nested
+                .filter(|value| matches!(value, PlistValue::Array(_) | PlistValue::Dictionary(_)))
+                .map(|value| std::mem::replace(value, PlistValue::Boolean(false))),
+        );
+    }
+}
+
+// the plist crate does not expose the position of errors
+fn error(category: ParseErrorCategory, message: String) -> Json2NixError {
+    Json2NixError::Parse {
+        line: 0,
+        column: 0,
+        category,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSGlobalDomain</key>
+	<dict>
+		<key>AppleShowAllExtensions</key>
+		<true/>
+		<key>KeyRepeat</key>
+		<integer>2</integer>
+		<key>com.apple.sound.beep.volume</key>
+		<real>0.5</real>
+	</dict>
+	<key>persistent-apps</key>
+	<array>
+		<string>/Applications/Safari.app</string>
+		<string>/Applications/Ghostty.app</string>
+	</array>
+	<key>LastUpdate</key>
+	<date>2024-01-31T12:00:00Z</date>
+	<key>Token</key>
+	<data>AAEC/w==</data>
+	<key>Disabled</key>
+	<false/>
+</dict>
+</plist>
+"#;
+
+    fn expected() -> Value {
+        json!({
+            "NSGlobalDomain": { "AppleShowAllExtensions": true, "KeyRepeat": 2, "com.apple.sound.beep.volume": 0.5 },
+            "persistent-apps": ["/Applications/Safari.app", "/Applications/Ghostty.app"],
+            "LastUpdate": "2024-01-31T12:00:00Z",
+            "Token": "AAEC/w==",
+            "Disabled": false,
+        })
+    }
+
+    #[test]
+    fn xml() {
+        assert_eq!(expected(), parse_plist(XML.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn binary() {
+        let mut binary = Vec::new();
+        PlistValue::from_reader_xml(XML.as_bytes())
+            .unwrap()
+            .to_writer_binary(&mut binary)
+            .unwrap();
+        assert!(binary.starts_with(b"bplist00"));
+
+        assert_eq!(expected(), parse_plist(&binary).unwrap());
+    }
+
+    #[test]
+    fn large_integers() {
+        let input = "<plist><array><integer>-1</integer><integer>18446744073709551615</integer></array></plist>";
+        assert_eq!(json!([-1, 18446744073709551615u64]), parse_plist(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn syntax_error() {
+        let err = parse_plist(b"<plist><dict><key>a</key></plist>").unwrap_err();
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                category: ParseErrorCategory::Syntax,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let depth = 100_000;
+        let input = format!(
+            "<plist>{}<true/>{}</plist>",
+            "<array><dict><key>a</key>".repeat(depth),
+            "</dict></array>".repeat(depth)
+        );
+        let mut value = parse_plist(input.as_bytes()).unwrap();
+        let mut actual_depth = 0;
+        while let Some(nested) = value.get_mut(0).and_then(|dict| dict.get_mut("a")) {
+            value = nested.take();
+            actual_depth += 1;
+        }
+        assert_eq!((depth, Value::Bool(true)), (actual_depth, value));
+    }
+
+    #[test]
+    fn deeply_nested_error() {
+        let depth = 100_000;
+        let input = format!(
+            "<plist><array>{}<true/>{}<real>nan</real></array></plist>",
+            "<array><dict><key>a</key>".repeat(depth),
+            "</dict></array>".repeat(depth)
+        );
+        let err = parse_plist(input.as_bytes()).unwrap_err();
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                category: ParseErrorCategory::Data,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,696 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Write},
+};
+
+use crate::{
+    Json2NixConfig, OutputStyle,
+    ast::{Binding, NixExpr},
+    doc::{Doc, drop_doc, render, render_to},
+    escape::{KEYWORDS, escape_attribute_set_key, is_identifier},
+    number::{format_float, format_integer},
+    raw::is_delimited,
+    string::{escape_string, indented_string, interpolated_string},
+};
+
+/// Renders `expr` according to the layout options of `config`.
+pub fn print_nix_expr(expr: &NixExpr, config: &Json2NixConfig) -> String {
+    let mut output = String::new();
+    write_nix_expr(expr, config, &mut output).expect("writing to a string cannot fail");
+    output
+}
+
+/// Like [`print_nix_expr`], but writes into `output`, e.g. to reuse a buffer or to implement `Display`.
+pub fn write_nix_expr(expr: &NixExpr, config: &Json2NixConfig, output: &mut impl Write) -> fmt::Result {
+    let printer = Printer { config };
+    printer.render_to(printer.expr(expr, true), output)
+}
+
+/// Renders a single binding without the surrounding braces, e.g. to paste it into an existing attribute set.
+pub fn write_binding(binding: &Binding, config: &Json2NixConfig, output: &mut impl Write) -> fmt::Result {
+    let printer = Printer { config };
+    printer.render_to(printer.binding(binding), output)
+}
+
+/// Renders `expr` as an element of a list at `indentation` with every list and attribute set expanded, which is the
+/// layout of streamed output.
+pub(crate) fn print_expanded_list_element(expr: &NixExpr, config: &Json2NixConfig, indentation: usize) -> String {
+    let printer = Printer { config };
+    render(&printer.list_element(expr), indentation, None)
+}
+
+/// Like [`print_expanded_list_element`], but for a binding of an attribute set.
+pub(crate) fn print_expanded_binding(binding: &Binding, config: &Json2NixConfig, indentation: usize) -> String {
+    let printer = Printer { config };
+    render(&printer.binding(binding), indentation, None)
+}
+
+// Translates expressions into documents, which describe all possible layouts. Lists and attribute sets are groups, so they
+// are rendered on one line if they fit into the maximum line width and expanded to one element per line otherwise.
+struct Printer<'a> {
+    config: &'a Json2NixConfig,
+}
+
+impl Printer<'_> {
+    const NIXFMT_INDENTATION: usize = 2;
+    const NIXFMT_LINE_WIDTH: usize = 100;
+
+    fn render_to(&self, doc: Doc<'_>, output: &mut impl Write) -> fmt::Result {
+        let result = match self.config.style {
+            OutputStyle::Default => render_to(&doc, self.config.initial_indentation, self.config.max_line_width, output),
+            OutputStyle::Nixfmt => {
+                render_to(&doc, self.config.initial_indentation, Some(Self::NIXFMT_LINE_WIDTH), output).and_then(|()| output.write_char('\n'))
+            }
+        };
+        drop_doc(doc);
+        result
+    }
+
+    fn indentation_increment(&self) -> usize {
+        match self.config.style {
+            OutputStyle::Default => self.config.indentation_increment,
+            OutputStyle::Nixfmt => Self::NIXFMT_INDENTATION,
+        }
+    }
+
+    fn expr<'e>(&self, expr: &'e NixExpr, at_line_start: bool) -> Doc<'e> {
+        self.translate(Task::Expr(expr, at_line_start))
+    }
+
+    fn list_element<'e>(&self, element: &'e NixExpr) -> Doc<'e> {
+        self.translate(Task::ListElement(element))
+    }
+
+    fn binding<'e>(&self, binding: &'e Binding) -> Doc<'e> {
+        self.translate(Task::Binding(binding))
+    }
+
+    // Translates the expressions of the tasks into documents, which are combined once all parts of an expression are
+    // translated. The tasks are kept on a stack instead of the call stack, so expressions of any depth can be translated.
+    fn translate<'e>(&self, task: Task<'e>) -> Doc<'e> {
+        // leaves are translated directly, without any stacks
+        if let Task::Expr(expr, _) | Task::ListElement(expr) | Task::Argument(expr) = task
+            && (matches!(task, Task::Expr(..)) || !needs_parentheses(expr))
+            && let Some(doc) = self.leaf(expr)
+        {
+            return doc;
+        }
+
+        let mut tasks = vec![task];
+        let mut docs = Vec::new();
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Expr(expr, at_line_start) => self.translate_expr(expr, at_line_start, &mut docs, &mut tasks),
+                Task::ListElement(element) => match element {
+                    NixExpr::Commented { comments, expr } => {
+                        tasks.push(Task::Combine(Combine::Commented(comments, true), docs.len()));
+                        tasks.push(Task::ListElement(expr));
+                    }
+                    element if needs_parentheses(element) => {
+                        tasks.push(Task::Combine(Combine::Parenthesized, docs.len()));
+                        tasks.push(Task::Expr(element, false));
+                    }
+                    element => self.translate_expr(element, true, &mut docs, &mut tasks),
+                },
+                Task::Argument(argument) if needs_parentheses(argument) => {
+                    tasks.push(Task::Combine(Combine::Parenthesized, docs.len()));
+                    tasks.push(Task::Expr(argument, false));
+                }
+                Task::Argument(argument) => self.translate_expr(argument, false, &mut docs, &mut tasks),
+                Task::Binding(binding) => match self.leaf(&binding.value) {
+                    Some(value) => docs.push(self.combine_binding(binding, value)),
+                    None => {
+                        tasks.push(Task::Combine(Combine::Binding(binding), docs.len()));
+                        tasks.push(Task::Expr(&binding.value, false));
+                    }
+                },
+                Task::Combine(combine, start) => {
+                    let doc = self.combine(combine, docs.drain(start..));
+                    docs.push(doc);
+                }
+            }
+        }
+
+        docs.pop().expect("the expression is translated")
+    }
+
+    // Pushes the document of `expr` if it is a leaf, and the tasks for its parts otherwise.
+    fn translate_expr<'e>(&self, expr: &'e NixExpr, at_line_start: bool, docs: &mut Vec<Doc<'e>>, tasks: &mut Vec<Task<'e>>) {
+        match self.leaf(expr) {
+            Some(doc) => docs.push(doc),
+            None => self.push_parts(expr, at_line_start, docs.len(), tasks),
+        }
+    }
+
+    // Returns the document of an expression without nested expressions.
+    fn leaf<'e>(&self, expr: &'e NixExpr) -> Option<Doc<'e>> {
+        Some(match expr {
+            NixExpr::Null => Doc::text("null"),
+            NixExpr::Bool(bool) => Doc::text(if *bool { "true" } else { "false" }),
+            NixExpr::Int(integer) => Doc::text(format_integer(*integer)),
+            NixExpr::Float(float) => Doc::text(format_float(*float)),
+            NixExpr::String(string) if self.config.string_style.use_indented_string(string) => indented_string(string, self.indentation_increment()),
+            NixExpr::String(string) => Doc::text(escape_string(string)),
+            NixExpr::InterpolatedString(parts) => Doc::text(interpolated_string(parts)),
+            NixExpr::Path(path) => Doc::text(path),
+            NixExpr::Raw(raw) => Doc::text(raw),
+            NixExpr::List(elements) if elements.is_empty() => Doc::text("[ ]"),
+            NixExpr::AttrSet(bindings) if bindings.is_empty() => Doc::text("{ }"),
+            _ => return None,
+        })
+    }
+
+    // Pushes the tasks for the nested expressions of `expr` in reverse order, so they are translated in order, below the
+    // task that combines their documents from `start` on.
+    fn push_parts<'e>(&self, expr: &'e NixExpr, at_line_start: bool, start: usize, tasks: &mut Vec<Task<'e>>) {
+        match expr {
+            NixExpr::List(elements) => {
+                tasks.push(Task::Combine(Combine::List, start));
+                tasks.extend(elements.iter().rev().map(Task::ListElement));
+            }
+            NixExpr::AttrSet(bindings) => {
+                tasks.push(Task::Combine(Combine::AttrSet, start));
+                tasks.extend(bindings.iter().rev().map(Task::Binding));
+            }
+            NixExpr::Apply { function, arguments } => {
+                tasks.push(Task::Combine(Combine::Apply(function), start));
+                tasks.extend(arguments.iter().rev().map(Task::Argument));
+            }
+            NixExpr::Function { formals, body } => {
+                tasks.push(Task::Combine(Combine::Function(formals), start));
+                tasks.push(Task::Expr(body, true));
+            }
+            NixExpr::Let { bindings, body } => {
+                tasks.push(Task::Combine(Combine::Let, start));
+                tasks.push(Task::Expr(body, true));
+                tasks.extend(bindings.iter().rev().map(Task::Binding));
+            }
+            NixExpr::Commented { comments, expr } => {
+                tasks.push(Task::Combine(Combine::Commented(comments, at_line_start), start));
+                tasks.push(Task::Expr(expr, at_line_start));
+            }
+            _ => unreachable!("leaves are translated directly"),
+        }
+    }
+
+    fn combine<'e>(&self, combine: Combine<'e>, mut parts: impl DoubleEndedIterator<Item = Doc<'e>> + ExactSizeIterator) -> Doc<'e> {
+        match combine {
+            Combine::List => self.group("[", parts, "]"),
+            Combine::AttrSet => self.group("{", parts, "}"),
+            Combine::Apply(function) => {
+                let mut docs = vec![Doc::text(function)];
+                for argument in parts {
+                    docs.push(Doc::text(" "));
+                    docs.push(argument);
+                }
+                Doc::Concat(docs)
+            }
+            Combine::Function(formals) => self.function(formals, parts.next_back().expect("the body is translated")),
+            Combine::Let => {
+                let body = parts.next_back().expect("the body is translated");
+                let mut content = Vec::new();
+                for binding in parts {
+                    content.push(Doc::HardLine);
+                    content.push(binding);
+                }
+                Doc::Concat(vec![
+                    Doc::text("let"),
+                    Doc::nest(self.indentation_increment(), Doc::Concat(content)),
+                    Doc::HardLine,
+                    Doc::text("in"),
+                    Doc::HardLine,
+                    body,
+                ])
+            }
+            Combine::Commented(comments, at_line_start) => Doc::Concat(vec![
+                comments_doc(comments, at_line_start),
+                parts.next_back().expect("the expression is translated"),
+            ]),
+            Combine::Parenthesized => Doc::Concat(vec![
+                Doc::text("("),
+                parts.next_back().expect("the expression is translated"),
+                Doc::text(")"),
+            ]),
+            Combine::Binding(binding) => self.combine_binding(binding, parts.next_back().expect("the value is translated")),
+        }
+    }
+
+    fn function<'e>(&self, formals: &'e [String], body: Doc<'e>) -> Doc<'e> {
+        let formals = formals.iter().map(String::as_str).chain(["..."]);
+
+        match self.config.style {
+            OutputStyle::Default => Doc::Concat(vec![
+                Doc::text(format!("{{ {} }}:", formals.collect::<Vec<_>>().join(", "))),
+                Doc::HardLine,
+                Doc::HardLine,
+                body,
+            ]),
+            // nixfmt puts every formal on its own line and the body directly below the pattern
+            OutputStyle::Nixfmt => {
+                let formals = formals.collect::<Vec<_>>();
+                let mut content = Vec::new();
+                for (index, formal) in formals.iter().enumerate() {
+                    content.push(Doc::HardLine);
+                    content.push(Doc::text(if index + 1 < formals.len() {
+                        format!("{},", formal)
+                    } else {
+                        formal.to_string()
+                    }));
+                }
+                Doc::Concat(vec![
+                    Doc::text("{"),
+                    Doc::nest(self.indentation_increment(), Doc::Concat(content)),
+                    Doc::HardLine,
+                    Doc::text("}:"),
+                    Doc::HardLine,
+                    body,
+                ])
+            }
+        }
+    }
+
+    fn group<'e>(&self, open: &'static str, elements: impl ExactSizeIterator<Item = Doc<'e>>, close: &'static str) -> Doc<'e> {
+        // nixfmt always expands lists and attribute sets with more than one element
+        let line = if self.config.style == OutputStyle::Nixfmt && elements.len() > 1 {
+            Doc::HardLine
+        } else {
+            Doc::Line
+        };
+
+        let mut content = Vec::new();
+        for element in elements {
+            content.push(line.clone());
+            content.push(element);
+        }
+
+        Doc::group(Doc::Concat(vec![
+            Doc::text(open),
+            Doc::nest(self.indentation_increment(), Doc::Concat(content)),
+            line,
+            Doc::text(close),
+        ]))
+    }
+
+    fn combine_binding<'e>(&self, binding: &'e Binding, value: Doc<'e>) -> Doc<'e> {
+        let path = match binding.path.as_slice() {
+            [key] => escape_attribute_set_key(key),
+            path => Cow::Owned(path.iter().map(|key| escape_attribute_set_key(key)).collect::<Vec<_>>().join(".")),
+        };
+
+        // nixfmt moves values that do not fit onto the line of the attribute to the next line, unless they start with
+        // an opening bracket or quotes that can stay on the line of the attribute
+        if self.config.style == OutputStyle::Nixfmt && !self.is_absorbable(&binding.value) {
+            return Doc::Concat(vec![
+                comments_doc(&binding.comments, true),
+                Doc::text(path),
+                Doc::text(" ="),
+                Doc::group(Doc::nest(self.indentation_increment(), Doc::Concat(vec![Doc::Line, value]))),
+                Doc::text(";"),
+            ]);
+        }
+
+        Doc::Concat(vec![
+            comments_doc(&binding.comments, true),
+            Doc::text(path),
+            Doc::text(" = "),
+            value,
+            Doc::text(";"),
+        ])
+    }
+
+    fn is_absorbable(&self, expr: &NixExpr) -> bool {
+        match expr {
+            NixExpr::List(elements) => !elements.is_empty(),
+            NixExpr::AttrSet(bindings) => !bindings.is_empty(),
+            NixExpr::String(string) => self.config.string_style.use_indented_string(string) && string.contains('\n'),
+            // nixfmt keeps applications on the line of the attribute if their last argument can stay there
+            NixExpr::Apply { arguments, .. } => arguments.last().is_some_and(|argument| self.is_absorbable(argument)),
+            _ => false,
+        }
+    }
+}
+
+/// A step of the translation of an expression into a document.
+enum Task<'e> {
+    /// Translates an expression, which starts at the beginning of a line if the flag is set.
+    Expr(&'e NixExpr, bool),
+    ListElement(&'e NixExpr),
+    Argument(&'e NixExpr),
+    Binding(&'e Binding),
+    /// Combines the documents from the given index on into the document of an expression.
+    Combine(Combine<'e>, usize),
+}
+
+/// How the documents of the parts of an expression are combined.
+enum Combine<'e> {
+    /// The documents are the elements.
+    List,
+    /// The documents are the bindings.
+    AttrSet,
+    /// The documents are the arguments of the function.
+    Apply(&'e str),
+    /// The document is the body.
+    Function(&'e [String]),
+    /// The documents are the bindings followed by the body.
+    Let,
+    /// The document is the expression after the comments, which is at the beginning of a line if the flag is set.
+    Commented(&'e [String], bool),
+    Parenthesized,
+    /// The document is the value of the binding.
+    Binding(&'e Binding),
+}
+
+// Negative numbers are negations, which are only allowed in lists and as arguments when wrapped in parentheses, just like
+// applications, functions and `let` expressions.
+fn needs_parentheses(expr: &NixExpr) -> bool {
+    match expr {
+        NixExpr::Int(integer) => *integer < 0,
+        NixExpr::Float(float) => *float < 0.0,
+        NixExpr::Apply { .. } | NixExpr::Function { .. } | NixExpr::Let { .. } => true,
+        NixExpr::Raw(raw) => !is_simple_raw(raw),
+        _ => false,
+    }
+}
+
+// Raw expressions like `a && b` or `1-2` would be split into multiple list elements or arguments, so only selections like
+// `pkgs.hello`, numbers, paths like `./a.png` and expressions in brackets or quotes are not parenthesized.
+fn is_simple_raw(raw: &str) -> bool {
+    is_selection(raw) || is_number(raw) || is_path(raw) || is_delimited(raw)
+}
+
+// Identifiers may contain `-`, so `pkgs.foo-bar` is a single selection.
+fn is_selection(raw: &str) -> bool {
+    let mut attributes = raw.split('.');
+    let first = attributes.next().expect("split returns at least one part");
+    is_identifier(first) && !KEYWORDS.contains(&first) && attributes.all(is_identifier)
+}
+
+// An integer or a float according to the Nix lexer: `[0-9]+` or `(([1-9][0-9]*\.[0-9]*)|(0?\.[0-9]+))([Ee][+-]?[0-9]+)?`
+fn is_number(raw: &str) -> bool {
+    let is_digits = |digits: &str| !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit());
+    let Some((integer, fraction)) = raw.split_once('.') else {
+        return is_digits(raw);
+    };
+    let (fraction, exponent) = fraction.split_once(['e', 'E']).unwrap_or((fraction, "0"));
+
+    let mantissa = match integer {
+        "" | "0" => is_digits(fraction),
+        _ => is_digits(integer) && !integer.starts_with('0') && (fraction.is_empty() || is_digits(fraction)),
+    };
+    mantissa && is_digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent))
+}
+
+// A path according to the Nix lexer, e.g. `./a.png`, `/etc/hosts`, `~/a` or `<nixpkgs>`. Paths must not end with `/`.
+fn is_path(raw: &str) -> bool {
+    let is_path_char = |c: char| c.is_ascii_alphanumeric() || "._-+".contains(c);
+    let is_segment = |segment: &str| !segment.is_empty() && segment.chars().all(is_path_char);
+
+    if let Some(search_path) = raw.strip_prefix('<').and_then(|raw| raw.strip_suffix('>')) {
+        return search_path.split('/').all(is_segment);
+    }
+    match raw.split_once('/') {
+        Some((first, rest)) => (first == "~" || first.chars().all(is_path_char)) && rest.split('/').all(is_segment),
+        None => false,
+    }
+}
+
+// Comments at the start of a line are line comments, otherwise they are block comments in front of the expression.
+fn comments_doc(comments: &[String], at_line_start: bool) -> Doc<'static> {
+    let mut docs = Vec::new();
+
+    for comment in comments {
+        if at_line_start {
+            for line in comment.lines() {
+                docs.push(Doc::text(if line.is_empty() { "#".to_string() } else { format!("# {}", line) }));
+                docs.push(Doc::HardLine);
+            }
+        } else {
+            docs.push(Doc::text(format!("/* {} */ ", comment.replace("*/", "* /"))));
+        }
+    }
+
+    Doc::Concat(docs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StringStyle;
+
+    fn print(expr: &NixExpr) -> String {
+        print_nix_expr(expr, &Json2NixConfig::new(0, 2, false))
+    }
+
+    fn binding(key: &str, value: NixExpr) -> Binding {
+        Binding::new(vec![key.to_string()], value)
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!("null", print(&NixExpr::Null));
+        assert_eq!("true", print(&NixExpr::Bool(true)));
+        assert_eq!("-1", print(&NixExpr::Int(-1)));
+        assert_eq!("1.0e100", print(&NixExpr::Float(1e100)));
+        assert_eq!(r#""a\"b""#, print(&NixExpr::String("a\"b".to_string())));
+        assert_eq!("./wallpaper.png", print(&NixExpr::Path("./wallpaper.png".to_string())));
+        assert_eq!("pkgs.hello", print(&NixExpr::Raw("pkgs.hello".to_string())));
+    }
+
+    #[test]
+    fn list() {
+        let expr = NixExpr::List(vec![
+            NixExpr::Int(1),
+            NixExpr::Int(-1),
+            NixExpr::Float(-0.5),
+            NixExpr::Float(-0.0),
+            NixExpr::List(vec![]),
+            NixExpr::Raw("pkgs.hello".to_string()),
+        ]);
+        let expected = "[\n  1\n  (-1)\n  (-0.5)\n  0.0\n  [ ]\n  pkgs.hello\n]";
+        assert_eq!(expected, print(&expr));
+    }
+
+    #[test]
+    fn raw_in_list() {
+        let simple = [
+            "pkgs.foo-bar",
+            "a-b",
+            "42",
+            "1.5e-3",
+            ".5",
+            "./a-b.png",
+            "/etc/hosts",
+            "~/a",
+            "<nixpkgs/lib>",
+            "[ 1 2 ]",
+            "\"a-b\"",
+            "(a - b)",
+        ];
+        let parenthesized = [
+            "1-2",
+            "a - b",
+            "-1",
+            "1e-3",
+            "0.",
+            "a.b-1.5",
+            "x.\"y\"",
+            "rec { }",
+            "a/",
+            "a//b",
+            "f x",
+            "(a) (b)",
+            "\"a\"-b",
+            "import ./a.nix",
+        ];
+        for raw in simple {
+            let expected = format!("[\n  {}\n]", raw);
+            assert_eq!(expected, print(&NixExpr::List(vec![NixExpr::Raw(raw.to_string())])), "raw: {}", raw);
+        }
+        for raw in parenthesized {
+            let expected = format!("[\n  ({})\n]", raw);
+            assert_eq!(expected, print(&NixExpr::List(vec![NixExpr::Raw(raw.to_string())])), "raw: {}", raw);
+        }
+    }
+
+    #[test]
+    fn attr_set() {
+        let expr = NixExpr::AttrSet(vec![
+            Binding::new(vec!["a".to_string(), "b c".to_string()], NixExpr::Int(1)),
+            binding("list", NixExpr::List(vec![NixExpr::AttrSet(vec![])])),
+        ]);
+        let expected = "{\n  a.\"b c\" = 1;\n  list = [\n    { }\n  ];\n}";
+        assert_eq!(expected, print(&expr));
+    }
+
+    #[test]
+    fn single_binding() {
+        let binding = binding("a", NixExpr::AttrSet(vec![Binding::new(vec!["b".to_string()], NixExpr::Null)]));
+        let mut output = String::new();
+        write_binding(&binding, &Json2NixConfig::new(0, 2, false), &mut output).unwrap();
+        assert_eq!("a = {\n  b = null;\n};", output);
+    }
+
+    #[test]
+    fn comments() {
+        let expr = NixExpr::Commented {
+            comments: vec!["the settings".to_string()],
+            expr: Box::new(NixExpr::AttrSet(vec![
+                Binding {
+                    comments: vec!["first line\n\nthird line".to_string()],
+                    ..binding("a", NixExpr::Int(1))
+                },
+                binding(
+                    "b",
+                    NixExpr::List(vec![NixExpr::Commented {
+                        comments: vec!["negative".to_string()],
+                        expr: Box::new(NixExpr::Int(-1)),
+                    }]),
+                ),
+                binding(
+                    "c",
+                    NixExpr::Commented {
+                        comments: vec!["inline */".to_string()],
+                        expr: Box::new(NixExpr::Null),
+                    },
+                ),
+            ])),
+        };
+        let expected = "# the settings\n{\n  # first line\n  #\n  # third line\n  a = 1;\n  b = [\n    # negative\n    (-1)\n  ];\n  c = /* inline * / */ null;\n}";
+        assert_eq!(expected, print(&expr));
+    }
+
+    #[test]
+    fn apply() {
+        let apply = |function: &str, arguments: Vec<NixExpr>| NixExpr::Apply {
+            function: function.to_string(),
+            arguments,
+        };
+        let expr = NixExpr::List(vec![
+            apply("lib.mkOverride", vec![NixExpr::Int(50), NixExpr::Int(-1)]),
+            apply(
+                "lib.mkIf",
+                vec![NixExpr::Raw("a && b".to_string()), apply("f", vec![NixExpr::Raw("x".to_string())])],
+            ),
+        ]);
+        let expected = "[\n  (lib.mkOverride 50 (-1))\n  (lib.mkIf (a && b) (f x))\n]";
+        assert_eq!(expected, print(&expr));
+    }
+
+    #[test]
+    fn function() {
+        let expr = NixExpr::Function {
+            formals: vec!["config".to_string(), "lib".to_string()],
+            body: Box::new(NixExpr::AttrSet(vec![binding("a", NixExpr::Int(1))])),
+        };
+        assert_eq!("{ config, lib, ... }:\n\n{\n  a = 1;\n}", print(&expr));
+
+        let config = Json2NixConfig {
+            style: OutputStyle::Nixfmt,
+            ..Json2NixConfig::default()
+        };
+        assert_eq!("{\n  config,\n  lib,\n  ...\n}:\n{ a = 1; }\n", print_nix_expr(&expr, &config));
+    }
+
+    #[test]
+    fn let_in() {
+        let expr = NixExpr::Let {
+            bindings: vec![binding("a", NixExpr::List(vec![NixExpr::Int(1)]))],
+            body: Box::new(NixExpr::List(vec![NixExpr::Raw("a".to_string()), NixExpr::Raw("a".to_string())])),
+        };
+        assert_eq!("let\n  a = [\n    1\n  ];\nin\n[\n  a\n  a\n]", print(&expr));
+
+        let config = Json2NixConfig {
+            style: OutputStyle::Nixfmt,
+            ..Json2NixConfig::default()
+        };
+        assert_eq!("let\n  a = [ 1 ];\nin\n[\n  a\n  a\n]\n", print_nix_expr(&expr, &config));
+    }
+
+    #[test]
+    fn write_into_buffer() {
+        let mut output = "x = ".to_string();
+        write_nix_expr(&NixExpr::List(vec![NixExpr::Null]), &Json2NixConfig::default(), &mut output).unwrap();
+        assert_eq!("x = [\n  null\n]", output);
+    }
+
+    #[test]
+    fn initial_indentation() {
+        let expr = NixExpr::List(vec![NixExpr::Null]);
+        assert_eq!("    [\n      null\n    ]", print_nix_expr(&expr, &Json2NixConfig::new(4, 2, false)));
+    }
+
+    #[test]
+    fn indented_string() {
+        let config = Json2NixConfig {
+            string_style: StringStyle::Indented {
+                double_quote_threshold: None,
+            },
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let expr = NixExpr::AttrSet(vec![binding("text", NixExpr::String("a\nb\n".to_string()))]);
+        assert_eq!("{\n  text = ''\n    a\n    b\n  '';\n}", print_nix_expr(&expr, &config));
+    }
+
+    #[test]
+    fn max_line_width() {
+        let config = Json2NixConfig {
+            max_line_width: Some(30),
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let expr = NixExpr::AttrSet(vec![
+            binding("list", NixExpr::List(vec![NixExpr::Int(1), NixExpr::Int(-2), NixExpr::Int(3)])),
+            binding(
+                "set",
+                NixExpr::AttrSet(vec![binding("a", NixExpr::Int(1)), binding("b", NixExpr::AttrSet(vec![]))]),
+            ),
+            binding(
+                "long",
+                NixExpr::List(vec![
+                    NixExpr::String("a long string".to_string()),
+                    NixExpr::String("another long string".to_string()),
+                ]),
+            ),
+        ]);
+        let expected = r#"{
+  list = [ 1 (-2) 3 ];
+  set = { a = 1; b = { }; };
+  long = [
+    "a long string"
+    "another long string"
+  ];
+}"#;
+        assert_eq!(expected, print_nix_expr(&expr, &config));
+    }
+
+    #[test]
+    fn max_line_width_whole_expression() {
+        let config = Json2NixConfig {
+            max_line_width: Some(80),
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let expr = NixExpr::AttrSet(vec![binding("a", NixExpr::List(vec![NixExpr::Null]))]);
+        assert_eq!("{ a = [ null ]; }", print_nix_expr(&expr, &config));
+    }
+
+    #[test]
+    fn max_line_width_forced_line_breaks() {
+        let config = Json2NixConfig {
+            max_line_width: Some(80),
+            string_style: StringStyle::Indented {
+                double_quote_threshold: None,
+            },
+            ..Json2NixConfig::new(0, 2, false)
+        };
+        let expr = NixExpr::List(vec![
+            NixExpr::Commented {
+                comments: vec!["comment".to_string()],
+                expr: Box::new(NixExpr::Int(1)),
+            },
+            NixExpr::List(vec![NixExpr::String("a\nb".to_string())]),
+            NixExpr::List(vec![NixExpr::Int(2)]),
+        ]);
+        let expected = "[\n  # comment\n  1\n  [\n    ''\n      a\n      b''\n  ]\n  [ 2 ]\n]";
+        assert_eq!(expected, print_nix_expr(&expr, &config));
+    }
+}
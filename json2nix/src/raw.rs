@@ -0,0 +1,183 @@
+/// The key of objects that contain a raw Nix expression, e.g. `{ "$nix": "pkgs.hello" }`.
+pub const RAW_NIX_KEY: &str = "$nix";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    Parenthesis,
+    Bracket,
+    Brace,
+    Interpolation,
+    String,
+    IndentedString,
+}
+
+impl Frame {
+    fn description(self) -> &'static str {
+        match self {
+            Frame::Parenthesis => "`(`",
+            Frame::Bracket => "`[`",
+            Frame::Brace => "`{`",
+            Frame::Interpolation => "`${`",
+            Frame::String => "string",
+            Frame::IndentedString => "indented string",
+        }
+    }
+}
+
+/// Checks that a raw Nix expression is lexically balanced: brackets, strings, interpolations and comments have to be
+/// closed. This does not check whether the expression is valid, but it ensures that it cannot break the surrounding code.
+pub fn check_balanced(raw: &str) -> Result<(), String> {
+    if raw.trim().is_empty() {
+        return Err("the expression is empty".to_string());
+    }
+    scan(raw).map(|_| ())
+}
+
+/// Returns whether the balanced `raw` is a single expression in brackets, braces, parentheses or quotes, like `[ 1 2 ]` or
+/// `"a ${b}"`, which never has to be parenthesized.
+pub fn is_delimited(raw: &str) -> bool {
+    (raw.starts_with(['(', '[', '{', '"']) || raw.starts_with("''")) && scan(raw) == Ok(raw.len())
+}
+
+// Returns the length of the first token of `raw`, where everything between brackets or quotes counts as one token.
+fn scan(raw: &str) -> Result<usize, String> {
+    let mut stack = Vec::new();
+    let mut rest = raw;
+    let mut first_length = None;
+
+    while let Some(c) = rest.chars().next() {
+        let length = match stack.last() {
+            Some(Frame::String) => match c {
+                '\\' => 1 + rest[1..].chars().next().map_or(0, char::len_utf8),
+                '$' if rest.starts_with("$$") => 2,
+                '$' if rest.starts_with("${") => {
+                    stack.push(Frame::Interpolation);
+                    2
+                }
+                '"' => {
+                    stack.pop();
+                    1
+                }
+                c => c.len_utf8(),
+            },
+            Some(Frame::IndentedString) => {
+                if rest.starts_with("'''") || rest.starts_with("''$") {
+                    3
+                } else if let Some(escaped) = rest.strip_prefix("''\\") {
+                    3 + escaped.chars().next().map_or(0, char::len_utf8)
+                } else if rest.starts_with("''") {
+                    stack.pop();
+                    2
+                } else if rest.starts_with("$$") {
+                    2
+                } else if rest.starts_with("${") {
+                    stack.push(Frame::Interpolation);
+                    2
+                } else {
+                    c.len_utf8()
+                }
+            }
+            _ => match c {
+                '#' => rest.find('\n').unwrap_or(rest.len()),
+                '/' if rest.starts_with("/*") => match rest[2..].find("*/") {
+                    Some(end) => end + 4,
+                    None => return Err("unterminated comment".to_string()),
+                },
+                '"' => {
+                    stack.push(Frame::String);
+                    1
+                }
+                '\'' if rest.starts_with("''") => {
+                    stack.push(Frame::IndentedString);
+                    2
+                }
+                '$' if rest.starts_with("${") => {
+                    stack.push(Frame::Interpolation);
+                    2
+                }
+                '(' | '[' | '{' => {
+                    stack.push(match c {
+                        '(' => Frame::Parenthesis,
+                        '[' => Frame::Bracket,
+                        _ => Frame::Brace,
+                    });
+                    1
+                }
+                ')' | ']' | '}' => {
+                    match (stack.pop(), c) {
+                        (Some(Frame::Parenthesis), ')') | (Some(Frame::Bracket), ']') | (Some(Frame::Brace | Frame::Interpolation), '}') => {}
+                        (Some(frame), _) => return Err(format!("unexpected `{}`, {} is not closed", c, frame.description())),
+                        (None, _) => return Err(format!("unexpected `{}`", c)),
+                    }
+                    1
+                }
+                c => c.len_utf8(),
+            },
+        };
+        rest = &rest[length..];
+        if stack.is_empty() && first_length.is_none() {
+            first_length = Some(raw.len() - rest.len());
+        }
+    }
+
+    match stack.last() {
+        None => Ok(first_length.unwrap_or(0)),
+        Some(frame) => Err(format!("{} is not closed", frame.description())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn balanced() {
+        let cases = [
+            "pkgs.hello",
+            "./wallpaper.png",
+            r#"config.home.homeDirectory + "/x""#,
+            r#"builtins.toJSON { a = [ 1 (2) ]; }"#,
+            r#""${pkgs.hello}/bin/hello \"${x}\" }""#,
+            r#""$${not an interpolation""#,
+            "''\n  echo ''${HOME} ''' ''\\n ${pkgs.hello}\n''",
+            "x: x # unbalanced ( in a comment",
+            "/* ] */ 1",
+            "{ ${name} = 1; }",
+        ];
+
+        for case in cases {
+            assert_eq!(Ok(()), check_balanced(case), "raw: {}", case);
+        }
+    }
+
+    #[test]
+    fn unbalanced() {
+        let cases = [
+            ("", "the expression is empty"),
+            (" \n", "the expression is empty"),
+            ("(1", "`(` is not closed"),
+            ("[ 1 ) ]", "unexpected `)`, `[` is not closed"),
+            ("1 }", "unexpected `}`"),
+            (r#""abc"#, "string is not closed"),
+            (r#""\""#, "string is not closed"),
+            ("''a'''", "indented string is not closed"),
+            (r#""${a""#, "string is not closed"),
+            (r#""${a"#, "`${` is not closed"),
+            ("/* a", "unterminated comment"),
+        ];
+
+        for (raw, expected) in cases {
+            assert_eq!(Err(expected.to_string()), check_balanced(raw), "raw: {}", raw);
+        }
+    }
+
+    #[test]
+    fn delimited() {
+        for raw in ["(a b)", "[ 1 (2) ]", "{ a = 1; }", r#""a ${b} )""#, "''a ''${b}''", "(a /* ) */)"] {
+            assert!(is_delimited(raw), "raw: {}", raw);
+        }
+        for raw in ["(a) (b)", "[ ] ++ [ ]", "{ a }: a", r#""a" + "b""#, "f (x)", "(a", "-(1)", "a"] {
+            assert!(!is_delimited(raw), "raw: {}", raw);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{
+    ast::{NixExpr, StringPart},
+    escape::escape_attribute_set_key,
+    string::interpolated_string,
+};
+
+/// Rewrites strings that contain machine-specific paths into Nix expressions. Every rewrite is reported with a
+/// [`crate::Warning::RewrittenString`], so it can be reviewed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StringRewrite {
+    /// Replaces this home directory (e.g. `/home/alice`) with `${config.home.homeDirectory}`, and `~` as long as it is a
+    /// path of its own like in `~/bin` or `PATH=~/bin:~/.local/bin`.
+    HomeDirectory(String),
+    /// Converts strings that are relative paths like `./foo` or `../foo` to path literals.
+    RelativePaths,
+    /// Replaces store paths like `/nix/store/<hash>-hello-2.12/bin/hello` with `${pkgs.hello}/bin/hello`. The attribute
+    /// is guessed from the name of the store path without its version.
+    StorePaths,
+}
+
+const HOME_DIRECTORY: &str = "config.home.homeDirectory";
+const STORE_DIRECTORY: &str = "/nix/store/";
+const NIX_BASE32: &str = "0123456789abcdfghijklmnpqrsvwxyz";
+const HASH_LENGTH: usize = 32;
+
+/// Applies `rewrites` to `value` and returns the resulting expression, or `None` if nothing was rewritten.
+pub fn rewrite_string(value: &str, rewrites: &[StringRewrite]) -> Option<NixExpr> {
+    if rewrites.contains(&StringRewrite::RelativePaths) && is_relative_path(value) {
+        return Some(NixExpr::Path(value.to_string()));
+    }
+
+    let home_directory = rewrites.iter().find_map(|rewrite| match rewrite {
+        StringRewrite::HomeDirectory(home_directory) => Some(home_directory.trim_end_matches('/')),
+        _ => None,
+    });
+    let store_paths = rewrites.contains(&StringRewrite::StorePaths);
+
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = value;
+    let mut previous = None;
+
+    while let Some(c) = rest.chars().next() {
+        let replacement = match previous.is_none_or(|previous| !is_path_char(previous)) {
+            true => store_paths
+                .then(|| store_path(rest))
+                .flatten()
+                .or_else(|| home_directory.and_then(|home_directory| home(rest, home_directory, previous))),
+            false => None,
+        };
+
+        match replacement {
+            Some((length, interpolation)) => {
+                if !literal.is_empty() {
+                    parts.push(StringPart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(StringPart::Interpolation(interpolation));
+                previous = rest[..length].chars().next_back();
+                rest = &rest[length..];
+            }
+            None => {
+                literal.push(c);
+                previous = Some(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    if parts.is_empty() {
+        return None;
+    }
+    if !literal.is_empty() {
+        parts.push(StringPart::Literal(literal));
+    }
+    Some(NixExpr::InterpolatedString(parts))
+}
+
+/// Renders the result of a rewrite for reports.
+pub fn describe(expr: &NixExpr) -> String {
+    match expr {
+        NixExpr::Path(path) => path.clone(),
+        NixExpr::InterpolatedString(parts) => interpolated_string(parts),
+        expr => format!("{:?}", expr),
+    }
+}
+
+// path literals must not end with a slash and may only contain these characters
+fn is_relative_path(value: &str) -> bool {
+    (value.starts_with("./") || value.starts_with("../"))
+        && !value.ends_with('/')
+        && !value.contains("//")
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "._-+/".contains(c))
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._-+/~".contains(c)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._-+".contains(c)
+}
+
+// the home directory followed by the end of the path or a `/`, or `~` as a whole path or in front of a `/`
+fn home(rest: &str, home_directory: &str, previous: Option<char>) -> Option<(usize, String)> {
+    let is_home_directory = !home_directory.is_empty() && rest.strip_prefix(home_directory).is_some_and(|after| !after.starts_with(is_name_char));
+    if is_home_directory {
+        return Some((home_directory.len(), HOME_DIRECTORY.to_string()));
+    }
+
+    // prose like `approx ~ 5 min` is left alone, so `~` has to start the string or follow a separator of paths
+    let is_tilde = previous.is_none_or(|previous| previous.is_whitespace() || ":=,;'\"".contains(previous))
+        && rest.strip_prefix('~').is_some_and(|after| after.is_empty() || after.starts_with('/'));
+    is_tilde.then(|| (1, HOME_DIRECTORY.to_string()))
+}
+
+fn store_path(rest: &str) -> Option<(usize, String)> {
+    let after_store = rest.strip_prefix(STORE_DIRECTORY)?;
+    let hash = after_store.get(..HASH_LENGTH)?;
+    if !hash.chars().all(|c| NIX_BASE32.contains(c)) {
+        return None;
+    }
+
+    let name = after_store[HASH_LENGTH..].strip_prefix('-')?;
+    let name = &name[..name.find(|c| !is_name_char(c)).unwrap_or(name.len())];
+    if name.is_empty() {
+        return None;
+    }
+
+    let length = STORE_DIRECTORY.len() + HASH_LENGTH + 1 + name.len();
+    Some((length, format!("pkgs.{}", escape_attribute_set_key(package_name(name)))))
+}
+
+// like `builtins.parseDrvName`: the version starts at the first dash that is followed by a character other than a letter
+fn package_name(name: &str) -> &str {
+    name.char_indices()
+        .find(|(index, c)| *c == '-' && name[index + 1..].chars().next().is_some_and(|next| !next.is_ascii_alphabetic()))
+        .map_or(name, |(index, _)| &name[..index])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HASH: &str = "0c0nv8ahhmsy1q5r5lhk10hdrqa5z1p7";
+
+    fn rewrite(value: &str) -> Option<String> {
+        let rewrites = [
+            StringRewrite::HomeDirectory("/home/alice/".to_string()),
+            StringRewrite::RelativePaths,
+            StringRewrite::StorePaths,
+        ];
+        rewrite_string(value, &rewrites).map(|expr| describe(&expr))
+    }
+
+    #[test]
+    fn home_directory() {
+        let cases = [
+            ("/home/alice", r#""${config.home.homeDirectory}""#),
+            ("/home/alice/.config/nvim", r#""${config.home.homeDirectory}/.config/nvim""#),
+            ("~/bin", r#""${config.home.homeDirectory}/bin""#),
+            ("~", r#""${config.home.homeDirectory}""#),
+            (
+                "PATH=~/bin:/home/alice/.local/bin",
+                r#""PATH=${config.home.homeDirectory}/bin:${config.home.homeDirectory}/.local/bin""#,
+            ),
+            ("--dir /home/alice/${x}", r#""--dir ${config.home.homeDirectory}/\${x}""#),
+            ("cd ~", r#""cd ${config.home.homeDirectory}""#),
+            ("a=~/x,~/y", r#""a=${config.home.homeDirectory}/x,${config.home.homeDirectory}/y""#),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(Some(expected.to_string()), rewrite(input), "input: {}", input);
+        }
+
+        for input in [
+            "/home/alice2",
+            "/home/bob",
+            "~bob",
+            "a~/b",
+            "/srv/home/alice",
+            "~.txt",
+            "approx ~ 5 min",
+            "~-",
+            "(~/x)",
+        ] {
+            assert_eq!(None, rewrite(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(Some("./wallpaper.png".to_string()), rewrite("./wallpaper.png"));
+        assert_eq!(Some("../a/b-c+d_e".to_string()), rewrite("../a/b-c+d_e"));
+
+        for input in ["./", "./a/", "./a b", "a/b", ".//a", "./${a}"] {
+            assert_eq!(None, rewrite(input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn store_paths() {
+        let cases = [
+            (format!("/nix/store/{}-hello-2.12.1/bin/hello", HASH), r#""${pkgs.hello}/bin/hello""#),
+            (format!("/nix/store/{}-python3-3.11.9", HASH), r#""${pkgs.python3}""#),
+            (
+                format!("/nix/store/{}-git-with-svn-2.44.0/bin/git", HASH),
+                r#""${pkgs.git-with-svn}/bin/git""#,
+            ),
+            (
+                format!("exec /nix/store/{}-foo.bar/x --flag", HASH),
+                r#""exec ${pkgs."foo.bar"}/x --flag""#,
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(Some(expected.to_string()), rewrite(&input), "input: {}", input);
+        }
+
+        for input in [
+            "/nix/store/abc-hello".to_string(),
+            format!("/nix/store/{}", HASH),
+            format!("/a/nix/store/{}-hello", HASH),
+        ] {
+            assert_eq!(None, rewrite(&input), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn only_configured_rewrites() {
+        assert_eq!(None, rewrite_string("~/bin", &[StringRewrite::StorePaths]));
+        assert_eq!(None, rewrite_string("./a", &[StringRewrite::HomeDirectory("/home/alice".to_string())]));
+        assert_eq!(
+            Some(NixExpr::InterpolatedString(vec![StringPart::Interpolation(HOME_DIRECTORY.to_string())])),
+            rewrite_string("~", &[StringRewrite::HomeDirectory(String::new())])
+        );
+    }
+}
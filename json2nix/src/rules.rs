@@ -0,0 +1,244 @@
+use serde_json::{Map, Value};
+
+use crate::{Json2NixError, NixExpr};
+
+/// Wraps or transforms every value whose JSON Pointer (RFC 6901) matches `pointer`. The pointer may contain `*`, which
+/// matches exactly one reference token, and `**`, which matches any number of them, e.g. `/services/*/enable` or
+/// `/**/enable`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub pointer: String,
+    pub action: RuleAction,
+}
+
+impl Rule {
+    pub fn new(pointer: impl Into<String>, action: RuleAction) -> Self {
+        Self {
+            pointer: pointer.into(),
+            action,
+        }
+    }
+
+    /// Whether the rule applies to the value at the JSON Pointer `pointer`, which must not contain wildcards.
+    pub fn matches(&self, pointer: &str) -> bool {
+        matches(&tokens(&self.pointer), &tokens(pointer))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self.pointer.is_empty() || self.pointer.starts_with('/') {
+            true => Ok(()),
+            false => Err(format!("the pointer '{}' of a rule must be empty or start with '/'", self.pointer)),
+        }
+    }
+}
+
+/// How a value matched by a [`Rule`] is changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+    /// `lib.mkDefault value`
+    MkDefault,
+    /// `lib.mkForce value`
+    MkForce,
+    /// `lib.mkOverride priority value`
+    MkOverride(u32),
+    /// `lib.mkIf condition value`, where the condition is a Nix expression that is inserted as is.
+    MkIf(String),
+    /// `function value`, where the function is a Nix expression that is inserted as is, e.g. `lib.mkBefore`.
+    Apply(String),
+}
+
+impl RuleAction {
+    pub fn apply(&self, expr: NixExpr) -> NixExpr {
+        let (function, mut arguments) = match self {
+            RuleAction::MkDefault => ("lib.mkDefault", vec![]),
+            RuleAction::MkForce => ("lib.mkForce", vec![]),
+            RuleAction::MkOverride(priority) => ("lib.mkOverride", vec![NixExpr::Int(i64::from(*priority))]),
+            RuleAction::MkIf(condition) => ("lib.mkIf", vec![NixExpr::Raw(condition.clone())]),
+            RuleAction::Apply(function) => (function.as_str(), vec![]),
+        };
+        arguments.push(expr);
+
+        NixExpr::Apply {
+            function: function.to_string(),
+            arguments,
+        }
+    }
+}
+
+/// Checks that all rules can be applied.
+pub(crate) fn validate_rules(rules: &[Rule]) -> Result<(), Json2NixError> {
+    rules.iter().try_for_each(Rule::validate).map_err(Json2NixError::InvalidConfig)
+}
+
+/// Parses rules from a JSON list like
+/// `[{ "pointer": "/services/*/enable", "action": "mkDefault" }, { "pointer": "/boot/loader/timeout", "action": "mkOverride", "priority": 50 }]`.
+/// `mkIf` takes a `condition` and `apply` takes a `function`.
+pub fn parse_rules(input: &str) -> Result<Vec<Rule>, Json2NixError> {
+    let value: Value = serde_json::from_str(input)?;
+    let Value::Array(rules) = value else {
+        return Err(Json2NixError::InvalidConfig("the rules must be a list".to_string()));
+    };
+
+    rules
+        .iter()
+        .enumerate()
+        .map(|(index, rule)| parse_rule(rule).map_err(|message| Json2NixError::InvalidConfig(format!("rule {}: {}", index + 1, message))))
+        .collect()
+}
+
+fn parse_rule(rule: &Value) -> Result<Rule, String> {
+    let Value::Object(rule) = rule else {
+        return Err("a rule must be an object".to_string());
+    };
+
+    let action = match string_field(rule, "action")? {
+        "mkDefault" => RuleAction::MkDefault,
+        "mkForce" => RuleAction::MkForce,
+        "mkOverride" => {
+            let priority = rule
+                .get("priority")
+                .and_then(Value::as_u64)
+                .ok_or("`mkOverride` requires a non-negative integer `priority`")?;
+            RuleAction::MkOverride(u32::try_from(priority).map_err(|_| format!("the priority {} is too large", priority))?)
+        }
+        "mkIf" => RuleAction::MkIf(string_field(rule, "condition")?.to_string()),
+        "apply" => RuleAction::Apply(string_field(rule, "function")?.to_string()),
+        action => {
+            return Err(format!(
+                "unknown action `{}`, expected `mkDefault`, `mkForce`, `mkOverride`, `mkIf` or `apply`",
+                action
+            ));
+        }
+    };
+
+    let rule = Rule::new(string_field(rule, "pointer")?, action);
+    rule.validate()?;
+    Ok(rule)
+}
+
+fn string_field<'a>(rule: &'a Map<String, Value>, name: &str) -> Result<&'a str, String> {
+    rule.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("the string `{}` is missing", name))
+}
+
+fn tokens(pointer: &str) -> Vec<&str> {
+    pointer.split('/').skip(1).collect()
+}
+
+fn matches(pattern: &[&str], pointer: &[&str]) -> bool {
+    match (pattern.split_first(), pointer.split_first()) {
+        (None, None) => true,
+        (Some((&"**", pattern_rest)), _) => matches(pattern_rest, pointer) || (!pointer.is_empty() && matches(pattern, &pointer[1..])),
+        (Some((token, pattern_rest)), Some((first, pointer_rest))) => (*token == "*" || token == first) && matches(pattern_rest, pointer_rest),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        let cases = [
+            ("", "", true),
+            ("", "/a", false),
+            ("/a", "/a", true),
+            ("/a", "/a/b", false),
+            ("/a/*", "/a/b", true),
+            ("/a/*", "/a", false),
+            ("/*/enable", "/nginx/enable", true),
+            ("/**", "", true),
+            ("/**/enable", "/enable", true),
+            ("/**/enable", "/services/nginx/enable", true),
+            ("/**/enable", "/services/nginx/enabled", false),
+            ("/a/**/b", "/a/x/y/b", true),
+            ("/a/**/b", "/a/b/c", false),
+            ("/a~1b/*", "/a~1b/0", true),
+            ("/a/", "/a/", true),
+        ];
+
+        for (pattern, pointer, expected) in cases {
+            assert_eq!(
+                expected,
+                Rule::new(pattern, RuleAction::MkDefault).matches(pointer),
+                "pattern: {}, pointer: {}",
+                pattern,
+                pointer
+            );
+        }
+    }
+
+    #[test]
+    fn actions() {
+        let apply = |action: RuleAction| action.apply(NixExpr::Bool(true));
+        let expected = |function: &str, mut arguments: Vec<NixExpr>| {
+            arguments.push(NixExpr::Bool(true));
+            NixExpr::Apply {
+                function: function.to_string(),
+                arguments,
+            }
+        };
+
+        assert_eq!(expected("lib.mkDefault", vec![]), apply(RuleAction::MkDefault));
+        assert_eq!(expected("lib.mkForce", vec![]), apply(RuleAction::MkForce));
+        assert_eq!(expected("lib.mkOverride", vec![NixExpr::Int(50)]), apply(RuleAction::MkOverride(50)));
+        assert_eq!(
+            expected("lib.mkIf", vec![NixExpr::Raw("config.a.enable".to_string())]),
+            apply(RuleAction::MkIf("config.a.enable".to_string()))
+        );
+        assert_eq!(expected("lib.mkBefore", vec![]), apply(RuleAction::Apply("lib.mkBefore".to_string())));
+    }
+
+    #[test]
+    fn parsing() {
+        let input = r#"[
+            { "pointer": "/services/*/enable", "action": "mkDefault" },
+            { "pointer": "/networking/firewall", "action": "mkForce" },
+            { "pointer": "/boot/loader/timeout", "action": "mkOverride", "priority": 50 },
+            { "pointer": "/services/nginx", "action": "mkIf", "condition": "config.web.enable" },
+            { "pointer": "/environment/systemPackages", "action": "apply", "function": "lib.mkAfter" }
+        ]"#;
+        let expected = vec![
+            Rule::new("/services/*/enable", RuleAction::MkDefault),
+            Rule::new("/networking/firewall", RuleAction::MkForce),
+            Rule::new("/boot/loader/timeout", RuleAction::MkOverride(50)),
+            Rule::new("/services/nginx", RuleAction::MkIf("config.web.enable".to_string())),
+            Rule::new("/environment/systemPackages", RuleAction::Apply("lib.mkAfter".to_string())),
+        ];
+        assert_eq!(expected, parse_rules(input).unwrap());
+    }
+
+    #[test]
+    fn parsing_errors() {
+        let cases = [
+            (r#"{}"#, "Invalid configuration: the rules must be a list"),
+            (r#"[1]"#, "Invalid configuration: rule 1: a rule must be an object"),
+            (
+                r#"[{ "action": "mkForce" }]"#,
+                "Invalid configuration: rule 1: the string `pointer` is missing",
+            ),
+            (
+                r#"[{ "pointer": "a", "action": "mkForce" }]"#,
+                "Invalid configuration: rule 1: the pointer 'a' of a rule must be empty or start with '/'",
+            ),
+            (
+                r#"[{ "pointer": "", "action": "mkOverride", "priority": -1 }]"#,
+                "Invalid configuration: rule 1: `mkOverride` requires a non-negative integer `priority`",
+            ),
+            (
+                r#"[{ "pointer": "", "action": "mkForce" }, { "pointer": "", "action": "mkIf" }]"#,
+                "Invalid configuration: rule 2: the string `condition` is missing",
+            ),
+            (
+                r#"[{ "pointer": "", "action": "mkMerge" }]"#,
+                "Invalid configuration: rule 1: unknown action `mkMerge`, expected `mkDefault`, `mkForce`, `mkOverride`, `mkIf` or `apply`",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(expected, parse_rules(input).unwrap_err().to_string(), "input: {}", input);
+        }
+    }
+}
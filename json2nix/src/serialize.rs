@@ -0,0 +1,774 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, BufWriter, Write},
+};
+
+use serde::{
+    Serialize,
+    ser::{self, Impossible},
+};
+use serde_json::{Number, Value};
+
+use crate::{
+    Json2NixConfig, Json2NixError,
+    ast::drop_expr,
+    convert::Comments,
+    json::{Event, ValueBuilder, drop_value},
+    stream::{Emitter, whole_value_option},
+    to_nix_expr, write_nix,
+};
+
+// The name of the struct that serde_json serializes its numbers as when `arbitrary_precision` is enabled.
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Converts any [`Serialize`] value to a Nix expression without going through a JSON string. All options except
+/// [`Json2NixConfig::input_format`] apply, and if [`Json2NixConfig::supports_streaming`], the expression is written while
+/// the value is serialized. The data model of serde is mapped like in `serde_json`:
+///
+/// - `None`, `()` and unit structs become `null`, `Some(value)` and newtype structs become their value.
+/// - Sequences, tuples and tuple structs become lists, byte arrays become lists of integers.
+/// - Maps and structs become attribute sets. Map keys may be strings, characters, booleans, numbers, unit variants or
+///   newtype structs of these, which are converted to attribute names like `"1"` or `"true"`.
+/// - Unit variants become the name of the variant as string. All other variants become a set with the name of the variant
+///   as only attribute, e.g. `{ Port = 22; }`.
+/// - Integers that do not fit into 64 bits are handled according to [`Json2NixConfig::unrepresentable_numbers`].
+/// - Floats that are not finite are rejected with [`Json2NixError::Serialize`], as Nix has no literal for them.
+pub fn to_nix_string<T: Serialize + ?Sized>(value: &T, config: &Json2NixConfig) -> Result<String, Json2NixError> {
+    let mut output = Vec::new();
+    to_nix_writer(&mut output, value, config)?;
+    Ok(String::from_utf8(output).expect("the Nix expression is valid UTF-8"))
+}
+
+/// Like [`to_nix_string`], but writes the Nix expression to `writer`, which is buffered. If the conversion fails, a part
+/// of the output may already be written.
+pub fn to_nix_writer<T: Serialize + ?Sized>(writer: impl Write, value: &T, config: &Json2NixConfig) -> Result<(), Json2NixError> {
+    let writer = BufWriter::new(writer);
+    if whole_value_option(config).is_none() {
+        let mut emitter = Emitter::new(config, writer)?;
+        value.serialize(Serializer { sink: &mut emitter })?;
+        emitter.finish()?;
+        return Ok(());
+    }
+
+    let mut builder = ValueBuilder::new();
+    value.serialize(Serializer { sink: &mut builder })?;
+    let value = builder.finish();
+    let expr = to_nix_expr(&value, &Comments::new(), config, &mut Vec::new());
+    drop_value(value);
+
+    let expr = expr?;
+    let mut output = IoWriter { writer, error: None };
+    let result = write_nix(&expr, config, &mut output);
+    drop_expr(expr);
+    if result.is_err() {
+        return Err(output.error.unwrap_or_else(|| io::Error::other("formatting failed")).into());
+    }
+    Ok(output.writer.flush()?)
+}
+
+// Passes formatted text on to an `io::Write` and keeps the error, which `fmt::Error` cannot carry.
+struct IoWriter<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.writer.write_all(text.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+// Receives the events of the serialized value, either to write them directly or to build a JSON value first.
+trait Sink {
+    fn event(&mut self, event: Event) -> Result<(), Json2NixError>;
+}
+
+impl<W: Write> Sink for Emitter<'_, W> {
+    fn event(&mut self, event: Event) -> Result<(), Json2NixError> {
+        Emitter::event(self, event)
+    }
+}
+
+impl Sink for ValueBuilder {
+    fn event(&mut self, event: Event) -> Result<(), Json2NixError> {
+        ValueBuilder::event(self, event).map_err(Json2NixError::Serialize)
+    }
+}
+
+fn float_error(float: impl Display) -> Json2NixError {
+    Json2NixError::Serialize(format!("the float {} cannot be represented in Nix", float))
+}
+
+fn key_error() -> Json2NixError {
+    Json2NixError::Serialize("key must be a string".to_string())
+}
+
+struct Serializer<'s, S: Sink> {
+    sink: &'s mut S,
+}
+
+impl<S: Sink> Serializer<'_, S> {
+    fn scalar(self, value: Value) -> Result<(), Json2NixError> {
+        self.sink.event(Event::Scalar(value))
+    }
+}
+
+impl<'s, S: Sink> ser::Serializer for Serializer<'s, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+    type SerializeSeq = Compound<'s, S>;
+    type SerializeTuple = Compound<'s, S>;
+    type SerializeTupleStruct = Compound<'s, S>;
+    type SerializeTupleVariant = Compound<'s, S>;
+    type SerializeMap = Compound<'s, S>;
+    type SerializeStruct = Compound<'s, S>;
+    type SerializeStructVariant = Compound<'s, S>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Json2NixError> {
+        self.scalar(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Json2NixError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Json2NixError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Json2NixError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Json2NixError> {
+        self.scalar(Value::from(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Json2NixError> {
+        self.scalar(Value::Number(Number::from_i128(v).expect("numbers have arbitrary precision")))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Json2NixError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Json2NixError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Json2NixError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Json2NixError> {
+        self.scalar(Value::from(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Json2NixError> {
+        self.scalar(Value::Number(Number::from_u128(v).expect("numbers have arbitrary precision")))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Json2NixError> {
+        if !v.is_finite() {
+            return Err(float_error(v));
+        }
+        self.scalar(Value::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Json2NixError> {
+        let number = Number::from_f64(v).ok_or_else(|| float_error(v))?;
+        self.scalar(Value::Number(number))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Json2NixError> {
+        self.scalar(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Json2NixError> {
+        self.scalar(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Json2NixError> {
+        self.sink.event(Event::StartList)?;
+        for byte in v {
+            self.sink.event(Event::Scalar(Value::from(*byte)))?;
+        }
+        self.sink.event(Event::End)
+    }
+
+    fn serialize_none(self) -> Result<(), Json2NixError> {
+        self.scalar(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Json2NixError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Json2NixError> {
+        self.scalar(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Json2NixError> {
+        self.scalar(Value::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<(), Json2NixError> {
+        self.scalar(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), Json2NixError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Json2NixError> {
+        self.sink.event(Event::StartObject)?;
+        self.sink.event(Event::Key(variant.to_string()))?;
+        value.serialize(Serializer { sink: &mut *self.sink })?;
+        self.sink.event(Event::End)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'s, S>, Json2NixError> {
+        self.sink.event(Event::StartList)?;
+        Ok(Compound::new(self.sink, 1))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'s, S>, Json2NixError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'s, S>, Json2NixError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, S>, Json2NixError> {
+        self.sink.event(Event::StartObject)?;
+        self.sink.event(Event::Key(variant.to_string()))?;
+        self.sink.event(Event::StartList)?;
+        Ok(Compound::new(self.sink, 2))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'s, S>, Json2NixError> {
+        self.sink.event(Event::StartObject)?;
+        Ok(Compound::new(self.sink, 1))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Compound<'s, S>, Json2NixError> {
+        // the number is passed as its only field, with its text as value
+        if name == NUMBER_TOKEN {
+            return Ok(Compound::new(self.sink, 0));
+        }
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, S>, Json2NixError> {
+        self.sink.event(Event::StartObject)?;
+        self.sink.event(Event::Key(variant.to_string()))?;
+        self.sink.event(Event::StartObject)?;
+        Ok(Compound::new(self.sink, 2))
+    }
+}
+
+// A list or set that is being serialized, together with the sets of the variant around it.
+struct Compound<'s, S: Sink> {
+    sink: &'s mut S,
+    /// The number of lists and sets to end, or 0 for a number of serde_json.
+    ends: usize,
+}
+
+impl<'s, S: Sink> Compound<'s, S> {
+    fn new(sink: &'s mut S, ends: usize) -> Self {
+        Self { sink, ends }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Json2NixError> {
+        value.serialize(Serializer { sink: &mut *self.sink })
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Json2NixError> {
+        if self.ends == 0 {
+            let number = value.serialize(MapKeySerializer)?;
+            let number = number
+                .parse()
+                .map_err(|_| Json2NixError::Serialize(format!("invalid number {}", number)))?;
+            return self.sink.event(Event::Scalar(Value::Number(number)));
+        }
+        self.sink.event(Event::Key(key.to_string()))?;
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        for _ in 0..self.ends {
+            self.sink.event(Event::End)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Sink> ser::SerializeSeq for Compound<'_, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Json2NixError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        Compound::end(self)
+    }
+}
+
+impl<S: Sink> ser::SerializeTuple for Compound<'_, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Json2NixError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        Compound::end(self)
+    }
+}
+
+impl<S: Sink> ser::SerializeTupleStruct for Compound<'_, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Json2NixError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        Compound::end(self)
+    }
+}
+
+impl<S: Sink> ser::SerializeTupleVariant for Compound<'_, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Json2NixError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        Compound::end(self)
+    }
+}
+
+impl<S: Sink> ser::SerializeMap for Compound<'_, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Json2NixError> {
+        let key = key.serialize(MapKeySerializer)?;
+        self.sink.event(Event::Key(key))
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Json2NixError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        Compound::end(self)
+    }
+}
+
+impl<S: Sink> ser::SerializeStruct for Compound<'_, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Json2NixError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        Compound::end(self)
+    }
+}
+
+impl<S: Sink> ser::SerializeStructVariant for Compound<'_, S> {
+    type Ok = ();
+    type Error = Json2NixError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Json2NixError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Json2NixError> {
+        Compound::end(self)
+    }
+}
+
+// Turns map keys into attribute names. Like in serde_json, only scalars are accepted.
+struct MapKeySerializer;
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
+    type Error = Json2NixError;
+    type SerializeSeq = Impossible<String, Json2NixError>;
+    type SerializeTuple = Impossible<String, Json2NixError>;
+    type SerializeTupleStruct = Impossible<String, Json2NixError>;
+    type SerializeTupleVariant = Impossible<String, Json2NixError>;
+    type SerializeMap = Impossible<String, Json2NixError>;
+    type SerializeStruct = Impossible<String, Json2NixError>;
+    type SerializeStructVariant = Impossible<String, Json2NixError>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<String, Json2NixError> {
+        if !v.is_finite() {
+            return Err(float_error(v));
+        }
+        Ok(Value::from(v).to_string())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<String, Json2NixError> {
+        Number::from_f64(v).map(|number| number.to_string()).ok_or_else(|| float_error(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Json2NixError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<String, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_unit(self) -> Result<String, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<String, Json2NixError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<String, Json2NixError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Json2NixError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Json2NixError> {
+        Err(key_error())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::{FlattenSetKeys, UnrepresentableNumbers};
+
+    fn print<T: Serialize + ?Sized>(value: &T) -> String {
+        to_nix_string(value, &Json2NixConfig::default()).unwrap()
+    }
+
+    #[derive(Serialize)]
+    struct Unit;
+
+    #[derive(Serialize, PartialEq, Eq, Hash)]
+    struct Port(u16);
+
+    #[derive(Serialize, PartialEq, Eq, Hash)]
+    enum Level {
+        Debug,
+        Port(u16),
+        Range(u16, u16),
+        Named { name: String, enable: bool },
+    }
+
+    #[derive(Serialize)]
+    struct Service {
+        enable: bool,
+        port: Port,
+        level: Level,
+        user: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+        marker: Unit,
+        weight: f32,
+        tags: (char, &'static str),
+    }
+
+    // A map with float keys, which the maps of std do not support.
+    struct FloatKeys(&'static [f64]);
+
+    impl Serialize for FloatKeys {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|key| (key, ())))
+        }
+    }
+
+    #[test]
+    fn structs() {
+        let service = Service {
+            enable: true,
+            port: Port(22),
+            level: Level::Debug,
+            user: None,
+            group: None,
+            marker: Unit,
+            weight: 0.1,
+            tags: ('a', "b"),
+        };
+        let expected = r#"{
+  enable = true;
+  port = 22;
+  level = "Debug";
+  user = null;
+  marker = null;
+  weight = 0.1;
+  tags = [
+    "a"
+    "b"
+  ];
+}"#;
+        assert_eq!(expected, print(&service));
+    }
+
+    #[test]
+    fn variants() {
+        assert_eq!("\"Debug\"", print(&Level::Debug));
+        assert_eq!("{\n  Port = 22;\n}", print(&Level::Port(22)));
+        assert_eq!("{\n  Range = [\n    1\n    2\n  ];\n}", print(&Level::Range(1, 2)));
+        assert_eq!(
+            "{\n  Named = {\n    name = \"a\";\n    enable = false;\n  };\n}",
+            print(&Level::Named {
+                name: "a".to_string(),
+                enable: false,
+            })
+        );
+    }
+
+    #[test]
+    fn map_keys() {
+        assert_eq!("{\n  \"-2\" = \"b\";\n  \"1\" = \"a\";\n}", print(&BTreeMap::from([(1, "a"), (-2, "b")])));
+        assert_eq!("{\n  false = 0;\n  true = 1;\n}", print(&BTreeMap::from([(false, 0), (true, 1)])));
+        assert_eq!("{\n  a = 1;\n}", print(&HashMap::from([('a', 1)])));
+        assert_eq!("{\n  Debug = 1;\n}", print(&HashMap::from([(Level::Debug, 1)])));
+        assert_eq!("{\n  \"22\" = 1;\n}", print(&HashMap::from([(Port(22), 1)])));
+
+        let err = to_nix_string(&HashMap::from([((1, 2), 1)]), &Json2NixConfig::default()).unwrap_err();
+        assert_eq!("Could not serialize the value: key must be a string", err.to_string());
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!("[\n  1\n  (-1)\n  1.5\n]", print(&(1u8, -1i128, 1.5f64)));
+        assert_eq!("9223372036854775807", print(&(i64::MAX as u128)));
+
+        let config = Json2NixConfig::default();
+        assert!(matches!(
+            to_nix_string(&u128::MAX, &config),
+            Err(Json2NixError::UnrepresentableNumber { .. })
+        ));
+        assert!(matches!(
+            to_nix_string(&u64::MAX, &config),
+            Err(Json2NixError::UnrepresentableNumber { .. })
+        ));
+        for result in [
+            to_nix_string(&f64::NAN, &config),
+            to_nix_string(&[f32::INFINITY], &config),
+            to_nix_string(&FloatKeys(&[f64::NEG_INFINITY]), &config),
+        ] {
+            assert!(matches!(result, Err(Json2NixError::Serialize(_))));
+        }
+        assert_eq!("{\n  \"1.5\" = null;\n}", print(&FloatKeys(&[1.5])));
+        assert_eq!(
+            "Could not serialize the value: the float NaN cannot be represented in Nix",
+            to_nix_string(&f64::NAN, &config).unwrap_err().to_string()
+        );
+
+        let config = Json2NixConfig {
+            unrepresentable_numbers: UnrepresentableNumbers::String,
+            ..Json2NixConfig::default()
+        };
+        assert_eq!("\"340282366920938463463374607431768211455\"", to_nix_string(&u128::MAX, &config).unwrap());
+    }
+
+    #[test]
+    fn json_values() {
+        let input = r#"{"a":[1,1e400,18446744073709551616,"b"],"c":{"d":null}}"#;
+        let value: serde_json::Value = serde_json::from_str(input).unwrap();
+        let config = Json2NixConfig {
+            unrepresentable_numbers: UnrepresentableNumbers::String,
+            ..Json2NixConfig::default()
+        };
+        assert_eq!(crate::json2nix(input, &config).unwrap(), to_nix_string(&value, &config).unwrap());
+    }
+
+    #[test]
+    fn whole_value_options() {
+        let service = BTreeMap::from([("services", BTreeMap::from([("sshd", Level::Port(22))]))]);
+        let config = Json2NixConfig {
+            flatten_set_keys: FlattenSetKeys::Always,
+            ..Json2NixConfig::default()
+        };
+        assert_eq!("{\n  services.sshd.Port = 22;\n}", to_nix_string(&service, &config).unwrap());
+
+        // the streamed output is the same as the one of the JSON value
+        let value = serde_json::to_value(&service).unwrap();
+        let config = Json2NixConfig {
+            key_priority: vec!["x".to_string()],
+            ..Json2NixConfig::default()
+        };
+        assert_eq!(to_nix_string(&value, &config).unwrap(), print(&service));
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!("[\n  null\n  1\n]", print(&[None, Some(1)]));
+        assert_eq!("null", print(&()));
+        assert_eq!("[\n  1\n  2\n]", print(&b"\x01\x02"[..]));
+    }
+
+    #[test]
+    fn config() {
+        let config = Json2NixConfig {
+            attr_path: vec!["services".to_string(), "sshd".to_string()],
+            ..Json2NixConfig::new(0, 2, true)
+        };
+        assert_eq!(
+            "services.sshd.port = 22;",
+            to_nix_string(&BTreeMap::from([("port", 22)]), &config).unwrap()
+        );
+    }
+
+    #[test]
+    fn writer() {
+        let mut output = Vec::new();
+        to_nix_writer(&mut output, &[1, 2], &Json2NixConfig::new(0, 2, false)).unwrap();
+        assert_eq!(b"[\n  1\n  2\n]", output.as_slice());
+
+        let mut full = [0u8; 4];
+        let result = to_nix_writer(&mut full[..], &[1, 2], &Json2NixConfig::new(0, 2, false));
+        assert!(matches!(result, Err(Json2NixError::Io(err)) if err.kind() == io::ErrorKind::WriteZero));
+    }
+}
@@ -0,0 +1,396 @@
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use serde_json::Value;
+
+use crate::{
+    FlattenSetKeys, InputFormat, Json2NixConfig, Json2NixError, KeyOrder, OutputStyle, Warning,
+    ast::{Binding, NixExpr},
+    convert::{apply_rules, check_nul, push_pointer, string_expr, wrap},
+    json::{Event, Parser},
+    number::convert_number,
+    printer::{print_expanded_binding, print_expanded_list_element, print_nix_expr},
+    rules::validate_rules,
+};
+
+// Stands for a list or set while its surroundings are rendered, which are then split at the placeholder. Like lists and
+// sets, it is never parenthesized.
+const PLACEHOLDER: &str = "__json2nix_value__";
+
+/// Returns the first option of `config` that cannot be used for streaming.
+pub(crate) fn unsupported_option(config: &Json2NixConfig) -> Option<&'static str> {
+    match config.input_format {
+        InputFormat::Json => whole_value_option(config),
+        _ => Some("input formats other than JSON"),
+    }
+}
+
+/// Returns the first option of `config` that needs the whole value, so the output cannot be written while it is read.
+pub(crate) fn whole_value_option(config: &Json2NixConfig) -> Option<&'static str> {
+    if config.compact_set_keys {
+        Some("compact_set_keys")
+    } else if config.flatten_set_keys != FlattenSetKeys::Never {
+        Some("flatten_set_keys")
+    } else if config.max_line_width.is_some() {
+        Some("max_line_width")
+    } else if config.style != OutputStyle::Default {
+        Some("styles other than the default")
+    } else if config.raw_nix_objects {
+        Some("raw_nix_objects")
+    } else if config.extract_repeated.is_some() {
+        Some("extract_repeated")
+    } else if config.key_order != KeyOrder::Preserve {
+        Some("key_order")
+    } else if !config.key_priority.is_empty() {
+        Some("key_priority")
+    } else {
+        None
+    }
+}
+
+/// Converts the JSON document read from `reader` and writes the Nix expression to `writer` while reading, so the memory
+/// usage only depends on the nesting depth and the longest string, not on the size of the input. Both are buffered.
+///
+/// The output is the same as the one of [`crate::json2nix_with_warnings`], but only options that do not need to look
+/// ahead are supported, see [`Json2NixConfig::supports_streaming`]. Other options are rejected with
+/// [`Json2NixError::InvalidConfig`]. Duplicate keys are written as duplicate attributes, which Nix merges if both values
+/// are sets and rejects otherwise, just like [`crate::json2nix`] does. If the conversion fails, a part of the output may
+/// already be written.
+pub fn json2nix_stream(reader: impl Read, writer: impl Write, config: &Json2NixConfig) -> Result<Vec<Warning>, Json2NixError> {
+    if let Some(option) = unsupported_option(config) {
+        return Err(Json2NixError::InvalidConfig(format!("{} cannot be used for streaming", option)));
+    }
+
+    let mut emitter = Emitter::new(config, BufWriter::new(writer))?;
+    let mut parser = Parser::new(BufReader::new(reader));
+    while let Some(event) = parser.next()? {
+        emitter.event(event)?;
+    }
+
+    let (_, warnings) = emitter.finish()?;
+    Ok(warnings)
+}
+
+/// A list or set whose closing bracket has not been read yet.
+struct Container {
+    closing: &'static str,
+    indentation: usize,
+    /// The text after the closing bracket.
+    suffix: String,
+    /// The length of the pointer in front of the reference token of the current value.
+    length: usize,
+    /// The index of the next element of a list.
+    next_index: usize,
+    /// The key of the current value of a set.
+    key: Option<String>,
+}
+
+/// Where a value is rendered, which determines the text around it.
+enum Position {
+    Root,
+    /// An element of a list at the given indentation.
+    ListElement(usize),
+    /// The value of the attribute with the given name at the given indentation.
+    Binding(usize, String),
+}
+
+/// Writes the Nix expression for the events of a JSON value while they are received.
+pub(crate) struct Emitter<'c, W: Write> {
+    config: &'c Json2NixConfig,
+    writer: W,
+    /// The lists and sets around the current value.
+    containers: Vec<Container>,
+    /// The JSON Pointer of the value that is currently converted.
+    pointer: String,
+    warnings: Vec<Warning>,
+    /// The opening bracket of the innermost list or set, which is only written with its first element, so empty ones can
+    /// be rendered as `[ ]` or `{ }`.
+    pending_opening: Option<String>,
+    /// The number of lists and sets around the current value.
+    depth: usize,
+}
+
+impl<'c, W: Write> Emitter<'c, W> {
+    pub(crate) fn new(config: &'c Json2NixConfig, writer: W) -> Result<Self, Json2NixError> {
+        validate_rules(&config.rules)?;
+
+        Ok(Self {
+            config,
+            writer,
+            containers: Vec::new(),
+            pointer: String::new(),
+            warnings: Vec::new(),
+            pending_opening: None,
+            depth: 0,
+        })
+    }
+
+    pub(crate) fn event(&mut self, event: Event) -> Result<(), Json2NixError> {
+        match event {
+            Event::StartList | Event::StartObject => {
+                let position = self.position();
+                let is_list = event == Event::StartList;
+                let (indentation, suffix) = self.open(if is_list { "[" } else { "{" }, &position)?;
+                self.containers.push(Container {
+                    closing: if is_list { "]" } else { "}" },
+                    indentation,
+                    suffix,
+                    length: 0,
+                    next_index: 0,
+                    key: None,
+                });
+            }
+            Event::Key(key) => {
+                let container = self.containers.last_mut().expect("keys are inside of sets");
+                container.length = push_pointer(&mut self.pointer, &key);
+                check_nul(&key, &self.pointer)?;
+                container.key = Some(key);
+            }
+            Event::Scalar(value) => {
+                let position = self.position();
+                let expr = self.scalar(value)?;
+                self.leaf(expr, &position)?;
+                self.end_value();
+            }
+            Event::End => {
+                let container = self.containers.pop().expect("the list or set was started");
+                self.close(container.closing, container.indentation, &container.suffix)?;
+                self.end_value();
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the output after the last event and returns the writer and the warnings.
+    pub(crate) fn finish(mut self) -> Result<(W, Vec<Warning>), Json2NixError> {
+        self.writer.flush()?;
+        Ok((self.writer, self.warnings))
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), Json2NixError> {
+        Ok(self.writer.write_all(text.as_bytes())?)
+    }
+
+    fn begin(&mut self, position: &Position) -> Result<(), Json2NixError> {
+        if let Some(opening) = self.pending_opening.take() {
+            self.write(&opening)?;
+        }
+        match position {
+            Position::Root => Ok(()),
+            _ => self.write("\n"),
+        }
+    }
+
+    // Renders `expr` with everything in front of and behind it that depends on its position, so the surroundings are
+    // rendered exactly like the printer does.
+    fn render(&self, expr: NixExpr, position: &Position) -> String {
+        let expr = apply_rules(expr, &self.pointer, self.config);
+
+        match position {
+            Position::Root => print_nix_expr(&wrap(expr, self.config), self.config),
+            Position::ListElement(indentation) => print_expanded_list_element(&expr, self.config, *indentation),
+            Position::Binding(indentation, key) => print_expanded_binding(&Binding::new(vec![key.clone()], expr), self.config, *indentation),
+        }
+    }
+
+    // Returns the position of the next value in the innermost container and adds its reference token to the pointer.
+    fn position(&mut self) -> Position {
+        match self.containers.last_mut() {
+            None => Position::Root,
+            Some(container) => match container.key.take() {
+                Some(key) => Position::Binding(container.indentation + self.config.indentation_increment, key),
+                None => {
+                    container.length = push_pointer(&mut self.pointer, &container.next_index.to_string());
+                    container.next_index += 1;
+                    Position::ListElement(container.indentation + self.config.indentation_increment)
+                }
+            },
+        }
+    }
+
+    // Removes the reference token of the value that was just converted from the pointer.
+    fn end_value(&mut self) {
+        if let Some(container) = self.containers.last() {
+            self.pointer.truncate(container.length);
+        }
+    }
+
+    fn scalar(&mut self, value: Value) -> Result<NixExpr, Json2NixError> {
+        match value {
+            Value::Null => Ok(NixExpr::Null),
+            Value::Bool(bool) => Ok(NixExpr::Bool(bool)),
+            Value::Number(number) => convert_number(&number, self.config.unrepresentable_numbers, &mut self.warnings),
+            Value::String(string) => string_expr(&string, &self.pointer, self.config, &mut self.warnings),
+            Value::Array(_) | Value::Object(_) => unreachable!("lists and objects are not scalars"),
+        }
+    }
+
+    fn leaf(&mut self, expr: NixExpr, position: &Position) -> Result<(), Json2NixError> {
+        self.begin(position)?;
+        let rendered = self.render(expr, position);
+        self.write(&rendered)
+    }
+
+    // Returns the indentation of the list or set and the text that has to be written after it.
+    fn open(&mut self, bracket: &str, position: &Position) -> Result<(usize, String), Json2NixError> {
+        if let Some(max_depth) = self.config.max_depth
+            && self.depth >= max_depth
+        {
+            return Err(Json2NixError::TooDeep {
+                pointer: self.pointer.clone(),
+                max_depth,
+            });
+        }
+        self.depth += 1;
+        self.begin(position)?;
+
+        let rendered = self.render(NixExpr::Raw(PLACEHOLDER.to_string()), position);
+        // the placeholder is the last expression, anything in front of it like rule conditions could contain its text
+        let (prefix, suffix) = rendered.rsplit_once(PLACEHOLDER).expect("the placeholder is rendered");
+        let line = prefix.rsplit('\n').next().unwrap_or(prefix);
+        let indentation = line.chars().take_while(|c| *c == ' ').count();
+
+        self.pending_opening = Some(format!("{}{}", prefix, bracket));
+        Ok((indentation, suffix.to_string()))
+    }
+
+    fn close(&mut self, bracket: &str, indentation: usize, suffix: &str) -> Result<(), Json2NixError> {
+        self.depth -= 1;
+        match self.pending_opening.take() {
+            Some(opening) => self.write(&format!("{} {}", opening, bracket))?,
+            None => self.write(&format!("\n{}{}", " ".repeat(indentation), bracket))?,
+        }
+        self.write(suffix)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Rule, RuleAction, StringRewrite, StringStyle, UnrepresentableNumbers, json2nix_with_warnings, parse::parse_nix};
+
+    fn stream(input: &str, config: &Json2NixConfig) -> Result<(String, Vec<Warning>), Json2NixError> {
+        let mut output = Vec::new();
+        let warnings = json2nix_stream(input.as_bytes(), &mut output, config)?;
+        Ok((String::from_utf8(output).unwrap(), warnings))
+    }
+
+    const INPUTS: [&str; 7] = [
+        "null",
+        r#""a ${b}\nc""#,
+        "[]",
+        "{}",
+        r#"[1, -2, 0.5, -1e100, 18446744073709551615, true, null, "x", [], {}, [[]], [{ "a": [] }]]"#,
+        r#"{ "a": { "b": { "c": 1 } }, "d e": [ { "f": "g\n\nh\n" } ], "in": {}, "": [-1] }"#,
+        r#"{ "home": "/home/alice/x", "raw": "nix:pkgs.hello", "services": { "nginx": { "enable": true }, "sshd": { "enable": false } } }"#,
+    ];
+
+    fn configs() -> Vec<Json2NixConfig> {
+        vec![
+            Json2NixConfig::default(),
+            Json2NixConfig::new(4, 3, false),
+            Json2NixConfig {
+                string_style: StringStyle::Indented {
+                    double_quote_threshold: Some(0),
+                },
+                unrepresentable_numbers: UnrepresentableNumbers::Float,
+                ..Json2NixConfig::new(2, 2, false)
+            },
+            Json2NixConfig {
+                attr_path: vec!["programs".to_string(), "x y".to_string()],
+                module: true,
+                rules: vec![
+                    Rule::new("/services/*/enable", RuleAction::MkDefault),
+                    Rule::new("/services/*", RuleAction::MkIf("a && b".to_string())),
+                    Rule::new("/**", RuleAction::MkOverride(50)),
+                    Rule::new("/**/*", RuleAction::MkForce),
+                ],
+                raw_nix_prefix: Some("nix:".to_string()),
+                rewrites: vec![StringRewrite::HomeDirectory("/home/alice".to_string())],
+                unrepresentable_numbers: UnrepresentableNumbers::String,
+                ..Json2NixConfig::new(1, 2, false)
+            },
+        ]
+    }
+
+    #[test]
+    fn same_output() {
+        for config in configs() {
+            for input in INPUTS {
+                let expected = json2nix_with_warnings(input, &config).map_err(|err| err.to_string());
+                let actual = stream(input, &config).map_err(|err| err.to_string());
+                assert_eq!(expected, actual, "input: {}, config: {:?}", input, config);
+            }
+        }
+    }
+
+    #[test]
+    fn deep_input() {
+        let depth = 50_000;
+        let input = format!("{}1{}", r#"{"a":["#.repeat(depth), "]}".repeat(depth));
+        let config = Json2NixConfig::new(0, 0, false);
+        assert_eq!(json2nix_with_warnings(&input, &config).unwrap(), stream(&input, &config).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let config = Json2NixConfig::default();
+        for input in ["[1, 2", "1 2", "{ \"a\": tru }"] {
+            assert_eq!(
+                json2nix_with_warnings(input, &config).unwrap_err().to_string(),
+                stream(input, &config).unwrap_err().to_string()
+            );
+        }
+        assert_eq!(
+            "The integer 18446744073709551615 is out of the range of Nix, which only supports integers from -9223372036854775808 to 9223372036854775807",
+            stream("[18446744073709551615]", &config).unwrap_err().to_string()
+        );
+
+        let config = Json2NixConfig {
+            max_depth: Some(2),
+            ..Json2NixConfig::default()
+        };
+        for input in [r#"{ "a": [[1]] }"#, r#"[1, { "b": {} }, []]"#] {
+            assert_eq!(
+                json2nix_with_warnings(input, &config).unwrap_err().to_string(),
+                stream(input, &config).unwrap_err().to_string()
+            );
+        }
+
+        let config = Json2NixConfig {
+            raw_nix_prefix: Some("nix:".to_string()),
+            ..Json2NixConfig::default()
+        };
+        assert_eq!(
+            "The raw Nix expression at '/a/0' is invalid: `(` is not closed",
+            stream(r#"{ "a": ["nix:(1"] }"#, &config).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        // the duplicate sets are merged by Nix
+        let input = r#"{ "a": { "b": 1 }, "a": { "c": 2 } }"#;
+        let (nix, _) = stream(input, &Json2NixConfig::default()).unwrap();
+        assert_eq!("{\n  a = {\n    b = 1;\n  };\n  a = {\n    c = 2;\n  };\n}", nix);
+        let (expected, _) = json2nix_with_warnings(input, &Json2NixConfig::default()).unwrap();
+        assert_eq!(parse_nix(&expected).unwrap(), parse_nix(&nix).unwrap());
+
+        // and all other duplicates are rejected
+        let input = r#"{ "a": 1, "b": { "a": 2 }, "a": 3 }"#;
+        let (nix, _) = stream(input, &Json2NixConfig::default()).unwrap();
+        assert_eq!("{\n  a = 1;\n  b = {\n    a = 2;\n  };\n  a = 3;\n}", nix);
+        assert!(parse_nix(&nix).is_err());
+        assert!(json2nix_with_warnings(input, &Json2NixConfig::default()).is_err());
+    }
+
+    #[test]
+    fn unsupported_options() {
+        let config = Json2NixConfig::new(0, 2, true);
+        assert!(!config.supports_streaming());
+        assert_eq!(
+            "Invalid configuration: compact_set_keys cannot be used for streaming",
+            stream("{}", &config).unwrap_err().to_string()
+        );
+        assert!(Json2NixConfig::default().supports_streaming());
+    }
+}
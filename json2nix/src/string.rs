@@ -0,0 +1,322 @@
+use std::ops::Not;
+
+use crate::{ast::StringPart, doc::Doc};
+
+/// Renders `value` as a double-quoted Nix string literal that evaluates to exactly `value`.
+///
+/// Nix only knows the escape sequences `\n`, `\r` and `\t`, every other character preceded by a backslash stands for itself.
+/// Therefore `"` and `\` are escaped with a backslash and `$` is escaped when it is followed by `{`, as that would start an
+/// interpolation otherwise. Carriage returns must be escaped because the Nix lexer normalizes raw `\r\n` and `\r` to `\n`.
+/// All other control characters have no escape sequence in Nix, so they are written as they are, which keeps the string a
+/// plain literal that can be used as attribute name.
+///
+/// Note that Nix strings cannot contain NUL bytes, so a string containing `\0` fails to evaluate. The conversion rejects
+/// such strings with [`crate::Json2NixError::NulCharacter`].
+pub fn escape_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => result.push_str(r#"\""#),
+            '\\' => result.push_str(r"\\"),
+            '$' if chars.peek() == Some(&'{') => result.push_str(r"\$"),
+            '\n' => result.push_str(r"\n"),
+            '\r' => result.push_str(r"\r"),
+            '\t' => result.push_str(r"\t"),
+            _ => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+/// Renders `parts` as a double-quoted Nix string literal with interpolations.
+pub fn interpolated_string(parts: &[StringPart]) -> String {
+    let mut result = String::from('"');
+
+    for (index, part) in parts.iter().enumerate() {
+        match part {
+            StringPart::Literal(literal) => {
+                let escaped = escape_string(literal);
+                result.push_str(&escaped[1..escaped.len() - 1]);
+                // `$${` is not an interpolation, so a `$` in front of one has to be escaped
+                if result.ends_with('$') && matches!(parts.get(index + 1), Some(StringPart::Interpolation(_))) {
+                    result.pop();
+                    result.push_str(r"\$");
+                }
+            }
+            StringPart::Interpolation(expr) => {
+                result.push_str("${");
+                result.push_str(expr);
+                result.push('}');
+            }
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+/// Renders `value` as a Nix indented string (`'' … ''`) that evaluates to exactly `value`.
+///
+/// Strings containing a newline span multiple lines: the content is nested by `indentation_increment` and the closing `''` is
+/// aligned with the surrounding expression. All other strings are rendered on a single line.
+///
+/// Nix strips the common indentation of all lines that do not consist only of spaces, as well as a trailing line that consists
+/// only of spaces. Both would change the value, so in these cases the first of the affected leading spaces is escaped.
+pub fn indented_string(value: &str, indentation_increment: usize) -> Doc<'static> {
+    let mut result = String::with_capacity(value.len() + 4);
+    result.push_str("''");
+
+    if value.contains('\n').not() {
+        escape_indented_string_line(value, value.starts_with(' '), true, &mut result);
+        result.push_str("''");
+        return Doc::text(result);
+    }
+
+    let lines = value.split('\n').collect::<Vec<_>>();
+    let last_line = lines.len() - 1;
+    let is_whitespace_only = |line: &str| line.chars().all(|c| c == ' ');
+    let has_unindented_line = lines.iter().any(|line| is_whitespace_only(line).not() && line.starts_with(' ').not());
+    let anchor_line = match has_unindented_line {
+        true => None,
+        false => lines.iter().position(|line| line.starts_with(' ')),
+    };
+
+    let mut content = Vec::with_capacity(lines.len() * 2);
+    for (index, line) in lines.iter().enumerate() {
+        let is_last_line = index == last_line;
+
+        if is_last_line && line.is_empty() {
+            break;
+        }
+
+        let mut escaped_line = String::with_capacity(line.len());
+        let escape_leading_space = anchor_line == Some(index) || (is_last_line && is_whitespace_only(line));
+        escape_indented_string_line(line, escape_leading_space, is_last_line, &mut escaped_line);
+
+        if is_last_line {
+            escaped_line.push_str("''");
+        }
+
+        content.push(Doc::HardLine);
+        content.push(Doc::text(escaped_line));
+    }
+
+    let mut docs = vec![Doc::text(result), Doc::nest(indentation_increment, Doc::Concat(content))];
+    if lines[last_line].is_empty() {
+        docs.push(Doc::HardLine);
+        docs.push(Doc::text("''"));
+    }
+
+    Doc::Concat(docs)
+}
+
+fn escape_indented_string_line(line: &str, escape_leading_space: bool, is_last_line: bool, result: &mut String) {
+    let mut chars = line.char_indices();
+
+    if escape_leading_space && line.starts_with(' ') {
+        chars.next();
+        result.push_str(r"''\ ");
+    }
+
+    while let Some((index, c)) = chars.next() {
+        let rest = &line[index + c.len_utf8()..];
+        match c {
+            '\'' if rest.starts_with('\'') => {
+                chars.next();
+                result.push_str("'''");
+            }
+            // a single quote directly before the closing quotes or another escape sequence would merge with them
+            '\'' if (is_last_line && rest.is_empty()) || rest.starts_with("${") || rest.starts_with('\r') => result.push_str(r"''\'"),
+            '$' if rest.starts_with('{') => {
+                chars.next();
+                result.push_str("''${");
+            }
+            '\r' => result.push_str(r"''\r"),
+            _ => result.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::doc::render;
+
+    // renders the indented string at the given position, like the first line of an expression
+    fn escape_indented_string(value: &str, indentation: usize, indentation_increment: usize) -> String {
+        render(&indented_string(value, indentation_increment), indentation, None)[indentation..].to_string()
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(r#""""#, escape_string(""));
+    }
+
+    #[test]
+    fn no_escape() {
+        let values = ["abc", "Hello, world!", "a'b", "a`b", "ä ö ü 🦀", "{ }", "#", "/* */", "''"];
+        for value in values {
+            let expected = format!("\"{}\"", value);
+            assert_eq!(expected, escape_string(value));
+        }
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(r#""\"""#, escape_string("\""));
+        assert_eq!(r#""say \"hello\"""#, escape_string("say \"hello\""));
+    }
+
+    #[test]
+    fn backslashes() {
+        assert_eq!(r#""\\""#, escape_string(r"\"));
+        assert_eq!(r#""C:\\Windows\\System32""#, escape_string(r"C:\Windows\System32"));
+        assert_eq!(r#""^\\d+\\.\\d+$""#, escape_string(r"^\d+\.\d+$"));
+        assert_eq!(r#""\\n""#, escape_string(r"\n"));
+        assert_eq!(r#""\\\"""#, escape_string(r#"\""#));
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(r#""\${HOME}""#, escape_string("${HOME}"));
+        assert_eq!(r#""echo \${a} \${b}""#, escape_string("echo ${a} ${b}"));
+        assert_eq!(r#""$\${x}""#, escape_string("$${x}"));
+        assert_eq!(r#""\\\${x}""#, escape_string(r"\${x}"));
+    }
+
+    #[test]
+    fn dollar_without_interpolation() {
+        let values = ["$", "$HOME", "$$", "a$", "$ {", "$(pwd)", "}$"];
+        for value in values {
+            let expected = format!("\"{}\"", value);
+            assert_eq!(expected, escape_string(value));
+        }
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(r#""\n""#, escape_string("\n"));
+        assert_eq!(r#""\r""#, escape_string("\r"));
+        assert_eq!(r#""\t""#, escape_string("\t"));
+        assert_eq!(r#""line 1\r\nline 2\n""#, escape_string("line 1\r\nline 2\n"));
+        assert_eq!(r#""key\tvalue""#, escape_string("key\tvalue"));
+    }
+
+    #[test]
+    fn other_control_characters() {
+        let control_characters = (0x00..0x20).chain(0x7f..0xa0).map(|c| char::from_u32(c).unwrap());
+        for c in control_characters.filter(|c| !matches!(c, '\n' | '\r' | '\t')) {
+            let expected = format!("\"a{}b\"", c);
+            assert_eq!(expected, escape_string(&format!("a{}b", c)));
+        }
+        assert_eq!("\"$\u{1b}[0m\"", escape_string("$\u{1b}[0m"));
+    }
+
+    #[test]
+    fn mixed() {
+        let input = "#!/bin/sh\necho \"${USER}\" > \\dev\\null\t$1\r";
+        let expected = r##""#!/bin/sh\necho \"\${USER}\" > \\dev\\null\t$1\r""##;
+        assert_eq!(expected, escape_string(input));
+    }
+
+    #[test]
+    fn indented_single_line() {
+        assert_eq!(r#"''say "hello"''"#, escape_indented_string(r#"say "hello""#, 0, 2));
+        assert_eq!(r#"''"a" \ "b"''"#, escape_indented_string(r#""a" \ "b""#, 4, 2));
+    }
+
+    #[test]
+    fn indented_multi_line() {
+        let input = "line 1\n  line 2\n\nline 4";
+        let expected = "''\n  line 1\n    line 2\n\n  line 4''";
+        assert_eq!(expected, escape_indented_string(input, 0, 2));
+    }
+
+    #[test]
+    fn indented_trailing_newline() {
+        let input = "line 1\nline 2\n";
+        let expected = "''\n      line 1\n      line 2\n    ''";
+        assert_eq!(expected, escape_indented_string(input, 4, 2));
+    }
+
+    #[test]
+    fn indented_no_indentation_increment() {
+        let input = "#!/bin/sh\necho hello\n";
+        let expected = "''\n  #!/bin/sh\n  echo hello\n  ''";
+        assert_eq!(expected, escape_indented_string(input, 2, 0));
+    }
+
+    #[test]
+    fn indented_escapes() {
+        let values_and_expected = [
+            ("${HOME}", "''''${HOME}''"),
+            ("$HOME", "''$HOME''"),
+            ("$${x}", "''$''${x}''"),
+            ("a''b", "''a'''b''"),
+            ("a'''b", "''a''''b''"),
+            ("a'b", "''a'b''"),
+            ("a'", r"''a''\'''"),
+            ("a''", "''a'''''"),
+            ("a'''", r"''a'''''\'''"),
+            ("'${x}", r"''''\'''${x}''"),
+            ("''${x}", "'''''''${x}''"),
+            ("a\r", r"''a''\r''"),
+            ("a'\r", r"''a''\'''\r''"),
+            (r"a\b", r"''a\b''"),
+            (r"''\", r"'''''\''"),
+            ("\t", "''\t''"),
+            ("\u{1b}[0m", "''\u{1b}[0m''"),
+            ("$\u{7}", "''$\u{7}''"),
+            ("'\u{7}", "'''\u{7}''"),
+        ];
+        for (value, expected) in values_and_expected {
+            assert_eq!(expected, escape_indented_string(value, 0, 2), "value: {:?}", value);
+        }
+    }
+
+    #[test]
+    fn indented_single_quote_at_line_end() {
+        let input = "it's'\nfine'";
+        let expected = "''\n  it's'\n  fine''\\'''";
+        assert_eq!(expected, escape_indented_string(input, 0, 2));
+    }
+
+    #[test]
+    fn indented_leading_spaces() {
+        assert_eq!(r"''''\  a''", escape_indented_string("  a", 0, 2));
+
+        let input = "  a\n    b\n";
+        let expected = "''\n  ''\\  a\n      b\n''";
+        assert_eq!(expected, escape_indented_string(input, 0, 2));
+
+        let input = "\n   \n";
+        let expected = "''\n\n  ''\\   \n''";
+        assert_eq!(expected, escape_indented_string(input, 0, 2));
+    }
+
+    #[test]
+    fn indented_trailing_spaces() {
+        let input = "a\n  ";
+        let expected = "''\n  a\n  ''\\  ''";
+        assert_eq!(expected, escape_indented_string(input, 0, 2));
+
+        let input = "a  \n  b  ";
+        let expected = "''\n  a  \n    b  ''";
+        assert_eq!(expected, escape_indented_string(input, 0, 2));
+    }
+
+    #[test]
+    fn interpolated() {
+        let parts = [
+            StringPart::Literal("\"a\" $".to_string()),
+            StringPart::Interpolation("pkgs.hello".to_string()),
+            StringPart::Literal("/bin/${x}".to_string()),
+        ];
+        assert_eq!(r#""\"a\" \$${pkgs.hello}/bin/\${x}""#, interpolated_string(&parts));
+    }
+}
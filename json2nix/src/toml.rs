@@ -0,0 +1,183 @@
+use ::toml::{
+    Table, Value as TomlValue,
+    value::{Datetime, Offset},
+};
+use serde_json::{Map, Number, Value};
+
+use crate::{Json2NixError, ParseErrorCategory, TomlDatetimes};
+
+/// Parses a TOML document, which is always a table.
+pub fn parse_toml(input: &str, datetimes: TomlDatetimes) -> Result<Value, Json2NixError> {
+    let table = input.parse::<Table>().map_err(|err| parse_error(input, err))?;
+    table_to_json(table, datetimes)
+}
+
+fn table_to_json(table: Table, datetimes: TomlDatetimes) -> Result<Value, Json2NixError> {
+    let mut object = Map::new();
+    for (key, value) in table {
+        object.insert(key, to_json(value, datetimes)?);
+    }
+    Ok(Value::Object(object))
+}
+
+fn to_json(value: TomlValue, datetimes: TomlDatetimes) -> Result<Value, Json2NixError> {
+    Ok(match value {
+        TomlValue::String(string) => Value::String(string),
+        TomlValue::Integer(integer) => Value::from(integer),
+        TomlValue::Float(float) => Value::Number(Number::from_f64(float).ok_or_else(|| Json2NixError::Parse {
+            line: 0,
+            column: 0,
+            category: ParseErrorCategory::Data,
+            message: format!("the float {} cannot be represented in Nix", float),
+        })?),
+        TomlValue::Boolean(bool) => Value::Bool(bool),
+        TomlValue::Datetime(datetime) => datetime_to_json(datetime, datetimes),
+        // arrays of tables are arrays as well
+        TomlValue::Array(array) => Value::Array(array.into_iter().map(|value| to_json(value, datetimes)).collect::<Result<_, _>>()?),
+        TomlValue::Table(table) => table_to_json(table, datetimes)?,
+    })
+}
+
+fn datetime_to_json(datetime: Datetime, datetimes: TomlDatetimes) -> Value {
+    match datetimes {
+        TomlDatetimes::String => Value::String(datetime.to_string()),
+        TomlDatetimes::AttrSet => {
+            let mut object = Map::new();
+            if let Some(date) = datetime.date {
+                object.insert("year".to_string(), date.year.into());
+                object.insert("month".to_string(), date.month.into());
+                object.insert("day".to_string(), date.day.into());
+            }
+            if let Some(time) = datetime.time {
+                object.insert("hour".to_string(), time.hour.into());
+                object.insert("minute".to_string(), time.minute.into());
+                object.insert("second".to_string(), time.second.unwrap_or(0).into());
+                object.insert("nanosecond".to_string(), time.nanosecond.unwrap_or(0).into());
+            }
+            if let Some(offset) = datetime.offset {
+                let minutes = match offset {
+                    Offset::Z => 0,
+                    Offset::Custom { minutes } => minutes,
+                };
+                object.insert("offsetMinutes".to_string(), minutes.into());
+            }
+            Value::Object(object)
+        }
+    }
+}
+
+fn parse_error(input: &str, err: ::toml::de::Error) -> Json2NixError {
+    let message = err.message().trim_end();
+    match err.span() {
+        Some(span) => Json2NixError::parse_at(input, span.start, ParseErrorCategory::Syntax, message),
+        None => Json2NixError::Parse {
+            line: 0,
+            column: 0,
+            category: ParseErrorCategory::Syntax,
+            message: message.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(input: &str) -> Value {
+        parse_toml(input, TomlDatetimes::String).unwrap()
+    }
+
+    #[test]
+    fn scalars() {
+        let input = r#"
+string = "a\tb"
+literal = 'C:\path'
+multi_line = """
+first
+second"""
+integer = 0xff
+negative = -1_000
+float = 6.626e-34
+bool = true
+"#;
+        assert_eq!(
+            json!({ "string": "a\tb", "literal": "C:\\path", "multi_line": "first\nsecond", "integer": 255, "negative": -1000, "float": 6.626e-34, "bool": true }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let input = r#"
+title = "starship"
+
+[character]
+success_symbol = "[➜](bold green)"
+
+[git_status.style]
+bold = true
+
+[[languages]]
+name = "rust"
+indent = { tab-width = 4, unit = "    " }
+
+[[languages]]
+name = "nix"
+"#;
+        let expected = json!({
+            "title": "starship",
+            "character": { "success_symbol": "[➜](bold green)" },
+            "git_status": { "style": { "bold": true } },
+            "languages": [
+                { "name": "rust", "indent": { "tab-width": 4, "unit": "    " } },
+                { "name": "nix" },
+            ],
+        });
+        assert_eq!(expected, parse(input));
+    }
+
+    #[test]
+    fn preserves_order() {
+        let keys = parse("b = 1\na = 2\nc = 3").as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(vec!["b", "a", "c"], keys);
+    }
+
+    #[test]
+    fn datetimes_as_strings() {
+        let input = "offset = 1979-05-27T07:32:00.5-07:00\nlocal = 1979-05-27T07:32:00\ndate = 1979-05-27\ntime = 07:32:00";
+        assert_eq!(
+            json!({ "offset": "1979-05-27T07:32:00.5-07:00", "local": "1979-05-27T07:32:00", "date": "1979-05-27", "time": "07:32:00" }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn datetimes_as_attr_sets() {
+        let input = "offset = 1979-05-27T07:32:00.5Z\ndate = 1979-05-27\ntime = 07:32:00";
+        let expected = json!({
+            "offset": { "year": 1979, "month": 5, "day": 27, "hour": 7, "minute": 32, "second": 0, "nanosecond": 500000000, "offsetMinutes": 0 },
+            "date": { "year": 1979, "month": 5, "day": 27 },
+            "time": { "hour": 7, "minute": 32, "second": 0, "nanosecond": 0 },
+        });
+        assert_eq!(expected, parse_toml(input, TomlDatetimes::AttrSet).unwrap());
+    }
+
+    #[test]
+    fn syntax_error() {
+        let err = parse_toml("a = 1\nb = [1, 2", TomlDatetimes::String).unwrap_err();
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                line: 2,
+                category: ParseErrorCategory::Syntax,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn duplicate_key() {
+        assert!(parse_toml("a = 1\na = 2", TomlDatetimes::String).is_err());
+    }
+}
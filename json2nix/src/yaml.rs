@@ -0,0 +1,202 @@
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use serde_yaml_ng::{Deserializer, Value as YamlValue};
+
+use crate::{Json2NixError, ParseErrorCategory};
+
+/// Parses a YAML stream. A single document is returned as is, multiple documents are returned as a list.
+pub fn parse_yaml(input: &str) -> Result<Value, Json2NixError> {
+    let mut documents = Vec::new();
+
+    for document in Deserializer::from_str(input) {
+        let mut document = YamlValue::deserialize(document)?;
+        // resolves merge keys (`<<: *anchor`), which are common in docker-compose files and CI workflows
+        document.apply_merge()?;
+        documents.push(to_json(document)?);
+    }
+
+    Ok(match documents.len() {
+        0 => Value::Null,
+        1 => documents.remove(0),
+        _ => Value::Array(documents),
+    })
+}
+
+fn to_json(value: YamlValue) -> Result<Value, Json2NixError> {
+    Ok(match value {
+        YamlValue::Null => Value::Null,
+        YamlValue::Bool(bool) => Value::Bool(bool),
+        YamlValue::Number(number) => {
+            if let Some(integer) = number.as_i64() {
+                Value::from(integer)
+            } else if let Some(integer) = number.as_u64() {
+                Value::from(integer)
+            } else {
+                let float = number.as_f64().expect("a YAML number is an integer or a float");
+                Value::Number(Number::from_f64(float).ok_or_else(|| data_error(format!("the float {} cannot be represented in Nix", number)))?)
+            }
+        }
+        YamlValue::String(string) => Value::String(string),
+        YamlValue::Sequence(sequence) => Value::Array(sequence.into_iter().map(to_json).collect::<Result<_, _>>()?),
+        YamlValue::Mapping(mapping) => {
+            let mut object = Map::new();
+            for (key, value) in mapping {
+                object.insert(key_to_string(key)?, to_json(value)?);
+            }
+            Value::Object(object)
+        }
+        // tags like `!Ref` have no meaning in Nix, so only the value is kept
+        YamlValue::Tagged(tagged) => to_json(tagged.value)?,
+    })
+}
+
+// Nix attribute names are always strings, so scalar keys are converted like YAML would print them.
+fn key_to_string(key: YamlValue) -> Result<String, Json2NixError> {
+    match key {
+        YamlValue::Null => Ok("null".to_string()),
+        YamlValue::Bool(bool) => Ok(bool.to_string()),
+        YamlValue::Number(number) => Ok(number.to_string()),
+        YamlValue::String(string) => Ok(string),
+        YamlValue::Tagged(tagged) => key_to_string(tagged.value),
+        YamlValue::Sequence(_) | YamlValue::Mapping(_) => Err(data_error("sequences and mappings cannot be used as keys")),
+    }
+}
+
+// Values are converted after parsing, so there is no position to report.
+fn data_error(message: impl Into<String>) -> Json2NixError {
+    Json2NixError::Parse {
+        line: 0,
+        column: 0,
+        category: ParseErrorCategory::Data,
+        message: message.into(),
+    }
+}
+
+impl From<serde_yaml_ng::Error> for Json2NixError {
+    fn from(err: serde_yaml_ng::Error) -> Self {
+        let (line, column) = err.location().map_or((0, 0), |location| (location.line(), location.column()));
+
+        // like for serde_json, the position is removed from the message as it is already part of the error
+        let message = err.to_string();
+        let message = match message.find(" at line ") {
+            Some(index) => message[..index].to_string(),
+            None => message,
+        };
+
+        Json2NixError::Parse {
+            line,
+            column,
+            category: ParseErrorCategory::Syntax,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(input: &str) -> Value {
+        parse_yaml(input).unwrap()
+    }
+
+    #[test]
+    fn scalars() {
+        let input = "null: ~\nbool: true\nyaml11_bool: yes\nint: 0x10\nfloat: 1.5\nstring: 'a'\nlarge: 18446744073709551615";
+        assert_eq!(
+            json!({ "null": null, "bool": true, "yaml11_bool": "yes", "int": 16, "float": 1.5, "string": "a", "large": 18446744073709551615u64 }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let input = "
+services:
+  web:
+    image: nginx
+    ports:
+      - 80:80
+      - '443:443'
+    environment:
+      - KEY=value
+";
+        assert_eq!(
+            json!({ "services": { "web": { "image": "nginx", "ports": ["80:80", "443:443"], "environment": ["KEY=value"] } } }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn block_scalars() {
+        let input = "script: |\n  echo a\n  echo b\nfolded: >\n  a\n  b\n";
+        assert_eq!(json!({ "script": "echo a\necho b\n", "folded": "a b\n" }), parse(input));
+    }
+
+    #[test]
+    fn anchors_and_merge_keys() {
+        let input = "
+base: &base
+  a: 1
+  b: 2
+derived:
+  <<: *base
+  b: 3
+list: [*base]
+";
+        assert_eq!(
+            json!({ "base": { "a": 1, "b": 2 }, "derived": { "b": 3, "a": 1 }, "list": [{ "a": 1, "b": 2 }] }),
+            parse(input)
+        );
+    }
+
+    #[test]
+    fn non_string_keys() {
+        assert_eq!(
+            json!({ "1": "a", "true": "b", "null": "c", "1.5": "d" }),
+            parse("1: a\ntrue: b\n~: c\n1.5: d")
+        );
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(json!({ "a": "b", "c": 1 }), parse("a: !Ref b\nc: !!int 1"));
+    }
+
+    #[test]
+    fn multiple_documents() {
+        assert_eq!(json!([{ "a": 1 }, [2], null]), parse("a: 1\n---\n- 2\n---\n"));
+        assert_eq!(json!({ "a": 1 }), parse("---\na: 1\n"));
+        assert_eq!(json!(null), parse(""));
+    }
+
+    #[test]
+    fn infinity() {
+        assert!(matches!(
+            parse_yaml("a: .inf"),
+            Err(Json2NixError::Parse {
+                category: ParseErrorCategory::Data,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn syntax_error() {
+        let err = parse_yaml("a: 1\nb: [1, 2\n").unwrap_err();
+        assert!(matches!(
+            err,
+            Json2NixError::Parse {
+                line: 3,
+                column: 1,
+                category: ParseErrorCategory::Syntax,
+                ..
+            }
+        ));
+        assert_eq!(
+            "Could not parse the input, syntax error at line 3, column 1: did not find expected ',' or ']'",
+            err.to_string()
+        );
+    }
+}
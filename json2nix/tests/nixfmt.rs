@@ -0,0 +1,81 @@
+//! Golden tests for [`OutputStyle::Nixfmt`]: every `tests/nixfmt/<name>.json` is converted and compared to
+//! `tests/nixfmt/<name>.nix`, which is exactly what `nixfmt` produces for it.
+
+use std::{fs, path::PathBuf};
+
+use json2nix::{Json2NixConfig, OutputStyle, StringStyle, json2nix};
+
+fn nixfmt_config() -> Json2NixConfig {
+    Json2NixConfig {
+        style: OutputStyle::Nixfmt,
+        ..Json2NixConfig::default()
+    }
+}
+
+fn golden(name: &str, config: Json2NixConfig) {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("nixfmt");
+    let input = fs::read_to_string(directory.join(format!("{}.json", name))).unwrap();
+    let expected = fs::read_to_string(directory.join(format!("{}.nix", name))).unwrap();
+
+    assert_eq!(expected, json2nix(&input, &config).unwrap());
+}
+
+#[test]
+fn scalars() {
+    golden("scalars", nixfmt_config());
+}
+
+#[test]
+fn lists() {
+    golden("lists", nixfmt_config());
+}
+
+#[test]
+fn sets() {
+    golden("sets", nixfmt_config());
+}
+
+#[test]
+fn single() {
+    golden("single", nixfmt_config());
+}
+
+#[test]
+fn empty() {
+    golden("empty", nixfmt_config());
+}
+
+#[test]
+fn long() {
+    golden("long", nixfmt_config());
+}
+
+#[test]
+fn indented_strings() {
+    let config = Json2NixConfig {
+        string_style: StringStyle::Indented {
+            double_quote_threshold: None,
+        },
+        ..nixfmt_config()
+    };
+    golden("indented_strings", config);
+}
+
+#[test]
+fn compact_set_keys() {
+    let config = Json2NixConfig {
+        compact_set_keys: true,
+        ..nixfmt_config()
+    };
+    golden("compact_set_keys", config);
+}
+
+#[test]
+fn ignores_layout_options() {
+    let config = Json2NixConfig {
+        indentation_increment: 4,
+        max_line_width: Some(10),
+        ..nixfmt_config()
+    };
+    golden("lists", config);
+}
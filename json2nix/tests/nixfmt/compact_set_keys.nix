@@ -0,0 +1,11 @@
+{
+  services.nginx.enable = true;
+  networking.firewall.allowedTCPPorts = [
+    80
+    443
+  ];
+  users.users.alice = {
+    isNormalUser = true;
+    extraGroups = [ "wheel" ];
+  };
+}
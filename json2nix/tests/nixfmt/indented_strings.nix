@@ -0,0 +1,16 @@
+{
+  script = ''
+    #!/bin/sh
+    echo hi
+  '';
+  inline = ''
+    a
+    b'';
+  list = [
+    ''
+      x
+      y
+    ''
+  ];
+  plain = "no newline";
+}
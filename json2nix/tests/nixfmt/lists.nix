@@ -0,0 +1,27 @@
+{
+  empty = [ ];
+  single = [ 1 ];
+  multiple = [
+    1
+    2
+  ];
+  negative = [ (-1) ];
+  nested = [
+    [ 1 ]
+    [
+      2
+      3
+    ]
+    [ ]
+  ];
+  sets = [
+    { a = 1; }
+    { }
+  ];
+  single_set = [
+    {
+      a = 1;
+      b = 2;
+    }
+  ];
+}
@@ -0,0 +1,14 @@
+{
+  description =
+    "A description that is long enough to not fit onto the line of its attribute anymore, so it moves";
+  fits = "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
+  tight =
+    "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
+  list = [
+    "an element that is long enough to not fit onto a single line together with the brackets of the list"
+  ];
+  set = {
+    description =
+      "A description that is long enough to not fit onto the line of its attribute anymore, so it moves";
+  };
+}
@@ -0,0 +1,10 @@
+{
+  null = null;
+  bool = true;
+  int = 42;
+  negative = -1;
+  float = 1.5;
+  exponent = 1.0e100;
+  string = "hello \"world\" \${x}";
+  escapes = "tab\tnewline\nbackslash\\";
+}
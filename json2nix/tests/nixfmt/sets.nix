@@ -0,0 +1,15 @@
+{
+  empty = { };
+  single = { a = 1; };
+  multiple = {
+    a = 1;
+    b = 2;
+  };
+  nested = { x = { y = { z = true; }; }; };
+  "quoted keys" = {
+    "1" = 1;
+    "\${x}" = 2;
+    "a.b" = 3;
+    or = 4;
+  };
+}
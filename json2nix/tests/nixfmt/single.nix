@@ -0,0 +1 @@
+{ enable = true; }